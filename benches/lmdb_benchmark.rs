@@ -355,7 +355,7 @@ fn benchmark<T: BenchDatabase>(mut db: T) -> Vec<(&'static str, Duration)> {
 fn main() {
     let redb_results = {
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        let db = unsafe { redb::Database::create(tmpfile.path(), 4096 * 1024 * 1024).unwrap() };
+        let db = redb::Database::create(tmpfile.path(), 4096 * 1024 * 1024).unwrap();
         // let table = RedbBenchDatabase::new(&db);
         benchmark_redb(db)
     };
@@ -12,7 +12,7 @@ const TABLE_DEF: TableDefinition<u64, [u8]> = TableDefinition::new("fuzz_table")
 
 fuzz_target!(|config: FuzzConfig| {
     let redb_file: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(redb_file.path(), config.max_db_size.value) };
+    let db = Database::create(redb_file.path(), config.max_db_size.value);
 
     if matches!(db, Err(Error::OutOfSpace)) {
         return;
@@ -47,11 +47,16 @@ fuzz_target!(|config: FuzzConfig| {
                         let key = key.value;
                         let value_size = value_size.value as usize;
                         let value = vec![0xFF; value_size];
-                        if let Err(err) = table.insert(&key, &value) {
-                            assert!(matches!(err, Error::OutOfSpace) && config.oom_plausible());
-                            return;
+                        match table.insert(&key, &value) {
+                            Ok(old_value) => {
+                                let reference_len = reference.insert(key, value_size);
+                                assert_eq!(old_value.map(|x| x.to_value().unwrap().len()), reference_len);
+                            },
+                            Err(err) => {
+                                assert!(matches!(err, Error::OutOfSpace) && config.oom_plausible());
+                                return;
+                            }
                         }
-                        reference.insert(key, value_size);
                     },
                     FuzzOperation::Remove { key } => {
                         let key = key.value;
@@ -59,7 +64,7 @@ fuzz_target!(|config: FuzzConfig| {
                             Some(reference_len) => {
                                 match table.remove(&key) {
                                     Ok(value) => {
-                                        assert_eq!(value.unwrap().to_value().len(), reference_len);
+                                        assert_eq!(value.unwrap().to_value().unwrap().len(), reference_len);
                                     },
                                     Err(err) => {
                                         assert!(matches!(err, Error::OutOfSpace) && config.oom_plausible());
@@ -78,18 +83,18 @@ fuzz_target!(|config: FuzzConfig| {
                     FuzzOperation::Range { start_key, len, reversed } => {
                         let start = start_key.value;
                         let end = start + len.value;
-                        let mut reference_iter: Box<dyn Iterator<Item=(&u64, &usize)>> = if *reversed {
+                        let reference_iter: Box<dyn Iterator<Item=(&u64, &usize)>> = if *reversed {
                             Box::new(reference.range(start..end).rev())
                         } else {
                             Box::new(reference.range(start..end))
                         };
-                        let mut iter = if *reversed {
-                            table.range(start..end).unwrap().rev()
+                        let mut iter: Box<dyn Iterator<Item=Result<(u64, &[u8]), Error>>> = if *reversed {
+                            Box::new(table.range(start..end).unwrap().rev())
                         } else {
-                            table.range(start..end).unwrap()
+                            Box::new(table.range(start..end).unwrap())
                         };
-                        while let Some((ref_key, ref_value_len)) = reference_iter.next() {
-                            let (key, value) = iter.next().unwrap();
+                        for (ref_key, ref_value_len) in reference_iter {
+                            let (key, value) = iter.next().unwrap().unwrap();
                             assert_eq!(*ref_key, key);
                             assert_eq!(*ref_value_len, value.len());
                         }
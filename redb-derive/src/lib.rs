@@ -0,0 +1,268 @@
+//! Derive macros for redb's `RedbValue` and `RedbKey` traits
+//!
+//! These are re-exported by redb when its `derive` feature is enabled, and should be used from
+//! there rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, Index, Lit, Meta, NestedMeta, Type,
+};
+
+/// Derives `RedbValue` and `FixedWidth` for a struct whose fields all implement `FixedWidth`,
+/// such as integers, `bool`, `char`, byte arrays, `Pod` types, tuples of these, or other derived
+/// structs. Variable-width fields, such as `String`, fail to compile
+///
+/// The fields are stored one after another, in declaration order, and values are read back as
+/// an owned copy of the struct, so it must also implement `Debug`.
+///
+/// The type name is the path of the struct, such as `my_crate::models::User`, so that a table
+/// can't be opened with a struct of the same name from another module. It does not change if the
+/// fields do. It can be set explicitly with `#[redb(type_name = "User")]`, for example so that
+/// the struct can be moved without changing it.
+#[proc_macro_derive(RedbValue, attributes(redb))]
+pub fn derive_redb_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, redb_value).into()
+}
+
+/// Derives `RedbKey` for a struct which also derives `RedbValue`, ordering by each field in turn,
+/// in declaration order
+#[proc_macro_derive(RedbKey)]
+pub fn derive_redb_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, redb_key).into()
+}
+
+struct Field {
+    // Expression which accesses the field on `self`
+    access: TokenStream2,
+    ty: Type,
+}
+
+fn expand(input: &DeriveInput, f: fn(&DeriveInput, &[Field]) -> TokenStream2) -> TokenStream2 {
+    match struct_fields(input) {
+        Ok(fields) => f(input, &fields),
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn struct_fields(input: &DeriveInput) -> Result<Vec<Field>, Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "redb types cannot have generic parameters",
+        ));
+    }
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "redb types can only be derived for structs",
+            ))
+        }
+    };
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let name = field.ident.as_ref().unwrap();
+                Field {
+                    access: quote!(#name),
+                    ty: field.ty.clone(),
+                }
+            })
+            .collect(),
+        Fields::Unnamed(fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let index = Index::from(i);
+                Field {
+                    access: quote!(#index),
+                    ty: field.ty.clone(),
+                }
+            })
+            .collect(),
+        Fields::Unit => vec![],
+    };
+    Ok(fields)
+}
+
+// Returns an expression for the type name, which is the path of the struct unless it's set with
+// a #[redb(type_name = "...")] attribute
+fn type_name(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let mut type_name = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("redb")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "expected #[redb(type_name = \"...\")]",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("type_name") => {
+                    match value.lit {
+                        Lit::Str(name) if type_name.is_none() => type_name = Some(name),
+                        Lit::Str(name) => {
+                            return Err(Error::new_spanned(name, "type_name is set more than once"))
+                        }
+                        lit => return Err(Error::new_spanned(lit, "type_name must be a string")),
+                    }
+                }
+                nested => return Err(Error::new_spanned(nested, "unknown redb attribute")),
+            }
+        }
+    }
+    if let Some(name) = type_name {
+        Ok(quote!(#name))
+    } else {
+        let name = input.ident.to_string();
+        Ok(quote!(::std::concat!(::std::module_path!(), "::", #name)))
+    }
+}
+
+fn redb_value(input: &DeriveInput, fields: &[Field]) -> TokenStream2 {
+    let name = &input.ident;
+    let type_name = match type_name(input) {
+        Ok(type_name) => type_name,
+        Err(err) => return err.to_compile_error(),
+    };
+    let types: Vec<&Type> = fields.iter().map(|field| &field.ty).collect();
+    let accesses: Vec<&TokenStream2> = fields.iter().map(|field| &field.access).collect();
+    // Fields are read as owned values, since some types, such as byte arrays, are otherwise read
+    // as a reference into the page, and they may not be aligned
+    let reads = types.iter().map(|ty| {
+        quote! {{
+            let width = <#ty as ::redb::RedbValue>::fixed_width()
+                .expect("fields of derived redb types must have a fixed width");
+            let value = <#ty as ::redb::FixedWidth>::from_unaligned_bytes(
+                &data[offset..(offset + width)],
+            )?;
+            offset += width;
+            value
+        }}
+    });
+    let construct = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(_) => quote!(#name { #(#accesses: #reads,)* }),
+            Fields::Unnamed(_) => quote!(#name(#(#reads,)*)),
+            Fields::Unit => quote!(#name),
+        },
+        _ => unreachable!(),
+    };
+
+    quote! {
+        impl ::redb::RedbValue for #name {
+            type View = ::redb::OwnedLifetime<#name>;
+            type ToBytes = ::redb::OwnedAsBytesLifetime<::std::vec::Vec<u8>>;
+
+            fn from_bytes(data: &[u8]) -> <Self::View as ::redb::WithLifetime>::Out {
+                <Self as ::redb::RedbValue>::try_from_bytes(data).unwrap()
+            }
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn try_from_bytes(
+                data: &[u8],
+            ) -> ::std::result::Result<<Self::View as ::redb::WithLifetime>::Out, ::redb::Error> {
+                if ::std::option::Option::Some(data.len())
+                    != <Self as ::redb::RedbValue>::fixed_width()
+                {
+                    return ::std::result::Result::Err(::redb::Error::Corrupted(::std::format!(
+                        "{} bytes are not a valid {}",
+                        data.len(),
+                        <Self as ::redb::RedbValue>::redb_type_name()
+                    )));
+                }
+                let mut offset = 0;
+                ::std::result::Result::Ok(#construct)
+            }
+
+            fn as_bytes(&self) -> <Self::ToBytes as ::redb::AsBytesWithLifetime>::Out {
+                let mut output = ::std::vec::Vec::new();
+                #(
+                    output.extend_from_slice(
+                        ::std::convert::AsRef::<[u8]>::as_ref(
+                            &<#types as ::redb::RedbValue>::as_bytes(&self.#accesses),
+                        ),
+                    );
+                )*
+                output
+            }
+
+            fn fixed_width() -> ::std::option::Option<usize> {
+                let mut width = 0;
+                #(width += <#types as ::redb::RedbValue>::fixed_width()?;)*
+                ::std::option::Option::Some(width)
+            }
+
+            fn redb_type_name() -> &'static str {
+                #type_name
+            }
+        }
+
+        impl ::redb::FixedWidth for #name {
+            fn from_unaligned_bytes(
+                data: &[u8],
+            ) -> ::std::result::Result<Self, ::redb::Error> {
+                <Self as ::redb::RedbValue>::try_from_bytes(data)
+            }
+        }
+    }
+}
+
+fn redb_key(input: &DeriveInput, fields: &[Field]) -> TokenStream2 {
+    let name = &input.ident;
+    let types = fields.iter().map(|field| &field.ty);
+
+    quote! {
+        impl ::redb::RedbKey for #name {
+            fn compare(data1: &[u8], data2: &[u8]) -> ::std::cmp::Ordering {
+                <Self as ::redb::RedbKey>::try_compare(data1, data2).unwrap()
+            }
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn try_compare(
+                data1: &[u8],
+                data2: &[u8],
+            ) -> ::std::result::Result<::std::cmp::Ordering, ::redb::Error> {
+                for data in [data1, data2] {
+                    if ::std::option::Option::Some(data.len())
+                        != <Self as ::redb::RedbValue>::fixed_width()
+                    {
+                        return ::std::result::Result::Err(::redb::Error::Corrupted(
+                            ::std::format!(
+                                "{} bytes are not a valid {}",
+                                data.len(),
+                                <Self as ::redb::RedbValue>::redb_type_name()
+                            ),
+                        ));
+                    }
+                }
+                let mut offset = 0;
+                #(
+                    let width = <#types as ::redb::RedbValue>::fixed_width()
+                        .expect("fields of derived redb types must have a fixed width");
+                    let range = offset..(offset + width);
+                    match <#types as ::redb::RedbKey>::try_compare(
+                        &data1[range.clone()],
+                        &data2[range],
+                    )? {
+                        ::std::cmp::Ordering::Equal => {}
+                        ordering => return ::std::result::Result::Ok(ordering),
+                    }
+                    offset += width;
+                )*
+                ::std::result::Result::Ok(::std::cmp::Ordering::Equal)
+            }
+        }
+    }
+}
@@ -1,19 +1,23 @@
 use crate::tree_store::{
-    get_db_size, AllPageNumbersBtreeIter, BtreeRangeIter, InternalTableDefinition, PageNumber,
-    TransactionalMemory,
+    get_db_size, AllPageNumbersBtreeIter, Btree, BtreeRangeIter, IntegrityChecker, IntegrityReport,
+    InternalTableDefinition, PageNumber, SnapshotRecord, TransactionalMemory, MAX_CAPACITY,
 };
 use crate::types::RedbValue;
 use crate::Error;
 use crate::{ReadTransaction, Result, WriteTransaction};
-use std::collections::btree_set::BTreeSet;
-use std::fs::{File, OpenOptions};
+use std::cmp::min;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryInto;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
 use std::io::ErrorKind;
 use std::marker::PhantomData;
+use std::mem::size_of;
 use std::ops::RangeFull;
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
-use std::{io, panic};
+use std::{io, panic, process};
 
 pub(crate) type TransactionId = u64;
 type AtomicTransactionId = AtomicU64;
@@ -21,9 +25,16 @@ type AtomicTransactionId = AtomicU64;
 /// Defines the name and types of a table
 ///
 /// A [`TableDefinition`] should be opened for use by calling [`ReadTransaction::open_table`] or [`WriteTransaction::open_table`]
+///
+/// Each table also has a schema version, which is stored with it and must match when the table is
+/// opened. It defaults to zero, and should be increased whenever the format of the keys or values
+/// changes without a change to their type names, for example when a field is added to a type
+/// stored with `Bincode`. Tables are upgraded to a new version by
+/// [`WriteTransaction::migrate_table`]
 // TODO: add trait bounds once const_fn_trait_bound is stable
 pub struct TableDefinition<'a, K: ?Sized, V: ?Sized> {
     name: &'a str,
+    version: u64,
     _key_type: PhantomData<K>,
     _value_type: PhantomData<V>,
 }
@@ -33,14 +44,25 @@ impl<'a, K: ?Sized, V: ?Sized> TableDefinition<'a, K, V> {
         assert!(!name.is_empty());
         Self {
             name,
+            version: 0,
             _key_type: PhantomData,
             _value_type: PhantomData,
         }
     }
 
+    /// Sets the schema version of the table
+    pub const fn with_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
     pub fn name(&self) -> &str {
         self.name
     }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
 }
 
 impl<'a, K: ?Sized, V: ?Sized> Clone for TableDefinition<'a, K, V> {
@@ -57,8 +79,10 @@ impl<'a, K: ?Sized, V: ?Sized> Copy for TableDefinition<'a, K, V> {}
 ///
 /// [Multimap tables](https://en.wikipedia.org/wiki/Multimap) may have multiple values associated with each key
 ///
+/// Multimap tables have a schema version, in the same way as [`TableDefinition`]
 pub struct MultimapTableDefinition<'a, K: ?Sized, V: ?Sized> {
     name: &'a str,
+    version: u64,
     _key_type: PhantomData<K>,
     _value_type: PhantomData<V>,
 }
@@ -68,14 +92,25 @@ impl<'a, K: ?Sized, V: ?Sized> MultimapTableDefinition<'a, K, V> {
         assert!(!name.is_empty());
         Self {
             name,
+            version: 0,
             _key_type: PhantomData,
             _value_type: PhantomData,
         }
     }
 
+    /// Sets the schema version of the table
+    pub const fn with_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
     pub fn name(&self) -> &str {
         self.name
     }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
 }
 
 impl<'a, K: ?Sized, V: ?Sized> Clone for MultimapTableDefinition<'a, K, V> {
@@ -107,7 +142,7 @@ impl<'a, K: ?Sized, V: ?Sized> Copy for MultimapTableDefinition<'a, K, V> {}
 /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 /// # let filename = tmpfile.path();
 /// # let db_max_size = 1024 * 1024;
-/// let db = unsafe { Database::create(filename, db_max_size)? };
+/// let db = Database::create(filename, db_max_size)?;
 /// let write_txn = db.begin_write()?;
 /// {
 ///     let mut table = write_txn.open_table(TABLE)?;
@@ -120,7 +155,8 @@ impl<'a, K: ?Sized, V: ?Sized> Copy for MultimapTableDefinition<'a, K, V> {}
 pub struct Database {
     mem: TransactionalMemory,
     next_transaction_id: AtomicTransactionId,
-    live_read_transactions: Mutex<BTreeSet<TransactionId>>,
+    // Number of live read transactions, by the transaction whose pages they must retain
+    live_read_transactions: Mutex<BTreeMap<TransactionId, usize>>,
     live_write_transaction: Mutex<Option<TransactionId>>,
     leaked_write_transaction: Mutex<Option<&'static panic::Location<'static>>>,
 }
@@ -131,48 +167,74 @@ impl Database {
     /// * if the file is a valid redb database, it will be opened
     /// * otherwise this function will return an error
     ///
-    /// `db_size`: the maximum size in bytes of the database.
+    /// `db_size`: the maximum size in bytes of the database. Address space is only reserved as the
+    /// file grows, so this may be much larger than the expected size. Pass `usize::MAX` for a
+    /// database with no practical limit on its size.
     ///
-    /// # Safety
+    /// If the database already exists, its maximum size is raised to `db_size`. Returns
+    /// [`Error::DbSizeMismatch`] if `db_size` is smaller than the existing maximum, or
+    /// [`Error::DbSizeNotRaisable`] if the database header doesn't have room to raise it that far.
     ///
-    /// The file referenced by `path` must not be concurrently modified by any other process
-    pub unsafe fn create(path: impl AsRef<Path>, db_size: usize) -> Result<Database> {
-        let file = if path.as_ref().exists() && File::open(path.as_ref())?.metadata()?.len() > 0 {
-            let existing_size = get_db_size(path.as_ref())?;
-            if existing_size != db_size {
-                return Err(Error::DbSizeMismatch {
-                    path: path.as_ref().to_string_lossy().to_string(),
-                    size: existing_size,
-                    requested_size: db_size,
-                });
-            }
-            OpenOptions::new().read(true).write(true).open(path)?
-        } else {
-            OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(path)?
-        };
+    /// The file is locked while the database is open. Returns [`Error::DatabaseAlreadyOpen`] if it's
+    /// already in use, by this or another process
+    pub fn create(path: impl AsRef<Path>, db_size: usize) -> Result<Database> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path.as_ref())?;
 
-        Database::new(file, db_size, None, true)
+        Database::new(file, db_size, None, true)?.check_max_capacity(path.as_ref(), db_size)
     }
 
     /// Opens an existing redb database.
     ///
-    /// # Safety
-    ///
-    /// The file referenced by `path` must not be concurrently modified by any other process
-    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Database> {
-        if File::open(path.as_ref())?.metadata()?.len() > 0 {
-            let existing_size = get_db_size(path.as_ref())?;
-            let file = OpenOptions::new().read(true).write(true).open(path)?;
+    /// The file is locked while the database is open. Returns [`Error::DatabaseAlreadyOpen`] if it's
+    /// already in use, by this or another process
+    pub fn open(path: impl AsRef<Path>) -> Result<Database> {
+        // Only open the file once, since closing it would release the locks of any other instance
+        // of it in this process
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        if file.metadata()?.len() > 0 {
+            let existing_size = get_db_size(&file)?;
             Database::new(file, existing_size, None, true)
         } else {
             Err(Error::Io(io::Error::from(ErrorKind::InvalidData)))
         }
     }
 
+    /// Opens an existing redb database, without write access
+    ///
+    /// The database may be open for writing by another process at the same time. Each read
+    /// transaction sees the last durable commit made before it began. Any attempt to write returns
+    /// [`Error::ReadOnly`].
+    ///
+    /// Read transactions lock the commit that they read, so that the writer doesn't reuse its pages
+    /// until they are dropped. Long running reads therefore cause the file to grow, as they do when
+    /// made by the writer. Opening the database waits if the writer is truncating the file in
+    /// [`Database::compact`]
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Database> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Err(Error::Io(io::Error::from(ErrorKind::InvalidData)));
+        }
+        let mem = TransactionalMemory::new_read_only(file)?;
+        Database::from_memory(mem)
+    }
+
+    // Checks that the maximum size of the database matches the one requested when it was opened
+    fn check_max_capacity(self, path: &Path, requested_size: usize) -> Result<Self> {
+        let size = self.mem.get_max_capacity();
+        if size != min(requested_size, MAX_CAPACITY) {
+            return Err(Error::DbSizeMismatch {
+                path: path.to_string_lossy().to_string(),
+                size,
+                requested_size,
+            });
+        }
+        Ok(self)
+    }
+
     pub(crate) fn get_memory(&self) -> &TransactionalMemory {
         &self.mem
     }
@@ -185,36 +247,74 @@ impl Database {
     ) -> Result<Self> {
         let mem = TransactionalMemory::new(file, max_capacity, page_size, dynamic_growth)?;
         if mem.needs_repair()? {
-            let root = mem
-                .get_data_root()
-                .expect("Tried to repair an empty database");
+            let root = mem.get_data_root();
+            let freed_root = mem.get_freed_root();
+            let snapshot_root = mem.get_snapshot_root();
 
             // Repair the allocator state
-            // All pages in the master table
-            let mut all_pages_iter: Box<dyn Iterator<Item = PageNumber>> =
-                Box::new(AllPageNumbersBtreeIter::new(root, &mem));
-
-            // Iterate over all other tables
-            let mut iter: BtreeRangeIter<[u8], [u8]> =
-                BtreeRangeIter::new::<RangeFull, [u8]>(.., Some(root), &mem);
-
-            // Chain all the other tables to the master table iter
-            while let Some(entry) = iter.next() {
-                let definition = InternalTableDefinition::from_bytes(entry.value());
-                if let Some(table_root) = definition.get_root() {
-                    let table_pages_iter = AllPageNumbersBtreeIter::new(table_root, &mem);
-                    all_pages_iter = Box::new(all_pages_iter.chain(table_pages_iter));
+            // All pages in the master table, the tables it references, and the snapshot table
+            let mut pages = HashSet::new();
+            if let Some(root) = root {
+                collect_table_pages(root, &mem, &mut pages)?;
+            }
+            // Read-only instances may still be reading the pages pending in the freed table, so
+            // they must be freed by a later commit, once no read transaction can reference them
+            if let Some(freed_root) = freed_root {
+                for page in AllPageNumbersBtreeIter::new(freed_root, &mem)? {
+                    pages.insert(page?);
+                }
+                let iter: BtreeRangeIter<[u8], [u8]> =
+                    BtreeRangeIter::new::<RangeFull, [u8]>(.., Some(freed_root), &mem)?;
+                for entry in iter {
+                    let entry = entry?;
+                    let value = entry.value();
+                    let length =
+                        u64::from_le_bytes(value[..size_of::<u64>()].try_into().unwrap()) as usize;
+                    // 1..=length because the array is length prefixed
+                    for i in 1..=length {
+                        pages.insert(PageNumber::from_le_bytes(
+                            value[i * 8..(i + 1) * 8].try_into().unwrap(),
+                        ));
+                    }
                 }
             }
+            let mut snapshot_pages = HashSet::new();
+            if let Some(snapshot_root) = snapshot_root {
+                for page in AllPageNumbersBtreeIter::new(snapshot_root, &mem)? {
+                    pages.insert(page?);
+                }
+                let iter: BtreeRangeIter<str, SnapshotRecord> =
+                    BtreeRangeIter::new::<RangeFull, &str>(.., Some(snapshot_root), &mem)?;
+                for entry in iter {
+                    if let Some(snapshot) = SnapshotRecord::from_bytes(entry?.value()).root {
+                        collect_table_pages(snapshot, &mem, &mut snapshot_pages)?;
+                    }
+                }
+            }
+            // Pages that are only reachable from a snapshot
+            let retained: Vec<PageNumber> = snapshot_pages.difference(&pages).copied().collect();
 
-            mem.repair_allocator(all_pages_iter)?;
+            mem.repair_allocator(pages.iter().chain(retained.iter()).map(|page| Ok(*page)))?;
 
-            // Clear the freed table. We just rebuilt the allocator state by walking all the
-            // reachable data pages, which implicitly frees the pages for the freed table
+            // Commit the repaired allocator state
             let transaction_id = mem.get_last_committed_transaction_id()? + 1;
-            mem.commit(Some(root), None, transaction_id, false)?;
+            mem.commit(root, freed_root, snapshot_root, transaction_id, false)?;
+
+            if !retained.is_empty() {
+                // Record the pages retained by snapshots as freed, so that they're released
+                // once the snapshots are deleted
+                let db = Database::from_memory(mem)?;
+                let txn = db.begin_write()?;
+                txn.retain_snapshot_pages(retained);
+                txn.commit()?;
+                return Ok(db);
+            }
         }
 
+        Database::from_memory(mem)
+    }
+
+    fn from_memory(mem: TransactionalMemory) -> Result<Self> {
         let next_transaction_id = mem.get_last_committed_transaction_id()? + 1;
 
         Ok(Database {
@@ -235,7 +335,18 @@ impl Database {
     }
 
     pub(crate) fn deallocate_read_transaction(&self, id: TransactionId) {
-        self.live_read_transactions.lock().unwrap().remove(&id);
+        let mut live_reads = self.live_read_transactions.lock().unwrap();
+        let count = live_reads.get_mut(&id).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            live_reads.remove(&id);
+        }
+        drop(live_reads);
+        if self.mem.is_read_only() {
+            // If this fails, the writer won't reuse the pages of this commit until the database is
+            // closed, which is safe
+            let _ = self.mem.unpin(id);
+        }
     }
 
     pub(crate) fn deallocate_write_transaction(&self, id: TransactionId) {
@@ -244,13 +355,21 @@ impl Database {
         *live = None;
     }
 
-    pub(crate) fn oldest_live_read_transaction(&self) -> Option<TransactionId> {
-        self.live_read_transactions
+    // Includes read transactions of read-only instances, in this or other processes
+    pub(crate) fn oldest_live_read_transaction(&self) -> Result<Option<TransactionId>> {
+        let oldest = self
+            .live_read_transactions
             .lock()
             .unwrap()
-            .iter()
+            .keys()
             .next()
-            .cloned()
+            .cloned();
+        let end = oldest.unwrap_or_else(|| self.next_transaction_id.load(Ordering::Acquire));
+        if let Some(pinned) = self.mem.oldest_pinned_transaction(end)? {
+            return Ok(Some(pinned));
+        }
+
+        Ok(oldest)
     }
 
     /// Convenience method for [`DatabaseBuilder::new`]
@@ -263,6 +382,9 @@ impl Database {
     /// Returns a [`WriteTransaction`] which may be used to read/write to the database. Only a single
     /// write may be in progress at a time
     pub fn begin_write(&self) -> Result<WriteTransaction> {
+        if self.mem.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
         let guard = self.leaked_write_transaction.lock().unwrap();
         if let Some(leaked) = *guard {
             return Err(Error::LeakedWriteTransaction(leaked));
@@ -284,10 +406,260 @@ impl Database {
     /// Returns a [`ReadTransaction`] which may be used to read from the database. Read transactions
     /// may exist concurrently with writes
     pub fn begin_read(&self) -> Result<ReadTransaction> {
+        if self.mem.is_read_only() {
+            // Read the last commit made by the writer, which is identified by its transaction id
+            let (id, root, snapshot_root) = self.mem.pin_last_commit()?;
+            *self
+                .live_read_transactions
+                .lock()
+                .unwrap()
+                .entry(id)
+                .or_default() += 1;
+            return Ok(ReadTransaction::new(self, id, root, snapshot_root));
+        }
         let id = self.next_transaction_id.fetch_add(1, Ordering::AcqRel);
-        self.live_read_transactions.lock().unwrap().insert(id);
-        Ok(ReadTransaction::new(self, id))
+        *self
+            .live_read_transactions
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_default() += 1;
+        Ok(ReadTransaction::new(
+            self,
+            id,
+            self.mem.get_data_root(),
+            self.mem.get_snapshot_root(),
+        ))
     }
+
+    /// Begins a read transaction on the persistent snapshot with the given name
+    ///
+    /// The transaction sees the database as it was when the snapshot was created with
+    /// [`WriteTransaction::create_snapshot`]. It remains valid even if the snapshot is deleted
+    /// while the transaction is alive.
+    ///
+    /// Returns [`Error::SnapshotDoesNotExist`] if there is no snapshot with this name
+    pub fn begin_read_snapshot(&self, name: &str) -> Result<ReadTransaction> {
+        if self.mem.is_read_only() {
+            return self.begin_read_snapshot_read_only(name);
+        }
+        // Hold the lock until the snapshot is registered, so that a concurrent commit which
+        // deletes the snapshot can't release its pages
+        let mut live_reads = self.live_read_transactions.lock().unwrap();
+        let snapshot_root = self.mem.get_snapshot_root();
+        let snapshot = Btree::<str, SnapshotRecord>::new(snapshot_root, &self.mem)
+            .get(name)?
+            .ok_or_else(|| Error::SnapshotDoesNotExist(name.to_string()))?;
+        *live_reads.entry(snapshot.transaction_id).or_default() += 1;
+        drop(live_reads);
+
+        Ok(ReadTransaction::new(
+            self,
+            snapshot.transaction_id,
+            snapshot.root,
+            snapshot_root,
+        ))
+    }
+
+    fn begin_read_snapshot_read_only(&self, name: &str) -> Result<ReadTransaction> {
+        loop {
+            // Keep the commit pinned while we read its snapshot tree, and until the snapshot is
+            // pinned, since the writer may delete the snapshot at any time
+            let (id, _, snapshot_root) = self.mem.pin_last_commit()?;
+            let snapshot = Btree::<str, SnapshotRecord>::new(snapshot_root, &self.mem)
+                .get(name)
+                .and_then(|snapshot| {
+                    snapshot.ok_or_else(|| Error::SnapshotDoesNotExist(name.to_string()))
+                });
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(err) => {
+                    self.mem.unpin(id)?;
+                    return Err(err);
+                }
+            };
+            if let Err(err) = self.mem.pin(snapshot.transaction_id) {
+                self.mem.unpin(id)?;
+                return Err(err);
+            }
+            // If the writer has moved on, it may have freed the snapshot without seeing our pin
+            let pinned = self.mem.is_last_commit(id);
+            self.mem.unpin(id)?;
+            if pinned? {
+                *self
+                    .live_read_transactions
+                    .lock()
+                    .unwrap()
+                    .entry(snapshot.transaction_id)
+                    .or_default() += 1;
+                return Ok(ReadTransaction::new(
+                    self,
+                    snapshot.transaction_id,
+                    snapshot.root,
+                    snapshot_root,
+                ));
+            }
+            self.mem.unpin(snapshot.transaction_id)?;
+        }
+    }
+
+    /// Verifies the consistency of the database file
+    ///
+    /// Walks every tree in the database, and cross-checks the pages they reference against the
+    /// state of the page allocator. Problems are returned in the [`IntegrityReport`], rather than
+    /// as an error, so that a damaged database can be inspected.
+    ///
+    /// New write transactions are blocked until the check completes. Returns
+    /// [`Error::WriteTransactionInProgress`] if a [`WriteTransaction`] is in progress
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        // The allocator state is owned by the writer, so can't be checked from a read-only instance
+        if self.mem.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+        let guard = self.leaked_write_transaction.lock().unwrap();
+        if let Some(leaked) = *guard {
+            return Err(Error::LeakedWriteTransaction(leaked));
+        }
+        drop(guard);
+
+        // Hold the write lock, so that no uncommitted pages exist while we inspect the allocator
+        let live_write = self.live_write_transaction.lock().unwrap();
+        if live_write.is_some() {
+            return Err(Error::WriteTransactionInProgress);
+        }
+        let checker = IntegrityChecker::new(&self.mem);
+        let report = checker.check(
+            self.mem.get_data_root(),
+            self.mem.get_freed_root(),
+            self.mem.get_snapshot_root(),
+        );
+        drop(live_write);
+
+        report
+    }
+
+    /// Writes a compacted copy of the database to a new file at `path`
+    ///
+    /// The copy contains all tables as of the last commit, and is taken from a read transaction,
+    /// so writes may continue while the backup is in progress. Persistent snapshots are not copied.
+    ///
+    /// The copy is written to a temporary file in the same directory, which is moved to `path` once
+    /// it is complete and durable, so `path` never holds a partial backup. The temporary file is
+    /// removed if the backup fails.
+    ///
+    /// Returns an error if a file already exists at `path`
+    pub fn backup_to(&self, path: impl AsRef<Path>) -> Result {
+        let path = path.as_ref();
+        // Fail rather than overwriting an existing database
+        if path.exists() {
+            return Err(Error::Io(io::Error::from(ErrorKind::AlreadyExists)));
+        }
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::Io(io::Error::from(ErrorKind::InvalidInput)))?;
+        let mut temp_name = OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".backup-{}", process::id()));
+        let temp_path = path.with_file_name(temp_name);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        let result = self
+            .write_backup(file, &temp_path)
+            .and_then(|_| Ok(fs::rename(&temp_path, path)?));
+        if result.is_err() {
+            // Nothing useful can be done if this fails, and the original error is more relevant
+            let _ = fs::remove_file(&temp_path);
+            return result;
+        }
+        // Make the rename durable
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_backup(&self, file: File, path: &Path) -> Result {
+        let read_txn = self.begin_read()?;
+        let backup = Database::new(
+            file,
+            self.mem.get_max_capacity(),
+            Some(self.mem.get_page_size()),
+            true,
+        )?;
+        let write_txn = backup.begin_write()?;
+        read_txn.copy_tables_to(&write_txn)?;
+        write_txn.commit()?;
+        drop(backup);
+        // Closing the database writes to the header, so the file must be synced after it's closed
+        File::open(path)?.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Compacts the database file
+    ///
+    /// Live pages are moved into free space toward the start of the file, and the file is then
+    /// truncated to release the space after the last page in use.
+    ///
+    /// The file is only truncated if no other instance, in this or another process, has it open
+    /// with [`Database::open_read_only`]. Otherwise the pages are still moved, and the file is
+    /// truncated by a later call.
+    ///
+    /// Returns `true` if the file was shrunk
+    pub fn compact(&mut self) -> Result<bool> {
+        // Release the pages freed by previous transactions, so that they can be reused
+        self.begin_write()?.commit()?;
+        loop {
+            let mut txn = self.begin_write()?;
+            let moved = txn.relocate_pages()?;
+            txn.commit()?;
+            if !moved {
+                break;
+            }
+        }
+
+        if self.mem.shrink()? {
+            // Commit twice, so that both commit slots reference the new layout before the file
+            // is truncated. Otherwise a crash could leave us with a slot pointing past the end of the file
+            self.begin_write()?.commit()?;
+            self.begin_write()?.commit()?;
+        }
+        // Safety: we hold a mutable reference, so no transactions or references to pages exist
+        unsafe { self.mem.truncate() }
+    }
+}
+
+// Adds the pages of the master table rooted at `root`, and of all the tables it references
+fn collect_table_pages(
+    root: PageNumber,
+    mem: &TransactionalMemory,
+    pages: &mut HashSet<PageNumber>,
+) -> Result {
+    for page in AllPageNumbersBtreeIter::new(root, mem)? {
+        pages.insert(page?);
+    }
+
+    let iter: BtreeRangeIter<[u8], [u8]> =
+        BtreeRangeIter::new::<RangeFull, [u8]>(.., Some(root), mem)?;
+    for entry in iter {
+        let entry = entry?;
+        let definition = InternalTableDefinition::from_bytes(entry.value());
+        if let Some(table_root) = definition.get_root() {
+            for page in AllPageNumbersBtreeIter::new(table_root, mem)? {
+                pages.insert(page?);
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub struct DatabaseBuilder {
@@ -326,18 +698,17 @@ impl DatabaseBuilder {
     /// * if the file is a valid redb database, it will be opened
     /// * otherwise this function will return an error
     ///
-    /// `db_size`: the maximum size in bytes of the database.
-    ///
-    /// # Safety
+    /// `db_size`: the maximum size in bytes of the database. See [`Database::create`]
     ///
-    /// The file referenced by `path` must not be concurrently modified by any other process
-    pub unsafe fn create(&self, path: impl AsRef<Path>, db_size: usize) -> Result<Database> {
+    /// Returns [`Error::DatabaseAlreadyOpen`] if the file is already in use
+    pub fn create(&self, path: impl AsRef<Path>, db_size: usize) -> Result<Database> {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
-            .open(path)?;
+            .open(path.as_ref())?;
 
-        Database::new(file, db_size, self.page_size, self.dynamic_growth)
+        Database::new(file, db_size, self.page_size, self.dynamic_growth)?
+            .check_max_capacity(path.as_ref(), db_size)
     }
 }
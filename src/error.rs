@@ -11,11 +11,32 @@ pub enum Error {
         size: usize,
         requested_size: usize,
     },
+    // The database header doesn't have room to track a database of the requested size
+    DbSizeNotRaisable {
+        size: usize,
+        requested_size: usize,
+    },
     TableDoesNotExist(String),
+    // WriteTransaction::migrate_table() mapped two entries of the named table to the same key
+    DuplicateKey(String),
+    // The entries passed to Table::bulk_load() were not in strictly ascending key order
+    UnsortedInput,
+    // The fill factor passed to Table::bulk_load() was not in (0, 1]
+    InvalidFillFactor(f32),
+    SnapshotDoesNotExist(String),
+    SnapshotAlreadyExists(String),
+    // The database file is in use by another Database instance, possibly in another process
+    DatabaseAlreadyOpen,
+    // The database was opened with Database::open_read_only()
+    ReadOnly,
     LeakedWriteTransaction(&'static panic::Location<'static>),
     // Tables cannot be opened for writing multiple times, since they could retrieve immutable &
     // mutable references to the same dirty pages, or multiple mutable references via insert_reserve()
     TableAlreadyOpen(String, &'static panic::Location<'static>),
+    // The savepoint belongs to another transaction, or a savepoint created before it was restored
+    InvalidSavepoint,
+    // The operation requires that no WriteTransaction is in progress
+    WriteTransactionInProgress,
     OutOfSpace,
     Io(io::Error),
     LockPoisoned(&'static panic::Location<'static>),
@@ -53,15 +74,56 @@ impl Display for Error {
                     path, size, requested_size
                 )
             }
+            Error::DbSizeNotRaisable {
+                size,
+                requested_size,
+            } => {
+                write!(
+                    f,
+                    "Database maximum size of {} bytes cannot be raised to {} bytes",
+                    size, requested_size
+                )
+            }
             Error::TableDoesNotExist(table) => {
                 write!(f, "Table '{}' does not exist", table)
             }
+            Error::DuplicateKey(table) => {
+                write!(
+                    f,
+                    "Migration mapped two entries of table '{}' to the same key",
+                    table
+                )
+            }
+            Error::UnsortedInput => {
+                write!(f, "Keys must be in strictly ascending order")
+            }
+            Error::InvalidFillFactor(fill_factor) => {
+                write!(f, "Fill factor {} is not in (0, 1]", fill_factor)
+            }
+            Error::SnapshotDoesNotExist(name) => {
+                write!(f, "Snapshot '{}' does not exist", name)
+            }
+            Error::SnapshotAlreadyExists(name) => {
+                write!(f, "Snapshot '{}' already exists", name)
+            }
+            Error::DatabaseAlreadyOpen => {
+                write!(f, "Database already open. Cannot acquire lock.")
+            }
+            Error::ReadOnly => {
+                write!(f, "Database is open read-only")
+            }
             Error::LeakedWriteTransaction(location) => {
                 write!(f, "Leaked write transaction: {}", location)
             }
             Error::TableAlreadyOpen(name, location) => {
                 write!(f, "Table '{}' already opened at: {}", name, location)
             }
+            Error::InvalidSavepoint => {
+                write!(f, "Savepoint is invalid or cannot be restored")
+            }
+            Error::WriteTransactionInProgress => {
+                write!(f, "A write transaction is in progress")
+            }
             Error::OutOfSpace => {
                 write!(f, "Database is out of space")
             }
@@ -4,20 +4,32 @@ pub use multimap_table::{
     MultimapRangeIter, MultimapTable, MultimapValueIter, ReadOnlyMultimapTable,
     ReadableMultimapTable,
 };
-pub use table::{RangeIter, ReadOnlyTable, ReadableTable, Table};
-pub use transactions::{DatabaseStats, Durability, ReadTransaction, WriteTransaction};
-pub use tree_store::AccessGuard;
+pub use table::{
+    Drain, Entry, OccupiedEntry, RangeIter, ReadOnlyTable, ReadableTable, Table, VacantEntry,
+};
+pub use transactions::{DatabaseStats, Durability, ReadTransaction, Savepoint, WriteTransaction};
+pub use tree_store::{AccessGuard, IntegrityReport, IntegrityViolation, PageLocation, PageOwner};
+pub use types::{
+    AsBytesWithLifetime, ByteOrderedKey, FixedWidth, OwnedAsBytesLifetime, OwnedLifetime, Pod,
+    RedbKey, RedbValue, RefAsBytesLifetime, RefLifetime, WithLifetime,
+};
 
 type Result<T = (), E = Error> = std::result::Result<T, E>;
 
 #[cfg(feature = "python")]
 pub use crate::python::redb;
+#[cfg(feature = "derive")]
+pub use redb_derive::{RedbKey, RedbValue};
+#[cfg(feature = "serde")]
+pub use serde_types::{Bincode, Json};
 
 mod db;
 mod error;
 mod multimap_table;
 #[cfg(feature = "python")]
 mod python;
+#[cfg(feature = "serde")]
+mod serde_types;
 mod table;
 mod transactions;
 mod tree_store;
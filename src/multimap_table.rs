@@ -73,6 +73,10 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> RedbValue for Multi
 
 impl<K: RedbKey + ?Sized, V: RedbKey + ?Sized> RedbKey for MultimapKVPair<K, V> {
     fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::try_compare(data1, data2).unwrap()
+    }
+
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
         let kv1 = MultimapKVPairAccessor::<K, V>::new(data1);
         let kv2 = MultimapKVPairAccessor::<K, V>::new(data2);
         // Only one of the inputs may be a query
@@ -81,33 +85,34 @@ impl<K: RedbKey + ?Sized, V: RedbKey + ?Sized> RedbKey for MultimapKVPair<K, V>
                 || kv2.compare_op() == MultimapKeyCompareOp::KeyAndValue
         );
         if kv1.compare_op() != MultimapKeyCompareOp::KeyAndValue {
-            Self::compare(data2, data1).reverse()
+            Ok(Self::try_compare(data2, data1)?.reverse())
         } else {
             // Can assume data2 is the query at this point
-            match kv2.compare_op() {
+            let ordering = match kv2.compare_op() {
                 MultimapKeyCompareOp::KeyAndValue => {
-                    match K::compare(kv1.key_bytes(), kv2.key_bytes()) {
+                    match K::try_compare(kv1.key_bytes(), kv2.key_bytes())? {
                         Ordering::Less => Ordering::Less,
-                        Ordering::Equal => V::compare(kv1.value_bytes(), kv2.value_bytes()),
+                        Ordering::Equal => V::try_compare(kv1.value_bytes(), kv2.value_bytes())?,
                         Ordering::Greater => Ordering::Greater,
                     }
                 }
                 MultimapKeyCompareOp::KeyMinusEpsilon => {
-                    match K::compare(kv1.key_bytes(), kv2.key_bytes()) {
+                    match K::try_compare(kv1.key_bytes(), kv2.key_bytes())? {
                         Ordering::Less => Ordering::Less,
                         Ordering::Equal => Ordering::Greater,
                         Ordering::Greater => Ordering::Greater,
                     }
                 }
                 MultimapKeyCompareOp::KeyPlusEpsilon => {
-                    match K::compare(kv1.key_bytes(), kv2.key_bytes()) {
+                    match K::try_compare(kv1.key_bytes(), kv2.key_bytes())? {
                         Ordering::Less => Ordering::Less,
                         Ordering::Equal => Ordering::Less,
                         Ordering::Greater => Ordering::Greater,
                     }
                 }
-                MultimapKeyCompareOp::KeyOnly => K::compare(kv1.key_bytes(), kv2.key_bytes()),
-            }
+                MultimapKeyCompareOp::KeyOnly => K::try_compare(kv1.key_bytes(), kv2.key_bytes())?,
+            };
+            Ok(ordering)
         }
     }
 }
@@ -232,23 +237,29 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> MultimapValueIter<'
         Self { inner }
     }
 
-    // TODO: implement Iter when GATs are stable
-    #[allow(clippy::should_implement_trait)]
-    pub fn next(&mut self) -> Option<<<V as RedbValue>::View as WithLifetime>::Out> {
-        if let Some(entry) = self.inner.next() {
-            let pair = MultimapKVPairAccessor::<K, V> {
-                data: entry.key(),
-                _key_type: Default::default(),
-                _value_type: Default::default(),
-            };
-            Some(V::from_bytes(pair.value_bytes()))
-        } else {
-            None
-        }
+    fn value(entry: &'a [u8]) -> Result<<<V as RedbValue>::View as WithLifetime<'a>>::Out> {
+        let pair = MultimapKVPairAccessor::<K, V>::new(entry);
+        V::try_from_bytes(pair.value_bytes())
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> Iterator
+    for MultimapValueIter<'a, K, V>
+{
+    type Item = Result<<<V as RedbValue>::View as WithLifetime<'a>>::Out>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| Self::value(entry?.key()))
     }
+}
 
-    pub fn rev(self) -> Self {
-        Self::new(self.inner.reverse())
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> DoubleEndedIterator
+    for MultimapValueIter<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| Self::value(entry?.key()))
     }
 }
 
@@ -264,30 +275,42 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> MultimapRangeIter<'
 
     // TODO: Simplify this when GATs are stable
     #[allow(clippy::type_complexity)]
-    // TODO: implement Iter when GATs are stable
-    #[allow(clippy::should_implement_trait)]
-    pub fn next(
-        &mut self,
-    ) -> Option<(
-        <<K as RedbValue>::View as WithLifetime>::Out,
-        <<V as RedbValue>::View as WithLifetime>::Out,
+    fn key_and_value(
+        entry: &'a [u8],
+    ) -> Result<(
+        <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+        <<V as RedbValue>::View as WithLifetime<'a>>::Out,
     )> {
-        if let Some(entry) = self.inner.next() {
-            let pair = MultimapKVPairAccessor::<K, V> {
-                data: entry.key(),
-                _key_type: Default::default(),
-                _value_type: Default::default(),
-            };
-            let key = K::from_bytes(pair.key_bytes());
-            let value = V::from_bytes(pair.value_bytes());
-            Some((key, value))
-        } else {
-            None
-        }
+        let pair = MultimapKVPairAccessor::<K, V>::new(entry);
+        Ok((
+            K::try_from_bytes(pair.key_bytes())?,
+            V::try_from_bytes(pair.value_bytes())?,
+        ))
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> Iterator
+    for MultimapRangeIter<'a, K, V>
+{
+    type Item = Result<(
+        <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+        <<V as RedbValue>::View as WithLifetime<'a>>::Out,
+    )>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|entry| Self::key_and_value(entry?.key()))
     }
+}
 
-    pub fn rev(self) -> Self {
-        Self::new(self.inner.reverse())
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbKey + ?Sized + 'a> DoubleEndedIterator
+    for MultimapRangeIter<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| Self::key_and_value(entry?.key()))
     }
 }
 
@@ -329,7 +352,8 @@ impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbKey + ?Sized> MultimapTable<'db, 'tx
         // Safety: No other references to this table can exist.
         // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
         // and we borrow &mut self.
-        unsafe { self.tree.insert(&kv, b"") }
+        unsafe { self.tree.insert(&kv, b"")? };
+        Ok(())
     }
 
     /// Removes the given key-value pair
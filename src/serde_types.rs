@@ -0,0 +1,106 @@
+use crate::types::{
+    intern_type_name, AsBytesWithLifetime, OwnedAsBytesLifetime, OwnedLifetime, RedbValue,
+    WithLifetime,
+};
+use crate::{Error, Result};
+use serde_crate::de::DeserializeOwned;
+use serde_crate::Serialize;
+use std::fmt::Debug;
+
+/// A value stored in [bincode](https://docs.rs/bincode) format, for any type implementing
+/// `Serialize` and `DeserializeOwned`
+///
+/// Values are inserted wrapped in a `Bincode`, and read back as an owned `T`. The type name stored
+/// with the table includes the path of `T`, as returned by [`std::any::type_name`], so opening a
+/// table with a different `T` returns `Error::TableTypeMismatch`. Renaming `T`, or moving it to
+/// another module, changes the stored name too.
+///
+/// ```rust
+/// use redb::{Bincode, Database, Error, ReadableTable, TableDefinition};
+/// # use serde_crate as serde;
+/// use serde::{Deserialize, Serialize};
+/// # use tempfile::NamedTempFile;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// # #[serde(crate = "serde_crate")]
+/// struct User {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// const TABLE: TableDefinition<str, Bincode<User>> = TableDefinition::new("users");
+///
+/// # fn main() -> Result<(), Error> {
+/// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+/// # let filename = tmpfile.path();
+/// let db = Database::create(filename, 1024 * 1024)?;
+/// let write_txn = db.begin_write()?;
+/// {
+///     let mut table = write_txn.open_table(TABLE)?;
+///     let user = User {
+///         name: "alice".to_string(),
+///         age: 30,
+///     };
+///     table.insert("alice", &Bincode(user))?;
+/// }
+/// write_txn.commit()?;
+///
+/// let read_txn = db.begin_read()?;
+/// let table = read_txn.open_table(TABLE)?;
+/// assert_eq!(30, table.get("alice")?.unwrap().age);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bincode<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned + Debug + 'static> RedbValue for Bincode<T> {
+    type View = OwnedLifetime<T>;
+    type ToBytes = OwnedAsBytesLifetime<Vec<u8>>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        bincode::deserialize(data).map_err(|err| Error::Corrupted(err.to_string()))
+    }
+
+    // Panics if `T` cannot be serialized
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        bincode::serialize(&self.0).unwrap()
+    }
+
+    fn redb_type_name() -> &'static str {
+        intern_type_name(format!("Bincode<{}>", std::any::type_name::<T>()))
+    }
+}
+
+/// A value stored as JSON, for any type implementing `Serialize` and `DeserializeOwned`
+///
+/// This is larger and slower than [`Bincode`], but leaves the stored values readable by other
+/// tools. As with `Bincode`, the type name stored with the table includes the path of `T`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned + Debug + 'static> RedbValue for Json<T> {
+    type View = OwnedLifetime<T>;
+    type ToBytes = OwnedAsBytesLifetime<Vec<u8>>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        serde_json::from_slice(data).map_err(|err| Error::Corrupted(err.to_string()))
+    }
+
+    // Panics if `T` cannot be serialized, for example a map whose keys are not strings
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        serde_json::to_vec(&self.0).unwrap()
+    }
+
+    fn redb_type_name() -> &'static str {
+        intern_type_name(format!("Json<{}>", std::any::type_name::<T>()))
+    }
+}
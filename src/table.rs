@@ -1,12 +1,13 @@
 use crate::tree_store::{
-    AccessGuardMut, Btree, BtreeMut, BtreeRangeIter, PageNumber, TransactionalMemory,
+    AccessGuardMut, Btree, BtreeMut, BtreeRangeIter, LeafPosition, PageNumber, TransactionalMemory,
 };
-use crate::types::{RedbKey, RedbValue, WithLifetime};
+use crate::types::{ByteOrderedKey, RedbKey, RedbValue, WithLifetime};
 use crate::Result;
 use crate::{AccessGuard, WriteTransaction};
 use std::borrow::Borrow;
 use std::cell::RefCell;
-use std::ops::RangeBounds;
+use std::collections::Bound;
+use std::ops::{RangeBounds, RangeFull};
 use std::rc::Rc;
 
 /// A table containing key-value mappings
@@ -37,8 +38,9 @@ impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Table<'db, 'txn, K,
     }
 
     /// Insert mapping of the given key to the given value
-    // TODO: return the old value, if it exists
-    pub fn insert(&mut self, key: &K, value: &V) -> Result {
+    ///
+    /// Returns the old value, if the key was present in the table
+    pub fn insert(&mut self, key: &K, value: &V) -> Result<Option<AccessGuard<V>>> {
         // Safety: No other references to this table can exist.
         // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
         // and we borrow &mut self.
@@ -63,6 +65,216 @@ impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Table<'db, 'txn, K,
         // and we borrow &mut self.
         unsafe { self.tree.remove(key) }
     }
+
+    /// Removes the entry with the smallest key, and returns it
+    ///
+    /// Together with [`insert`](Table::insert), this allows a table to be used as a queue
+    ///
+    /// # Examples
+    ///
+    /// Usage:
+    /// ```rust
+    /// use redb::*;
+    /// # use tempfile::NamedTempFile;
+    /// const QUEUE: TableDefinition<u64, str> = TableDefinition::new("queue");
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    /// # let filename = tmpfile.path();
+    /// # let db_max_size = 1024 * 1024;
+    /// let db = Database::create(filename, db_max_size)?;
+    /// let write_txn = db.begin_write()?;
+    /// {
+    ///     let mut queue = write_txn.open_table(QUEUE)?;
+    ///     queue.insert(&1, "first")?;
+    ///     queue.insert(&2, "second")?;
+    ///     let (sequence, job) = queue.pop_first()?.unwrap();
+    ///     assert_eq!(1, sequence.to_value()?);
+    ///     assert_eq!("first", job.to_value()?);
+    /// }
+    /// write_txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pop_first(&mut self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        // Safety: No other references to this table can exist.
+        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+        // and we borrow &mut self.
+        unsafe { self.tree.pop(false) }
+    }
+
+    /// Removes the entry with the largest key, and returns it
+    pub fn pop_last(&mut self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        // Safety: No other references to this table can exist.
+        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+        // and we borrow &mut self.
+        unsafe { self.tree.pop(true) }
+    }
+
+    /// Inserts the given entries, which must be sorted by key, much faster than
+    /// [`insert`](Table::insert)
+    ///
+    /// When the table is empty, or all the keys are greater than those already in the table,
+    /// pages are built bottom-up and filled to `fill_factor` (between 0 and 1) of their capacity.
+    /// A lower fill factor leaves room for later inserts, without having to split pages.
+    /// Otherwise, the entries are collected and then inserted one at a time
+    ///
+    /// Returns `Error::UnsortedInput` if the keys are not in strictly ascending order, and
+    /// `Error::InvalidFillFactor` if `fill_factor` is not in `(0, 1]`. The table is left unchanged
+    /// in either case
+    ///
+    /// # Examples
+    ///
+    /// Usage:
+    /// ```rust
+    /// use redb::*;
+    /// # use tempfile::NamedTempFile;
+    /// const TABLE: TableDefinition<u64, u64> = TableDefinition::new("my_data");
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    /// # let filename = tmpfile.path();
+    /// # let db_max_size = 1024 * 1024;
+    /// let db = Database::create(filename, db_max_size)?;
+    /// let write_txn = db.begin_write()?;
+    /// {
+    ///     let mut table = write_txn.open_table(TABLE)?;
+    ///     table.bulk_load((0..1000u64).map(|i| (i, i * i)), 1.0)?;
+    ///     assert_eq!(Some(100), table.get(&10)?);
+    /// }
+    /// write_txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bulk_load<I, KR, VR>(&mut self, entries: I, fill_factor: f32) -> Result
+    where
+        I: IntoIterator<Item = (KR, VR)>,
+        KR: Borrow<K>,
+        VR: Borrow<V>,
+    {
+        // Safety: No other references to this table can exist.
+        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+        // and we borrow &mut self.
+        unsafe { self.tree.bulk_load(entries.into_iter(), fill_factor) }
+    }
+
+    /// Removes all entries in the given range
+    ///
+    /// Pages which only contain keys in the range are freed without being read, so this is much
+    /// faster than removing the keys one at a time
+    pub fn remove_range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(&mut self, range: T) -> Result {
+        // Safety: No other references to this table can exist.
+        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+        // and we borrow &mut self.
+        unsafe { self.tree.remove_range(range) }
+    }
+
+    /// Removes all entries in the given range, and returns them
+    pub fn drain<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(
+        &mut self,
+        range: T,
+    ) -> Result<Drain<K, V>> {
+        // Safety: No other references to this table can exist.
+        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+        // and we borrow &mut self.
+        let entries = unsafe { self.tree.drain(range)? };
+
+        Ok(Drain::new(entries))
+    }
+
+    /// Retains only the entries for which `predicate` returns `true`
+    ///
+    /// Consecutive entries which are rejected are removed together, as in [`Table::remove_range`]
+    pub fn retain<F>(&mut self, mut predicate: F) -> Result
+    where
+        F: for<'f> FnMut(
+            <<K as RedbValue>::View as WithLifetime<'f>>::Out,
+            <<V as RedbValue>::View as WithLifetime<'f>>::Out,
+        ) -> bool,
+    {
+        // First and last key of each run of rejected entries
+        let mut runs: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+        {
+            let mut current: Option<(Vec<u8>, Vec<u8>)> = None;
+            for entry in self.tree.range::<RangeFull, &K>(..)? {
+                let entry = entry?;
+                if predicate(
+                    K::try_from_bytes(entry.key())?,
+                    V::try_from_bytes(entry.value())?,
+                ) {
+                    runs.extend(current.take());
+                } else if let Some((_, ref mut last)) = current {
+                    *last = entry.key().to_vec();
+                } else {
+                    current = Some((entry.key().to_vec(), entry.key().to_vec()));
+                }
+            }
+            runs.extend(current);
+        }
+
+        for (first, last) in runs {
+            // Safety: No other references to this table can exist.
+            // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+            // and we borrow &mut self.
+            unsafe {
+                self.tree
+                    .remove_serialized_range(Bound::Included(&first), Bound::Included(&last))?
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Gets the given key's entry in the table, for in-place manipulation
+    ///
+    /// The tree is searched once, and the pages on the path to the key are copied when the entry
+    /// is created, so that operations on the entry only need to modify a single leaf
+    ///
+    /// # Examples
+    ///
+    /// Usage:
+    /// ```rust
+    /// use redb::*;
+    /// # use tempfile::NamedTempFile;
+    /// const TABLE: TableDefinition<str, u64> = TableDefinition::new("my_data");
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    /// # let filename = tmpfile.path();
+    /// # let db_max_size = 1024 * 1024;
+    /// let db = Database::create(filename, db_max_size)?;
+    /// let write_txn = db.begin_write()?;
+    /// {
+    ///     let mut table = write_txn.open_table(TABLE)?;
+    ///     for word in ["a", "b", "a"] {
+    ///         table.entry(word)?.and_modify(|count| count + 1)?.or_insert(&1)?;
+    ///     }
+    ///     assert_eq!(Some(2), table.get("a")?);
+    ///     assert_eq!(Some(1), table.get("b")?);
+    /// }
+    /// write_txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entry<'a>(&'a mut self, key: &'a K) -> Result<Entry<'a, 'txn, K, V>> {
+        // Safety: No other references to this table can exist.
+        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
+        // and we borrow &mut self.
+        let position = unsafe { self.tree.prepare_entry(key)? };
+        let tree = &mut self.tree;
+        Ok(match position {
+            Some(position) if position.found => Entry::Occupied(OccupiedEntry {
+                tree,
+                key,
+                position,
+            }),
+            position => Entry::Vacant(VacantEntry {
+                tree,
+                key,
+                position,
+            }),
+        })
+    }
 }
 
 impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
@@ -75,10 +287,70 @@ impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
     fn range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(
         &'a self,
         range: T,
-    ) -> Result<RangeIter<K, V>> {
+    ) -> Result<RangeIter<'a, K, V>> {
         self.tree.range(range).map(RangeIter::new)
     }
 
+    fn iter(&self) -> Result<RangeIter<K, V>> {
+        self.tree.range::<RangeFull, &K>(..).map(RangeIter::new)
+    }
+
+    fn prefix(&self, prefix: &K) -> Result<RangeIter<K, V>>
+    where
+        K: ByteOrderedKey,
+    {
+        self.tree.prefix(prefix).map(RangeIter::new)
+    }
+
+    fn first(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.tree.first()
+    }
+
+    fn last(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.tree.last()
+    }
+
+    fn rank(&self, key: &K) -> Result<usize> {
+        self.tree.rank(key)
+    }
+
+    fn count_range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(&self, range: T) -> Result<usize> {
+        self.tree.count_range(range)
+    }
+
+    fn approximate_size<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(
+        &self,
+        range: T,
+    ) -> Result<u64> {
+        self.tree.approximate_size(range)
+    }
+
+    fn nth(
+        &self,
+        n: usize,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.tree.nth(n)
+    }
+
     fn len(&self) -> Result<usize> {
         self.tree.len()
     }
@@ -94,6 +366,121 @@ impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Drop for Table<'db,
     }
 }
 
+/// A view into a single entry of a [`Table`], which is either occupied or vacant
+///
+/// Created by [`Table::entry`]
+pub enum Entry<'a, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
+    Occupied(OccupiedEntry<'a, 'txn, K, V>),
+    Vacant(VacantEntry<'a, 'txn, K, V>),
+}
+
+impl<'a, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Entry<'a, 'txn, K, V> {
+    /// Returns the key of this entry
+    pub fn key(&self) -> &'a K {
+        match self {
+            Entry::Occupied(entry) => entry.key,
+            Entry::Vacant(entry) => entry.key,
+        }
+    }
+
+    /// Inserts `value` if the entry is vacant
+    ///
+    /// Returns the occupied entry
+    pub fn or_insert(self, value: &V) -> Result<OccupiedEntry<'a, 'txn, K, V>> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry),
+            Entry::Vacant(entry) => entry.insert(value),
+        }
+    }
+
+    /// Replaces the value with the result of `f`, if the entry is occupied
+    pub fn and_modify<F, T>(self, f: F) -> Result<Self>
+    where
+        F: for<'v> FnOnce(<<V as RedbValue>::View as WithLifetime<'v>>::Out) -> T,
+        T: Borrow<V>,
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                let new_value = f(entry.value()?.to_value()?);
+                entry.insert(new_value.borrow())?;
+                Ok(Entry::Occupied(entry))
+            }
+            Entry::Vacant(entry) => Ok(Entry::Vacant(entry)),
+        }
+    }
+}
+
+/// An occupied [`Entry`]
+pub struct OccupiedEntry<'a, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
+    tree: &'a mut BtreeMut<'txn, K, V>,
+    key: &'a K,
+    position: LeafPosition,
+}
+
+impl<'a, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> OccupiedEntry<'a, 'txn, K, V> {
+    /// Returns the key of this entry
+    pub fn key(&self) -> &'a K {
+        self.key
+    }
+
+    /// Returns the value of this entry
+    pub fn value(&self) -> Result<AccessGuard<V>> {
+        self.tree.entry_value(&self.position)
+    }
+
+    /// Replaces the value of this entry
+    ///
+    /// Returns the old value
+    pub fn insert(&mut self, value: &V) -> Result<AccessGuard<'txn, V>> {
+        // Safety: the entry holds the only reference to the table, and we borrow &mut self
+        let (position, old_value) = unsafe {
+            self.tree
+                .insert_entry(Some(&self.position), self.key, value)?
+        };
+        self.position = position;
+        Ok(old_value.unwrap())
+    }
+
+    /// Removes this entry from the table
+    ///
+    /// Returns the removed value
+    pub fn remove(self) -> Result<AccessGuard<'a, V>> {
+        // Safety: the entry holds the only reference to the table, and is consumed
+        unsafe { self.tree.remove_entry(&self.position, self.key) }
+    }
+}
+
+/// A vacant [`Entry`]
+pub struct VacantEntry<'a, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
+    tree: &'a mut BtreeMut<'txn, K, V>,
+    key: &'a K,
+    // None if the table is empty
+    position: Option<LeafPosition>,
+}
+
+impl<'a, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> VacantEntry<'a, 'txn, K, V> {
+    /// Returns the key of this entry
+    pub fn key(&self) -> &'a K {
+        self.key
+    }
+
+    /// Inserts `value` into the table
+    ///
+    /// Returns the now occupied entry
+    pub fn insert(self, value: &V) -> Result<OccupiedEntry<'a, 'txn, K, V>> {
+        // Safety: the entry holds the only reference to the table, and is consumed
+        let (position, _) = unsafe {
+            self.tree
+                .insert_entry(self.position.as_ref(), self.key, value)?
+        };
+        Ok(OccupiedEntry {
+            tree: self.tree,
+            key: self.key,
+            position,
+        })
+    }
+}
+
 pub trait ReadableTable<K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     /// Returns the value corresponding to the given key
     fn get(&self, key: &K) -> Result<Option<<<V as RedbValue>::View as WithLifetime>::Out>>;
@@ -112,7 +499,7 @@ pub trait ReadableTable<K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
     /// # let filename = tmpfile.path();
     /// # let db_max_size = 1024 * 1024;
-    /// let db = unsafe { Database::create(filename, db_max_size)? };
+    /// let db = Database::create(filename, db_max_size)?;
     /// let write_txn = db.begin_write()?;
     /// {
     ///     let mut table = write_txn.open_table(TABLE)?;
@@ -125,14 +512,141 @@ pub trait ReadableTable<K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     /// let read_txn = db.begin_read()?;
     /// let table = read_txn.open_table(TABLE)?;
     /// let mut iter = table.range("a".."c")?;
-    /// assert_eq!(Some(("a", 0)), iter.next());
+    /// assert_eq!(("a", 0), iter.next().unwrap()?);
     /// # Ok(())
     /// # }
     /// ```
     fn range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(
         &'a self,
         range: T,
-    ) -> Result<RangeIter<K, V>>;
+    ) -> Result<RangeIter<'a, K, V>>;
+
+    /// Returns a double-ended iterator over all elements in the table
+    fn iter(&self) -> Result<RangeIter<K, V>>;
+
+    /// Returns a double-ended iterator over the elements whose key begins with `prefix`
+    ///
+    /// The keys are compared by their serialized bytes, so this is only available for key types
+    /// which are ordered lexicographically by those bytes. See [`ByteOrderedKey`]
+    ///
+    /// ```rust
+    /// use redb::{Database, Error, ReadableTable, TableDefinition};
+    /// # use tempfile::NamedTempFile;
+    ///
+    /// const TABLE: TableDefinition<str, u64> = TableDefinition::new("my_data");
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    /// # let filename = tmpfile.path();
+    /// let db = Database::create(filename, 1024 * 1024)?;
+    /// let write_txn = db.begin_write()?;
+    /// {
+    ///     let mut table = write_txn.open_table(TABLE)?;
+    ///     table.insert("user/1", &1)?;
+    ///     table.insert("user/2", &2)?;
+    ///     table.insert("users", &3)?;
+    /// }
+    /// write_txn.commit()?;
+    ///
+    /// let read_txn = db.begin_read()?;
+    /// let table = read_txn.open_table(TABLE)?;
+    /// let keys: Vec<&str> = table.prefix("user/")?.map(|x| x.unwrap().0).collect();
+    /// assert_eq!(keys, ["user/1", "user/2"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn prefix(&self, prefix: &K) -> Result<RangeIter<K, V>>
+    where
+        K: ByteOrderedKey;
+
+    /// Returns the entry with the smallest key
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    fn first(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    >;
+
+    /// Returns the entry with the largest key
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    fn last(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    >;
+
+    /// Returns the number of keys in the table which are less than `key`. `key` itself does not
+    /// need to be present
+    fn rank(&self, key: &K) -> Result<usize>;
+
+    /// Returns the `n`th entry, counting from zero in key order, or `None` if the table has `n`
+    /// or fewer entries
+    ///
+    /// Runs in time proportional to the height of the tree, so it can be used to page through
+    /// a table without iterating over the skipped entries:
+    /// ```rust
+    /// use redb::{Database, Error, ReadableTable, TableDefinition};
+    /// # use tempfile::NamedTempFile;
+    ///
+    /// const TABLE: TableDefinition<u64, str> = TableDefinition::new("users");
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    /// # let filename = tmpfile.path();
+    /// let db = Database::create(filename, 1024 * 1024)?;
+    /// let write_txn = db.begin_write()?;
+    /// {
+    ///     let mut table = write_txn.open_table(TABLE)?;
+    ///     for (id, name) in [(7, "alice"), (12, "bob"), (31, "carol"), (40, "dave")] {
+    ///         table.insert(&id, name)?;
+    ///     }
+    /// }
+    /// write_txn.commit()?;
+    ///
+    /// let read_txn = db.begin_read()?;
+    /// let table = read_txn.open_table(TABLE)?;
+    /// let page_size = 2;
+    /// let page: Vec<(u64, &str)> = (page_size..2 * page_size)
+    ///     .filter_map(|i| table.nth(i).unwrap())
+    ///     .collect();
+    /// assert_eq!(page, vec![(31, "carol"), (40, "dave")]);
+    /// assert_eq!(table.rank(&31)?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    fn nth(
+        &self,
+        n: usize,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    >;
+
+    /// Returns the number of entries in the given range
+    ///
+    /// This is computed from the entry counts stored in the branch pages, so only the pages on
+    /// the paths to the two ends of the range are read
+    fn count_range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(&self, range: T) -> Result<usize>;
+
+    /// Returns an estimate of the number of bytes of keys and values stored in the given range
+    ///
+    /// Like [`ReadableTable::count_range`], only the pages on the paths to the two ends of the
+    /// range are read. The average entry size in the leaf pages at those ends is assumed to hold
+    /// for the whole range, so the estimate is exact if all entries are the same size
+    fn approximate_size<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(&self, range: T)
+        -> Result<u64>;
 
     /// Returns the number of entries in the table
     fn len(&self) -> Result<usize>;
@@ -167,10 +681,70 @@ impl<'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
     fn range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(
         &'a self,
         range: T,
-    ) -> Result<RangeIter<K, V>> {
+    ) -> Result<RangeIter<'a, K, V>> {
         self.tree.range(range).map(RangeIter::new)
     }
 
+    fn iter(&self) -> Result<RangeIter<K, V>> {
+        self.tree.range::<RangeFull, &K>(..).map(RangeIter::new)
+    }
+
+    fn prefix(&self, prefix: &K) -> Result<RangeIter<K, V>>
+    where
+        K: ByteOrderedKey,
+    {
+        self.tree.prefix(prefix).map(RangeIter::new)
+    }
+
+    fn first(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.tree.first()
+    }
+
+    fn last(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.tree.last()
+    }
+
+    fn rank(&self, key: &K) -> Result<usize> {
+        self.tree.rank(key)
+    }
+
+    fn count_range<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(&self, range: T) -> Result<usize> {
+        self.tree.count_range(range)
+    }
+
+    fn approximate_size<'a, T: RangeBounds<KR>, KR: Borrow<K> + 'a>(
+        &self,
+        range: T,
+    ) -> Result<u64> {
+        self.tree.approximate_size(range)
+    }
+
+    fn nth(
+        &self,
+        n: usize,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.tree.nth(n)
+    }
+
     fn len(&self) -> Result<usize> {
         self.tree.len()
     }
@@ -189,28 +763,61 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> RangeIter<'a, K,
     fn new(inner: BtreeRangeIter<'a, K, V>) -> Self {
         Self { inner }
     }
+}
 
-    // TODO: Simplify this when GATs are stable
-    #[allow(clippy::type_complexity)]
-    // TODO: implement Iter when GATs are stable
-    #[allow(clippy::should_implement_trait)]
-    pub fn next(
-        &mut self,
-    ) -> Option<(
-        <<K as RedbValue>::View as WithLifetime>::Out,
-        <<V as RedbValue>::View as WithLifetime>::Out,
-    )> {
-        if let Some(entry) = self.inner.next() {
-            let key = K::from_bytes(entry.key());
-            let value = V::from_bytes(entry.value());
-            Some((key, value))
-        } else {
-            None
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> Iterator for RangeIter<'a, K, V> {
+    type Item = Result<(
+        <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+        <<V as RedbValue>::View as WithLifetime<'a>>::Out,
+    )>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| {
+            let entry = entry?;
+            let key = K::try_from_bytes(entry.key())?;
+            let value = V::try_from_bytes(entry.value())?;
+            Ok((key, value))
+        })
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> DoubleEndedIterator
+    for RangeIter<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|entry| {
+            let entry = entry?;
+            let key = K::try_from_bytes(entry.key())?;
+            let value = V::try_from_bytes(entry.value())?;
+            Ok((key, value))
+        })
+    }
+}
+
+/// Entries which were removed by [`Table::drain`]
+pub struct Drain<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
+    entries: std::vec::IntoIter<(AccessGuard<'a, K>, AccessGuard<'a, V>)>,
+}
+
+impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Drain<'a, K, V> {
+    fn new(entries: Vec<(AccessGuard<'a, K>, AccessGuard<'a, V>)>) -> Self {
+        Self {
+            entries: entries.into_iter(),
         }
     }
+}
+
+impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Iterator for Drain<'a, K, V> {
+    type Item = (AccessGuard<'a, K>, AccessGuard<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+}
 
-    pub fn rev(self) -> Self {
-        Self::new(self.inner.reverse())
+impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> DoubleEndedIterator for Drain<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back()
     }
 }
 
@@ -253,7 +860,7 @@ mod test {
         let definition: TableDefinition<ReverseKey, [u8]> = TableDefinition::new("x");
 
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+        let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
         let write_txn = db.begin_write().unwrap();
         {
             let mut table = write_txn.open_table(definition).unwrap();
@@ -270,7 +877,7 @@ mod test {
         let end = ReverseKey(vec![3u8]);
         let mut iter = table.range(start..=end).unwrap();
         for i in (3..=7u8).rev() {
-            let (key, value) = iter.next().unwrap();
+            let (key, value) = iter.next().unwrap().unwrap();
             assert_eq!(&[i], key);
             assert_eq!(b"value", value);
         }
@@ -1,14 +1,14 @@
 use crate::db::TransactionId;
 use crate::tree_store::{
-    Btree, BtreeMut, FreedTableKey, InternalTableDefinition, PageNumber, TableTree, TableType,
-    TransactionalMemory,
+    Btree, BtreeMut, FreedTableKey, InternalTableDefinition, PageNumber, SnapshotRecord, TableTree,
+    TableType, TransactionalMemory,
 };
-use crate::types::{RedbKey, RedbValue};
+use crate::types::{RedbKey, RedbValue, WithLifetime};
 use crate::{
     Database, Error, MultimapTable, MultimapTableDefinition, ReadOnlyMultimapTable, ReadOnlyTable,
     Result, Table, TableDefinition,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::cmp::min;
 use std::collections::HashMap;
 use std::mem::size_of;
@@ -89,6 +89,20 @@ pub enum Durability {
     Immediate,
 }
 
+/// A snapshot of the uncommitted state of a [`WriteTransaction`]
+///
+/// Created with [`WriteTransaction::savepoint`], and only valid within that transaction
+pub struct Savepoint {
+    id: u64,
+    transaction_id: TransactionId,
+    log_position: usize,
+    root: Option<PageNumber>,
+    freed_root: Option<PageNumber>,
+    snapshot_root: Option<PageNumber>,
+    freed_pages: Vec<PageNumber>,
+    pending_table_updates: HashMap<String, Option<PageNumber>>,
+}
+
 /// A read/write transaction
 ///
 /// Only a single [`WriteTransaction`] may exist at a time
@@ -102,8 +116,15 @@ pub struct WriteTransaction<'db> {
     // The binary blob is a length-prefixed array of PageNumber
     freed_tree: BtreeMut<'db, FreedTableKey, [u8]>,
     freed_pages: Rc<RefCell<Vec<PageNumber>>>,
+    // Persistent snapshots, by name
+    snapshot_tree: RefCell<BtreeMut<'db, str, SnapshotRecord>>,
+    // The last committed state, which this transaction started from
+    committed_root: Option<PageNumber>,
+    committed_transaction_id: TransactionId,
     open_tables: RefCell<HashMap<String, &'static panic::Location<'static>>>,
-    pending_table_updates: RefCell<HashMap<String, Option<PageNumber>>>,
+    // Ids of the savepoints which may still be restored, in the order they were created
+    live_savepoints: RefCell<Vec<u64>>,
+    next_savepoint_id: Cell<u64>,
     completed: AtomicBool,
     durability: Durability,
 }
@@ -114,6 +135,7 @@ impl<'db> WriteTransaction<'db> {
     pub(crate) unsafe fn new(db: &'db Database, transaction_id: TransactionId) -> Result<Self> {
         let root_page = db.get_memory().get_data_root();
         let freed_root = db.get_memory().get_freed_root();
+        let snapshot_root = db.get_memory().get_snapshot_root();
         let freed_pages = Rc::new(RefCell::new(vec![]));
         Ok(Self {
             db,
@@ -125,9 +147,17 @@ impl<'db> WriteTransaction<'db> {
                 freed_pages.clone(),
             )),
             freed_tree: BtreeMut::new(freed_root, db.get_memory(), freed_pages.clone()),
+            snapshot_tree: RefCell::new(BtreeMut::new(
+                snapshot_root,
+                db.get_memory(),
+                freed_pages.clone(),
+            )),
             freed_pages,
+            committed_root: root_page,
+            committed_transaction_id: db.get_memory().get_last_committed_transaction_id()?,
             open_tables: RefCell::new(Default::default()),
-            pending_table_updates: RefCell::new(Default::default()),
+            live_savepoints: RefCell::new(vec![]),
+            next_savepoint_id: Cell::new(0),
             completed: Default::default(),
             durability: Durability::Immediate,
         })
@@ -139,6 +169,97 @@ impl<'db> WriteTransaction<'db> {
         self.durability = durability;
     }
 
+    /// Capture the current state of the transaction, so that later writes can be undone with
+    /// [`Self::restore_savepoint`] without aborting the whole transaction
+    ///
+    /// Pages written before the savepoint are no longer modified in place, so writes after a
+    /// savepoint may allocate more space than they otherwise would
+    pub fn savepoint(&mut self) -> Savepoint {
+        assert!(self.open_tables.borrow().is_empty());
+        let id = self.next_savepoint_id.get();
+        self.next_savepoint_id.set(id + 1);
+        self.live_savepoints.borrow_mut().push(id);
+
+        Savepoint {
+            id,
+            transaction_id: self.transaction_id,
+            log_position: self.mem.create_savepoint(),
+            root: self.table_tree.borrow().get_root(),
+            freed_root: self.freed_tree.get_root(),
+            snapshot_root: self.snapshot_tree.borrow().get_root(),
+            freed_pages: self.freed_pages.borrow().clone(),
+            pending_table_updates: self.table_tree.borrow().pending_table_updates().clone(),
+        }
+    }
+
+    /// Undo all writes performed since `savepoint` was created
+    ///
+    /// The savepoint remains valid and may be restored again. Savepoints created after it are
+    /// invalidated. Returns [`Error::InvalidSavepoint`] if the savepoint belongs to another
+    /// transaction, or was invalidated
+    pub fn restore_savepoint(&mut self, savepoint: &Savepoint) -> Result {
+        assert!(self.open_tables.borrow().is_empty());
+        if savepoint.transaction_id != self.transaction_id {
+            return Err(Error::InvalidSavepoint);
+        }
+        let mut live_savepoints = self.live_savepoints.borrow_mut();
+        match live_savepoints.iter().position(|id| *id == savepoint.id) {
+            Some(index) => live_savepoints.truncate(index + 1),
+            None => return Err(Error::InvalidSavepoint),
+        }
+        drop(live_savepoints);
+
+        self.mem.restore_savepoint(savepoint.log_position)?;
+        let mut table_tree = TableTree::new(savepoint.root, self.mem, self.freed_pages.clone());
+        table_tree.set_pending_table_updates(savepoint.pending_table_updates.clone());
+        *self.table_tree.borrow_mut() = table_tree;
+        self.freed_tree = BtreeMut::new(savepoint.freed_root, self.mem, self.freed_pages.clone());
+        *self.snapshot_tree.borrow_mut() =
+            BtreeMut::new(savepoint.snapshot_root, self.mem, self.freed_pages.clone());
+        *self.freed_pages.borrow_mut() = savepoint.freed_pages.clone();
+
+        Ok(())
+    }
+
+    /// Create a persistent snapshot of the database, as it was when this transaction began
+    ///
+    /// The snapshot is stored in the database once this transaction commits, and can then be read
+    /// with [`Database::begin_read_snapshot`], even after the database is reopened.
+    ///
+    /// Like a long running read transaction, a snapshot prevents the reuse of every page freed by
+    /// a later transaction, not only of those it references. The file therefore grows with each
+    /// write made while the snapshot exists, until it is deleted with [`Self::delete_snapshot`].
+    ///
+    /// Returns [`Error::SnapshotAlreadyExists`] if a snapshot with this name already exists
+    pub fn create_snapshot(&self, name: &str) -> Result {
+        let mut snapshot_tree = self.snapshot_tree.borrow_mut();
+        if snapshot_tree.get(name)?.is_some() {
+            return Err(Error::SnapshotAlreadyExists(name.to_string()));
+        }
+        let record = SnapshotRecord {
+            root: self.committed_root,
+            transaction_id: self.committed_transaction_id,
+        };
+        // Safety: References into the snapshot table are never returned to the user
+        unsafe { snapshot_tree.insert(name, &record)? };
+        Ok(())
+    }
+
+    /// Delete the given persistent snapshot, allowing the pages it retains to be reused
+    ///
+    /// Returns a bool indicating whether the snapshot existed
+    pub fn delete_snapshot(&self, name: &str) -> Result<bool> {
+        // Safety: References into the snapshot table are never returned to the user
+        let found = unsafe { self.snapshot_tree.borrow_mut().remove(name)?.is_some() };
+        Ok(found)
+    }
+
+    /// List all the persistent snapshots
+    pub fn list_snapshots(&self) -> Result<impl Iterator<Item = String>> {
+        let root = self.snapshot_tree.borrow().get_root();
+        list_snapshots(&Btree::new(root, self.mem))
+    }
+
     /// Open the given table
     ///
     /// The table will be created if it does not exist
@@ -152,15 +273,15 @@ impl<'db> WriteTransaction<'db> {
                 location,
             ));
         }
+        let internal_table = self.table_tree.borrow_mut().get_or_create_table::<K, V>(
+            definition.name(),
+            TableType::Normal,
+            definition.version(),
+        )?;
         self.open_tables
             .borrow_mut()
             .insert(definition.name().to_string(), panic::Location::caller());
 
-        let internal_table = self
-            .table_tree
-            .borrow_mut()
-            .get_or_create_table::<K, V>(definition.name(), TableType::Normal)?;
-
         Ok(Table::new(
             definition.name(),
             internal_table.get_root(),
@@ -183,15 +304,15 @@ impl<'db> WriteTransaction<'db> {
                 location,
             ));
         }
+        let internal_table = self.table_tree.borrow_mut().get_or_create_table::<K, V>(
+            definition.name(),
+            TableType::Multimap,
+            definition.version(),
+        )?;
         self.open_tables
             .borrow_mut()
             .insert(definition.name().to_string(), panic::Location::caller());
 
-        let internal_table = self
-            .table_tree
-            .borrow_mut()
-            .get_or_create_table::<K, V>(definition.name(), TableType::Multimap)?;
-
         Ok(MultimapTable::new(
             definition.name(),
             internal_table.get_root(),
@@ -201,15 +322,86 @@ impl<'db> WriteTransaction<'db> {
         ))
     }
 
+    /// Rewrites the given table into new key and value types, and a new schema version
+    ///
+    /// The table must currently have key type `K1` and value type `V1`, and may have any version.
+    /// Each of its entries is passed to `f`, which returns the entry to insert into the new table.
+    /// The returned key and value cannot borrow from the entry passed to `f`, so they must be
+    /// owned. Like any other write, the new table is only visible to other transactions once this
+    /// one commits, and if the migration fails the table is left unchanged. If `f` maps two entries
+    /// to the same key, the migration fails with `Error::DuplicateKey`.
+    ///
+    /// Returns false, without calling `f`, if the table does not exist or already has the types
+    /// and version of `definition`, so it's safe to call every time the database is opened.
+    ///
+    /// Multimap tables can't be migrated: passing the name of one returns
+    /// `Error::TableTypeMismatch`.
+    ///
+    /// ```rust
+    /// use redb::{Database, Error, ReadableTable, TableDefinition};
+    /// # use tempfile::NamedTempFile;
+    ///
+    /// const V0: TableDefinition<u64, u32> = TableDefinition::new("scores");
+    /// const V1: TableDefinition<str, u64> = TableDefinition::new("scores").with_version(1);
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    /// # let filename = tmpfile.path();
+    /// let db = Database::create(filename, 1024 * 1024)?;
+    /// let write_txn = db.begin_write()?;
+    /// write_txn.open_table(V0)?.insert(&7, &100)?;
+    /// write_txn.commit()?;
+    ///
+    /// let write_txn = db.begin_write()?;
+    /// let migrated = write_txn.migrate_table::<u64, u32, _, _, _, _, _>(V1, |id, score| {
+    ///     (format!("user{}", id), u64::from(score))
+    /// })?;
+    /// assert!(migrated);
+    /// assert_eq!(100, write_txn.open_table(V1)?.get("user7")?.unwrap());
+    /// write_txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub fn migrate_table<K1, V1, K2, V2, F, KB, VB>(
+        &self,
+        definition: TableDefinition<K2, V2>,
+        f: F,
+    ) -> Result<bool>
+    where
+        K1: RedbKey + ?Sized,
+        V1: RedbValue + ?Sized,
+        K2: RedbKey + ?Sized,
+        V2: RedbValue + ?Sized,
+        F: for<'f> FnMut(
+            <<K1 as RedbValue>::View as WithLifetime<'f>>::Out,
+            <<V1 as RedbValue>::View as WithLifetime<'f>>::Out,
+        ) -> (KB, VB),
+        KB: std::borrow::Borrow<K2>,
+        VB: std::borrow::Borrow<V2>,
+    {
+        if let Some(location) = self.open_tables.borrow().get(definition.name()) {
+            return Err(Error::TableAlreadyOpen(
+                definition.name().to_string(),
+                location,
+            ));
+        }
+
+        self.table_tree
+            .borrow_mut()
+            .migrate_table::<K1, V1, K2, V2, F, KB, VB>(definition.name(), definition.version(), f)
+    }
+
     pub(crate) fn close_table<K: RedbKey + ?Sized, V: RedbValue + ?Sized>(
         &self,
         name: &str,
         table: &mut BtreeMut<K, V>,
     ) {
         self.open_tables.borrow_mut().remove(name).unwrap();
-        self.pending_table_updates
+        self.table_tree
             .borrow_mut()
-            .insert(name.to_string(), table.get_root());
+            .stage_update_table_root(name, table.get_root());
     }
 
     /// Delete the given table
@@ -219,9 +411,11 @@ impl<'db> WriteTransaction<'db> {
         &self,
         definition: TableDefinition<K, V>,
     ) -> Result<bool> {
-        self.table_tree
-            .borrow_mut()
-            .delete_table::<K, V>(definition.name(), TableType::Normal)
+        self.table_tree.borrow_mut().delete_table::<K, V>(
+            definition.name(),
+            TableType::Normal,
+            definition.version(),
+        )
     }
 
     /// Delete the given table
@@ -231,9 +425,11 @@ impl<'db> WriteTransaction<'db> {
         &self,
         definition: MultimapTableDefinition<K, V>,
     ) -> Result<bool> {
-        self.table_tree
-            .borrow_mut()
-            .delete_table::<K, V>(definition.name(), TableType::Multimap)
+        self.table_tree.borrow_mut().delete_table::<K, V>(
+            definition.name(),
+            TableType::Multimap,
+            definition.version(),
+        )
     }
 
     /// List all the tables
@@ -259,11 +455,7 @@ impl<'db> WriteTransaction<'db> {
     /// All writes performed in this transaction will be visible to future transactions, and are
     /// durable as consistent with the [`Durability`] level set by [`Self::set_durability`]
     pub fn commit(mut self) -> Result {
-        for (name, root) in self.pending_table_updates.borrow_mut().drain() {
-            self.table_tree
-                .borrow_mut()
-                .update_table_root(&name, root)?;
-        }
+        self.table_tree.borrow_mut().flush_table_root_updates()?;
         match self.commit_inner() {
             Ok(_) => {
                 self.db.deallocate_write_transaction(self.transaction_id);
@@ -303,11 +495,54 @@ impl<'db> WriteTransaction<'db> {
         Ok(())
     }
 
+    // Moves live pages into free space closer to the start of the file. Used by compaction.
+    // Returns true if any page was moved
+    pub(crate) fn relocate_pages(&mut self) -> Result<bool> {
+        assert!(self.open_tables.borrow().is_empty());
+        let mut moved = self.table_tree.borrow_mut().relocate()?;
+        // Safety: the freed tree is private to the write transaction
+        moved |= unsafe { self.freed_tree.relocate()? };
+        // Safety: References into the snapshot table are never returned to the user
+        moved |= unsafe { self.snapshot_tree.borrow_mut().relocate()? };
+
+        Ok(moved)
+    }
+
+    // Pages freed after a snapshot was taken must be retained, just as for a live read transaction
+    fn oldest_snapshot(&self) -> Result<Option<TransactionId>> {
+        let snapshot_tree = self.snapshot_tree.borrow();
+        let iter = snapshot_tree.range::<RangeFull, &str>(..)?;
+        let mut oldest = None;
+        for entry in iter {
+            let transaction_id = SnapshotRecord::from_bytes(entry?.value()).transaction_id;
+            oldest = Some(min(oldest.unwrap_or(transaction_id), transaction_id));
+        }
+        Ok(oldest)
+    }
+
+    // Pages which are reachable only from persistent snapshots, and so must be freed once the last
+    // snapshot referencing them is deleted. Used when the allocator state is rebuilt
+    pub(crate) fn retain_snapshot_pages(&self, pages: impl IntoIterator<Item = PageNumber>) {
+        self.freed_pages.borrow_mut().extend(pages);
+    }
+
     pub(crate) fn durable_commit(&mut self, eventual: bool) -> Result {
-        let oldest_live_read = self
+        // Read-only instances check this flag after pinning a commit, since a pin taken while we're
+        // freeing pages may be missed. See TransactionalMemory::is_last_commit()
+        self.mem.set_reclaiming(true);
+        let result = self.durable_commit_inner(eventual);
+        self.mem.set_reclaiming(false);
+        result
+    }
+
+    fn durable_commit_inner(&mut self, eventual: bool) -> Result {
+        let mut oldest_live_read = self
             .db
-            .oldest_live_read_transaction()
+            .oldest_live_read_transaction()?
             .unwrap_or(self.transaction_id);
+        if let Some(oldest_snapshot) = self.oldest_snapshot()? {
+            oldest_live_read = min(oldest_live_read, oldest_snapshot);
+        }
 
         self.process_freed_pages(oldest_live_read)?;
         if oldest_live_read < self.transaction_id {
@@ -324,9 +559,15 @@ impl<'db> WriteTransaction<'db> {
 
         let root = self.table_tree.borrow().get_root();
         let freed_root = self.freed_tree.get_root();
-
-        self.mem
-            .commit(root, freed_root, self.transaction_id, eventual)?;
+        let snapshot_root = self.snapshot_tree.borrow().get_root();
+
+        self.mem.commit(
+            root,
+            freed_root,
+            snapshot_root,
+            self.transaction_id,
+            eventual,
+        )?;
         Ok(())
     }
 
@@ -338,9 +579,10 @@ impl<'db> WriteTransaction<'db> {
 
         let root = self.table_tree.borrow().get_root();
         let freed_root = self.freed_tree.get_root();
+        let snapshot_root = self.snapshot_tree.borrow().get_root();
 
         self.mem
-            .non_durable_commit(root, freed_root, self.transaction_id)?;
+            .non_durable_commit(root, freed_root, snapshot_root, self.transaction_id)?;
         Ok(())
     }
 
@@ -355,8 +597,8 @@ impl<'db> WriteTransaction<'db> {
         };
 
         let mut to_remove = vec![];
-        let mut iter = self.freed_tree.range(..lookup_key)?;
-        while let Some(entry) = iter.next() {
+        for entry in self.freed_tree.range(..lookup_key)? {
+            let entry = entry?;
             to_remove.push(FreedTableKey::from_bytes(entry.key()));
             let value = entry.value();
             let length = u64::from_le_bytes(value[..size_of::<u64>()].try_into().unwrap()) as usize;
@@ -370,7 +612,6 @@ impl<'db> WriteTransaction<'db> {
                 }
             }
         }
-        drop(iter);
 
         // Remove all the old transactions
         for key in to_remove {
@@ -421,7 +662,7 @@ impl<'db> WriteTransaction<'db> {
     pub fn stats(&self) -> Result<DatabaseStats> {
         let table_tree = self.table_tree.borrow();
         let data_tree_stats = table_tree.stats()?;
-        let freed_tree_stats = self.freed_tree.stats();
+        let freed_tree_stats = self.freed_tree.stats()?;
         let total_metadata_bytes = data_tree_stats.metadata_bytes()
             + freed_tree_stats.metadata_bytes
             + freed_tree_stats.stored_leaf_bytes;
@@ -447,9 +688,10 @@ impl<'db> WriteTransaction<'db> {
 
             let master_tree: Btree<str, InternalTableDefinition> = Btree::new(Some(page), self.mem);
             master_tree.print_debug(true);
-            let mut iter = master_tree.range::<RangeFull, &str>(..).unwrap();
+            let iter = master_tree.range::<RangeFull, &str>(..).unwrap();
 
-            while let Some(entry) = iter.next() {
+            for entry in iter {
+                let entry = entry.unwrap();
                 eprintln!("{} tree:", String::from_utf8_lossy(entry.key()));
                 let definition = InternalTableDefinition::from_bytes(entry.value());
                 if let Some(table_root) = definition.get_root() {
@@ -476,15 +718,23 @@ impl<'a> Drop for WriteTransaction<'a> {
 pub struct ReadTransaction<'a> {
     db: &'a Database,
     tree: TableTree<'a>,
+    snapshot_tree: Btree<'a, str, SnapshotRecord>,
     transaction_id: TransactionId,
 }
 
 impl<'db> ReadTransaction<'db> {
-    pub(crate) fn new(db: &'db Database, transaction_id: TransactionId) -> Self {
-        let root_page = db.get_memory().get_data_root();
+    // root_page: the root of the master table to read from. Pages freed after transaction_id must
+    // be retained while this transaction is alive
+    pub(crate) fn new(
+        db: &'db Database,
+        transaction_id: TransactionId,
+        root_page: Option<PageNumber>,
+        snapshot_root: Option<PageNumber>,
+    ) -> Self {
         Self {
             db,
             tree: TableTree::new(root_page, db.get_memory(), Default::default()),
+            snapshot_tree: Btree::new(snapshot_root, db.get_memory()),
             transaction_id,
         }
     }
@@ -496,7 +746,7 @@ impl<'db> ReadTransaction<'db> {
     ) -> Result<ReadOnlyTable<K, V>> {
         let header = self
             .tree
-            .get_table::<K, V>(definition.name(), TableType::Normal)?
+            .get_table::<K, V>(definition.name(), TableType::Normal, definition.version())?
             .ok_or_else(|| Error::TableDoesNotExist(definition.name().to_string()))?;
 
         Ok(ReadOnlyTable::new(header.get_root(), self.db.get_memory()))
@@ -509,7 +759,7 @@ impl<'db> ReadTransaction<'db> {
     ) -> Result<ReadOnlyMultimapTable<K, V>> {
         let header = self
             .tree
-            .get_table::<K, V>(definition.name(), TableType::Multimap)?
+            .get_table::<K, V>(definition.name(), TableType::Multimap, definition.version())?
             .ok_or_else(|| Error::TableDoesNotExist(definition.name().to_string()))?;
 
         Ok(ReadOnlyMultimapTable::new(
@@ -533,6 +783,27 @@ impl<'db> ReadTransaction<'db> {
             .list_tables(TableType::Multimap)
             .map(|x| x.into_iter())
     }
+
+    /// List all the persistent snapshots
+    pub fn list_snapshots(&self) -> Result<impl Iterator<Item = String>> {
+        list_snapshots(&self.snapshot_tree)
+    }
+
+    // Copies all tables visible to this transaction into `destination`, which must be the only
+    // write to a database with the same page size
+    pub(crate) fn copy_tables_to(&self, destination: &WriteTransaction) -> Result {
+        self.tree.copy_to(&mut destination.table_tree.borrow_mut())
+    }
+}
+
+// TODO: should return an iterator of &str, once GATs are available
+fn list_snapshots(tree: &Btree<str, SnapshotRecord>) -> Result<std::vec::IntoIter<String>> {
+    let mut names = vec![];
+    let iter = tree.range::<RangeFull, &str>(..)?;
+    for entry in iter {
+        names.push(str::from_bytes(entry?.key()).to_string());
+    }
+    Ok(names.into_iter())
 }
 
 impl<'a> Drop for ReadTransaction<'a> {
@@ -551,7 +822,7 @@ mod test {
     #[test]
     fn transaction_id_persistence() {
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+        let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
         let write_txn = db.begin_write().unwrap();
         {
             let mut table = write_txn.open_table(X).unwrap();
@@ -561,7 +832,7 @@ mod test {
         write_txn.commit().unwrap();
         drop(db);
 
-        let db2 = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+        let db2 = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
         let write_txn = db2.begin_write().unwrap();
         assert!(write_txn.transaction_id > first_txn_id);
     }
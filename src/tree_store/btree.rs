@@ -1,4 +1,7 @@
-use crate::tree_store::btree_base::{BranchAccessor, FreePolicy, LeafAccessor, BRANCH, LEAF};
+use crate::tree_store::btree_base::{
+    subtree_entries, BranchAccessor, BranchMutator, EntryAccessor, FreePolicy, LeafAccessor,
+    LeafBuilder, LeafMutator, BRANCH, LEAF,
+};
 use crate::tree_store::btree_mutator::MutateHelper;
 use crate::tree_store::page_store::{Page, PageImpl, TransactionalMemory};
 use crate::tree_store::{AccessGuardMut, BtreeRangeIter, PageNumber};
@@ -7,8 +10,9 @@ use crate::{AccessGuard, Result};
 use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::cmp::max;
+use std::collections::Bound;
 use std::marker::PhantomData;
-use std::ops::{RangeBounds, RangeFull};
+use std::ops::RangeBounds;
 use std::rc::Rc;
 
 pub(crate) struct BtreeStats {
@@ -20,6 +24,13 @@ pub(crate) struct BtreeStats {
     pub(crate) fragmented_bytes: usize,
 }
 
+// Location of a key in a leaf page which is private to the current transaction
+pub(crate) struct LeafPosition {
+    pub(crate) page: PageNumber,
+    pub(crate) position: usize,
+    pub(crate) found: bool,
+}
+
 pub(crate) struct BtreeMut<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     mem: &'a TransactionalMemory,
     root: Option<PageNumber>,
@@ -47,8 +58,13 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> BtreeMut<'a, K, V> {
         self.root
     }
 
+    // Returns the previous value, if the key was already present
     // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
-    pub(crate) unsafe fn insert(&mut self, key: &K, value: &V) -> Result {
+    pub(crate) unsafe fn insert(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<Option<AccessGuard<'a, V>>> {
         let mut freed_pages = self.freed_pages.borrow_mut();
         let mut operation = MutateHelper::new(
             &mut self.root,
@@ -56,8 +72,8 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> BtreeMut<'a, K, V> {
             self.mem,
             freed_pages.as_mut(),
         );
-        operation.insert(key, value)?;
-        Ok(())
+        let (_, old_value) = operation.insert(key, value)?;
+        Ok(old_value)
     }
 
     /// Reserve space to insert a key-value pair
@@ -76,7 +92,7 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> BtreeMut<'a, K, V> {
             self.mem,
             freed_pages.as_mut(),
         );
-        let guard = operation.insert(key, value.as_slice())?;
+        let (guard, _) = operation.insert(key, value.as_slice())?;
         Ok(guard)
     }
 
@@ -93,6 +109,252 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> BtreeMut<'a, K, V> {
         Ok(result)
     }
 
+    // Finds the leaf for key, copying the path to it if necessary, so that the entry can be
+    // read and modified without searching the tree again. Returns None if the tree is empty
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    pub(crate) unsafe fn prepare_entry(&mut self, key: &K) -> Result<Option<LeafPosition>> {
+        let mut freed_pages = self.freed_pages.borrow_mut();
+        let mut operation: MutateHelper<K, V> = MutateHelper::new(
+            &mut self.root,
+            FreePolicy::Uncommitted,
+            self.mem,
+            freed_pages.as_mut(),
+        );
+        let result = operation.prepare_leaf(key)?;
+        Ok(result.map(|(page, position, found)| LeafPosition {
+            page,
+            position,
+            found,
+        }))
+    }
+
+    // entry must be occupied, and have been returned by prepare_entry() or insert_entry()
+    pub(crate) fn entry_value(&self, entry: &LeafPosition) -> Result<AccessGuard<V>> {
+        assert!(entry.found);
+        let page = self.mem.get_page(entry.page)?;
+        let accessor = LeafAccessor::new(&page);
+        let (start, end) = accessor.value_range(entry.position).unwrap();
+        drop(accessor);
+        // Safety: the page is not freed on drop
+        Ok(unsafe { AccessGuard::new(page, start, end - start, false, self.mem) })
+    }
+
+    // Inserts or overwrites the value at entry. Returns the new position of the entry, and the
+    // previous value, if any
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    pub(crate) unsafe fn insert_entry(
+        &mut self,
+        entry: Option<&LeafPosition>,
+        key: &K,
+        value: &V,
+    ) -> Result<(LeafPosition, Option<AccessGuard<'a, V>>)> {
+        if let Some(entry) = entry {
+            let key_bytes = key.as_bytes();
+            let value_bytes = value.as_bytes();
+            let key_bytes = key_bytes.as_ref();
+            let value_bytes = value_bytes.as_ref();
+            let page = self.mem.get_page(entry.page)?;
+            if LeafMutator::sufficient_insert_inplace_space(
+                &page,
+                entry.position,
+                entry.found,
+                key_bytes,
+                value_bytes,
+            ) {
+                let old_value = if entry.found {
+                    let accessor = LeafAccessor::new(&page);
+                    let (start, end) = accessor.value_range(entry.position).unwrap();
+                    Some(AccessGuard::with_owned_value(
+                        &page.memory()[start..end],
+                        self.mem,
+                    ))
+                } else {
+                    None
+                };
+                drop(page);
+                let mut page_mut = self.mem.get_page_mut(entry.page);
+                let mut mutator = LeafMutator::new(&mut page_mut);
+                mutator.insert(entry.position, entry.found, key_bytes, value_bytes);
+                drop(page_mut);
+                if !entry.found {
+                    self.adjust_path_entries(key_bytes, true)?;
+                }
+                let new_entry = LeafPosition {
+                    page: entry.page,
+                    position: entry.position,
+                    found: true,
+                };
+                return Ok((new_entry, old_value));
+            }
+        }
+
+        // The leaf has to be split, so fall back to a regular insert
+        let old_value = self.insert(key, value)?;
+        let new_entry = self.prepare_entry(key)?.unwrap();
+        Ok((new_entry, old_value))
+    }
+
+    // Removes the occupied entry, and returns its value
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    pub(crate) unsafe fn remove_entry(
+        &mut self,
+        entry: &LeafPosition,
+        key: &K,
+    ) -> Result<AccessGuard<V>> {
+        assert!(entry.found);
+        let page = self.mem.get_page(entry.page)?;
+        let accessor = LeafAccessor::new(&page);
+        let num_pairs = accessor.num_pairs();
+        let new_kv_bytes = accessor.length_of_pairs(0, num_pairs)
+            - accessor.length_of_pairs(entry.position, entry.position + 1);
+        let new_required_bytes =
+            LeafBuilder::required_bytes(num_pairs - 1, new_kv_bytes, V::alignment());
+        // Same condition as the in-place fast-path of remove(), so that leaves are merged identically
+        if num_pairs > 1 && new_required_bytes >= self.mem.get_usable_page_size() / 2 {
+            let (start, end) = accessor.value_range(entry.position).unwrap();
+            let old_value = AccessGuard::with_owned_value(&page.memory()[start..end], self.mem);
+            drop(accessor);
+            drop(page);
+            let mut page_mut = self.mem.get_page_mut(entry.page);
+            let mut mutator = LeafMutator::new(&mut page_mut);
+            mutator.remove(entry.position);
+            drop(page_mut);
+            self.adjust_path_entries(key.as_bytes().as_ref(), false)?;
+            return Ok(old_value);
+        }
+        drop(accessor);
+        drop(page);
+
+        Ok(self.remove(key)?.unwrap())
+    }
+
+    // Updates the entry counts in the branches above the leaf containing key, after an entry was
+    // inserted into, or removed from, that leaf in place. The path to the leaf must be
+    // uncommitted, as it is after prepare_entry()
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    unsafe fn adjust_path_entries(&mut self, key: &[u8], inserted: bool) -> Result {
+        let mut page_number = if let Some(root) = self.root {
+            root
+        } else {
+            return Ok(());
+        };
+        loop {
+            let page = self.mem.get_page(page_number)?;
+            if page.memory()[0] != BRANCH {
+                return Ok(());
+            }
+            let accessor = BranchAccessor::new(&page);
+            let (child_index, child_page) = accessor.child_for_key::<K>(key)?;
+            let entries = accessor.child_entries(child_index).unwrap();
+            drop(accessor);
+            drop(page);
+            debug_assert!(self.mem.uncommitted(page_number));
+            let mut page_mut = self.mem.get_page_mut(page_number);
+            let mut mutator = BranchMutator::new(&mut page_mut);
+            if inserted {
+                mutator.write_child_entries(child_index, entries + 1);
+            } else {
+                mutator.write_child_entries(child_index, entries - 1);
+            }
+            page_number = child_page;
+        }
+    }
+
+    // Removes the first entry, or the last one if last is true, and returns its key and value
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    #[allow(clippy::type_complexity)]
+    pub(crate) unsafe fn pop(
+        &mut self,
+        last: bool,
+    ) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        let key = if let Some(entry) = self.read_tree().edge_entry(last)? {
+            entry.key().to_vec()
+        } else {
+            return Ok(None);
+        };
+        let mut freed_pages = self.freed_pages.borrow_mut();
+        let mut operation: MutateHelper<K, V> = MutateHelper::new(
+            &mut self.root,
+            FreePolicy::Uncommitted,
+            self.mem,
+            freed_pages.as_mut(),
+        );
+        let value = operation.delete_serialized(&key)?.unwrap();
+        Ok(Some((AccessGuard::with_owned_value(&key, self.mem), value)))
+    }
+
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    pub(crate) unsafe fn bulk_load<I, KR, VR>(&mut self, entries: I, fill_factor: f32) -> Result
+    where
+        I: Iterator<Item = (KR, VR)>,
+        KR: Borrow<K>,
+        VR: Borrow<V>,
+    {
+        let mut freed_pages = self.freed_pages.borrow_mut();
+        let mut operation: MutateHelper<K, V> = MutateHelper::new(
+            &mut self.root,
+            FreePolicy::Uncommitted,
+            self.mem,
+            freed_pages.as_mut(),
+        );
+        operation.bulk_load(entries, fill_factor)
+    }
+
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    pub(crate) unsafe fn remove_range<T: RangeBounds<KR>, KR: Borrow<K> + ?Sized>(
+        &mut self,
+        range: T,
+    ) -> Result {
+        let start = serialize_bound(range.start_bound());
+        let end = serialize_bound(range.end_bound());
+        self.remove_serialized_range(as_slice_bound(&start), as_slice_bound(&end))
+    }
+
+    // Removes all entries in the given range, and returns copies of them
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    #[allow(clippy::type_complexity)]
+    pub(crate) unsafe fn drain<T: RangeBounds<KR>, KR: Borrow<K> + ?Sized>(
+        &mut self,
+        range: T,
+    ) -> Result<Vec<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        let mut entries = vec![];
+        let iter: BtreeRangeIter<K, V> = BtreeRangeIter::new::<_, KR>(
+            (range.start_bound(), range.end_bound()),
+            self.root,
+            self.mem,
+        )?;
+        for entry in iter {
+            let entry = entry?;
+            // Check that the entries can be read, before removing them
+            K::try_from_bytes(entry.key())?;
+            V::try_from_bytes(entry.value())?;
+            entries.push((
+                AccessGuard::with_owned_value(entry.key(), self.mem),
+                AccessGuard::with_owned_value(entry.value(), self.mem),
+            ));
+        }
+        self.remove_range(range)?;
+
+        Ok(entries)
+    }
+
+    // Like remove_range(), but takes the bounds in their serialized form
+    // Safety: caller must ensure that no uncommitted data is accessed within this tree, from other references
+    pub(crate) unsafe fn remove_serialized_range(
+        &mut self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result {
+        let mut freed_pages = self.freed_pages.borrow_mut();
+        let mut operation: MutateHelper<K, V> = MutateHelper::new(
+            &mut self.root,
+            FreePolicy::Uncommitted,
+            self.mem,
+            freed_pages.as_mut(),
+        );
+        operation.delete_range(start, end)
+    }
+
     // Like remove(), but does not free uncommitted data
     pub(crate) fn remove_retain_uncommitted(&mut self, key: &K) -> Result<Option<AccessGuard<V>>> {
         let mut freed_pages = self.freed_pages.borrow_mut();
@@ -106,12 +368,76 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> BtreeMut<'a, K, V> {
         Ok(result)
     }
 
+    // Moves pages of this tree into free space at lower addresses, so that the end of the file
+    // can be truncated. Returns true if any page was moved
+    // Safety: caller must ensure that no references to pages in this tree exist
+    pub(crate) unsafe fn relocate(&mut self) -> Result<bool> {
+        if let Some(root) = self.root {
+            let mut freed_pages = self.freed_pages.borrow_mut();
+            if let Some(new_root) = self.relocate_helper(root, freed_pages.as_mut())? {
+                self.root = Some(new_root);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Returns the new page number, if the page had to be copied
+    unsafe fn relocate_helper(
+        &self,
+        page_number: PageNumber,
+        freed: &mut Vec<PageNumber>,
+    ) -> Result<Option<PageNumber>> {
+        let page = self.mem.get_page(page_number)?;
+        let mut moved_children = vec![];
+        if page.memory()[0] == BRANCH {
+            let accessor = BranchAccessor::new(&page);
+            for i in 0..accessor.count_children() {
+                let child = accessor.child_page(i).unwrap();
+                if let Some(new_child) = self.relocate_helper(child, freed)? {
+                    moved_children.push((i, new_child));
+                }
+            }
+        }
+
+        let mut new_page = if let Some(new_page) = self.mem.allocate_lower(page_number)? {
+            new_page
+        } else if moved_children.is_empty() {
+            return Ok(None);
+        } else if self.mem.uncommitted(page_number) {
+            // Page is private to this transaction, so the child pointers can be updated in place
+            drop(page);
+            let mut page_mut = self.mem.get_page_mut(page_number);
+            let mut mutator = BranchMutator::new(&mut page_mut);
+            for (i, child) in moved_children {
+                mutator.write_child_page(i, child);
+            }
+            return Ok(None);
+        } else {
+            self.mem.allocate(page.memory().len())?
+        };
+
+        new_page.memory_mut().copy_from_slice(page.memory());
+        if !moved_children.is_empty() {
+            let mut mutator = BranchMutator::new(&mut new_page);
+            for (i, child) in moved_children {
+                mutator.write_child_page(i, child);
+            }
+        }
+        let new_page_number = new_page.get_page_number();
+        drop(new_page);
+        drop(page);
+        FreePolicy::Uncommitted.conditional_free(page_number, freed, self.mem)?;
+
+        Ok(Some(new_page_number))
+    }
+
     #[allow(dead_code)]
     pub(crate) fn print_debug(&self, include_values: bool) {
         self.read_tree().print_debug(include_values)
     }
 
-    pub(crate) fn stats(&self) -> BtreeStats {
+    pub(crate) fn stats(&self) -> Result<BtreeStats> {
         self.read_tree().stats()
     }
 
@@ -133,9 +459,89 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> BtreeMut<'a, K, V> {
         self.read_tree().range(range)
     }
 
+    pub(crate) fn prefix(&self, prefix: &K) -> Result<BtreeRangeIter<K, V>> {
+        self.read_tree().prefix(prefix)
+    }
+
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn first(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.read_tree().first()
+    }
+
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn last(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.read_tree().last()
+    }
+
     pub(crate) fn len(&self) -> Result<usize> {
         self.read_tree().len()
     }
+
+    pub(crate) fn rank(&self, key: &K) -> Result<usize> {
+        self.read_tree().rank(key)
+    }
+
+    pub(crate) fn count_range<T: RangeBounds<KR>, KR: Borrow<K> + ?Sized>(
+        &self,
+        range: T,
+    ) -> Result<usize> {
+        self.read_tree().count_range(range)
+    }
+
+    pub(crate) fn approximate_size<T: RangeBounds<KR>, KR: Borrow<K> + ?Sized>(
+        &self,
+        range: T,
+    ) -> Result<u64> {
+        self.read_tree().approximate_size(range)
+    }
+
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn nth(
+        &self,
+        n: usize,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime>::Out,
+            <<V as RedbValue>::View as WithLifetime>::Out,
+        )>,
+    > {
+        self.read_tree().nth(n)
+    }
+}
+
+fn serialize_bound<K: RedbKey + ?Sized, KR: Borrow<K> + ?Sized>(
+    bound: Bound<&KR>,
+) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.borrow().as_bytes().as_ref().to_vec()),
+        Bound::Excluded(key) => Bound::Excluded(key.borrow().as_bytes().as_ref().to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn as_slice_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_slice()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_slice()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
 }
 
 pub(crate) struct Btree<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
@@ -160,8 +566,8 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
         key: &K,
     ) -> Result<Option<<<V as RedbValue>::View as WithLifetime<'a>>::Out>> {
         if let Some(p) = self.root {
-            let root_page = self.mem.get_page(p);
-            return Ok(self.get_helper(root_page, key.as_bytes().as_ref()));
+            let root_page = self.mem.get_page(p)?;
+            self.get_helper(root_page, key.as_bytes().as_ref())
         } else {
             Ok(None)
         }
@@ -172,19 +578,22 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
         &self,
         page: PageImpl<'a>,
         query: &[u8],
-    ) -> Option<<<V as RedbValue>::View as WithLifetime<'a>>::Out> {
+    ) -> Result<Option<<<V as RedbValue>::View as WithLifetime<'a>>::Out>> {
         let node_mem = page.memory();
         match node_mem[0] {
             LEAF => {
                 let accessor = LeafAccessor::new(&page);
-                let entry_index = accessor.find_key::<K>(query)?;
-                let (start, end) = accessor.value_range(entry_index).unwrap();
-                Some(V::from_bytes(&page.into_memory()[start..end]))
+                if let Some(entry_index) = accessor.find_key::<K>(query)? {
+                    let (start, end) = accessor.value_range(entry_index).unwrap();
+                    Ok(Some(V::try_from_bytes(&page.into_memory()[start..end])?))
+                } else {
+                    Ok(None)
+                }
             }
             BRANCH => {
                 let accessor = BranchAccessor::new(&page);
-                let (_, child_page) = accessor.child_for_key::<K>(query);
-                self.get_helper(self.mem.get_page(child_page), query)
+                let (_, child_page) = accessor.child_for_key::<K>(query)?;
+                self.get_helper(self.mem.get_page(child_page)?, query)
             }
             _ => unreachable!(),
         }
@@ -194,23 +603,306 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
         &self,
         range: T,
     ) -> Result<BtreeRangeIter<'a, K, V>> {
-        Ok(BtreeRangeIter::new(range, self.root, self.mem))
+        BtreeRangeIter::new(range, self.root, self.mem)
+    }
+
+    pub(crate) fn prefix(&self, prefix: &K) -> Result<BtreeRangeIter<'a, K, V>> {
+        BtreeRangeIter::prefix(prefix.as_bytes().as_ref(), self.root, self.mem)
+    }
+
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn first(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+            <<V as RedbValue>::View as WithLifetime<'a>>::Out,
+        )>,
+    > {
+        self.edge_entry(false)?.map(Self::decode_entry).transpose()
+    }
+
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn last(
+        &self,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+            <<V as RedbValue>::View as WithLifetime<'a>>::Out,
+        )>,
+    > {
+        self.edge_entry(true)?.map(Self::decode_entry).transpose()
+    }
+
+    // Returns the first entry, or the last one if last is true, by descending the leftmost
+    // (or rightmost) branch of the tree
+    fn edge_entry(&self, last: bool) -> Result<Option<EntryAccessor<'a>>> {
+        let mut page = if let Some(p) = self.root {
+            self.mem.get_page(p)?
+        } else {
+            return Ok(None);
+        };
+        loop {
+            match page.memory()[0] {
+                LEAF => {
+                    let accessor = LeafAccessor::new(&page);
+                    let entry = if last { accessor.num_pairs() - 1 } else { 0 };
+                    let (key_start, key_end) = accessor.key_range(entry).unwrap();
+                    let (value_start, value_end) = accessor.value_range(entry).unwrap();
+                    drop(accessor);
+                    let mem = page.into_memory();
+                    return Ok(Some(EntryAccessor::new(
+                        &mem[key_start..key_end],
+                        &mem[value_start..value_end],
+                    )));
+                }
+                BRANCH => {
+                    let accessor = BranchAccessor::new(&page);
+                    let child = if last {
+                        accessor.count_children() - 1
+                    } else {
+                        0
+                    };
+                    let child_page = accessor.child_page(child).unwrap();
+                    drop(accessor);
+                    page = self.mem.get_page(child_page)?;
+                }
+                _ => unreachable!(),
+            }
+        }
     }
 
     pub(crate) fn len(&self) -> Result<usize> {
-        let mut iter: BtreeRangeIter<[u8], [u8]> =
-            BtreeRangeIter::new::<RangeFull, [u8]>(.., self.root, self.mem);
-        let mut count = 0;
-        while iter.next().is_some() {
-            count += 1;
+        if let Some(p) = self.root {
+            Ok(subtree_entries(&self.mem.get_page(p)?) as usize)
+        } else {
+            Ok(0)
+        }
+    }
+
+    // Returns the number of keys which are less than key
+    pub(crate) fn rank(&self, key: &K) -> Result<usize> {
+        let query = key.as_bytes();
+        let (rank, _) = self.bound_position(Bound::Included(query.as_ref()), false)?;
+        Ok(rank as usize)
+    }
+
+    // Returns the number of entries in the range
+    pub(crate) fn count_range<T: RangeBounds<KR>, KR: Borrow<K> + ?Sized>(
+        &self,
+        range: T,
+    ) -> Result<usize> {
+        let start = serialize_bound(range.start_bound());
+        let end = serialize_bound(range.end_bound());
+        let (start, _) = self.bound_position(as_slice_bound(&start), false)?;
+        let (end, _) = self.bound_position(as_slice_bound(&end), true)?;
+        Ok(end.saturating_sub(start) as usize)
+    }
+
+    // Estimates the number of bytes of keys and values in the range, by assuming that its entries
+    // are the same size, on average, as those in the leaves at either end of it
+    pub(crate) fn approximate_size<T: RangeBounds<KR>, KR: Borrow<K> + ?Sized>(
+        &self,
+        range: T,
+    ) -> Result<u64> {
+        let start = serialize_bound(range.start_bound());
+        let end = serialize_bound(range.end_bound());
+        let (start, (start_entries, start_bytes)) =
+            self.bound_position(as_slice_bound(&start), false)?;
+        let (end, (end_entries, end_bytes)) = self.bound_position(as_slice_bound(&end), true)?;
+        let count = end.saturating_sub(start);
+        if count == 0 {
+            return Ok(0);
+        }
+        // The range is non-empty, so at least one of the leaves has entries
+        let sampled_entries = start_entries + end_entries;
+        let sampled_bytes = start_bytes + end_bytes;
+        Ok(count * sampled_bytes / sampled_entries)
+    }
+
+    // Returns the number of entries which precede the bound, treating it as the start of a range,
+    // or as the end of one if is_end is true. Also returns the number of entries, and of bytes of
+    // keys and values, in the leaf where the bound falls
+    fn bound_position(&self, bound: Bound<&[u8]>, is_end: bool) -> Result<(u64, (u64, u64))> {
+        let mut page = if let Some(p) = self.root {
+            self.mem.get_page(p)?
+        } else {
+            return Ok((0, (0, 0)));
+        };
+        let mut preceding = 0;
+        loop {
+            match page.memory()[0] {
+                LEAF => {
+                    let accessor = LeafAccessor::new(&page);
+                    let num_pairs = accessor.num_pairs();
+                    let position = match bound {
+                        Bound::Included(query) | Bound::Excluded(query) => {
+                            let (position, found) = accessor.position::<K>(query)?;
+                            // An included end, or excluded start, also covers an equal key
+                            let covers_equal = is_end == matches!(bound, Bound::Included(_));
+                            if found && covers_equal {
+                                position + 1
+                            } else {
+                                position
+                            }
+                        }
+                        Bound::Unbounded => {
+                            if is_end {
+                                num_pairs
+                            } else {
+                                0
+                            }
+                        }
+                    };
+                    let bytes = accessor.length_of_pairs(0, num_pairs);
+                    return Ok((
+                        preceding + position as u64,
+                        (num_pairs as u64, bytes as u64),
+                    ));
+                }
+                BRANCH => {
+                    let accessor = BranchAccessor::new(&page);
+                    let child_index = match bound {
+                        Bound::Included(query) | Bound::Excluded(query) => {
+                            accessor.child_for_key::<K>(query)?.0
+                        }
+                        Bound::Unbounded => {
+                            if is_end {
+                                accessor.count_children() - 1
+                            } else {
+                                0
+                            }
+                        }
+                    };
+                    for i in 0..child_index {
+                        preceding += accessor.child_entries(i).unwrap();
+                    }
+                    let child_page = accessor.child_page(child_index).unwrap();
+                    drop(accessor);
+                    page = self.mem.get_page(child_page)?;
+                }
+                _ => unreachable!(),
+            }
         }
-        Ok(count)
+    }
+
+    // Returns the entry at index n, in key order
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn nth(
+        &self,
+        n: usize,
+    ) -> Result<
+        Option<(
+            <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+            <<V as RedbValue>::View as WithLifetime<'a>>::Out,
+        )>,
+    > {
+        self.nth_entry(n as u64)?
+            .map(Self::decode_entry)
+            .transpose()
+    }
+
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    fn decode_entry(
+        entry: EntryAccessor<'a>,
+    ) -> Result<(
+        <<K as RedbValue>::View as WithLifetime<'a>>::Out,
+        <<V as RedbValue>::View as WithLifetime<'a>>::Out,
+    )> {
+        Ok((
+            K::try_from_bytes(entry.key())?,
+            V::try_from_bytes(entry.value())?,
+        ))
+    }
+
+    // Descends to the entry at index n, skipping over subtrees using their entry counts
+    fn nth_entry(&self, mut n: u64) -> Result<Option<EntryAccessor<'a>>> {
+        let mut page = if let Some(p) = self.root {
+            self.mem.get_page(p)?
+        } else {
+            return Ok(None);
+        };
+        if n >= subtree_entries(&page) {
+            return Ok(None);
+        }
+        loop {
+            match page.memory()[0] {
+                LEAF => {
+                    let accessor = LeafAccessor::new(&page);
+                    let (key_start, key_end) = accessor.key_range(n as usize).unwrap();
+                    let (value_start, value_end) = accessor.value_range(n as usize).unwrap();
+                    drop(accessor);
+                    let mem = page.into_memory();
+                    return Ok(Some(EntryAccessor::new(
+                        &mem[key_start..key_end],
+                        &mem[value_start..value_end],
+                    )));
+                }
+                BRANCH => {
+                    let accessor = BranchAccessor::new(&page);
+                    let mut child = 0;
+                    loop {
+                        let entries = accessor.child_entries(child).unwrap();
+                        if n < entries {
+                            break;
+                        }
+                        n -= entries;
+                        child += 1;
+                    }
+                    let child_page = accessor.child_page(child).unwrap();
+                    drop(accessor);
+                    page = self.mem.get_page(child_page)?;
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Copies every page of the tree into `destination`, which must use the same page size.
+    // Returns the root of the copy
+    pub(crate) fn copy_to(&self, destination: &TransactionalMemory) -> Result<Option<PageNumber>> {
+        if let Some(root) = self.root {
+            Ok(Some(self.copy_helper(root, destination)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn copy_helper(
+        &self,
+        page_number: PageNumber,
+        destination: &TransactionalMemory,
+    ) -> Result<PageNumber> {
+        let page = self.mem.get_page(page_number)?;
+        let mut children = vec![];
+        if page.memory()[0] == BRANCH {
+            let accessor = BranchAccessor::new(&page);
+            for i in 0..accessor.count_children() {
+                let child = accessor.child_page(i).unwrap();
+                children.push(self.copy_helper(child, destination)?);
+            }
+        }
+
+        let mut new_page = destination.allocate(page.memory().len())?;
+        new_page.memory_mut().copy_from_slice(page.memory());
+        if !children.is_empty() {
+            let mut mutator = BranchMutator::new(&mut new_page);
+            for (i, child) in children.into_iter().enumerate() {
+                mutator.write_child_page(i, child);
+            }
+        }
+
+        Ok(new_page.get_page_number())
     }
 
     #[allow(dead_code)]
     pub(crate) fn print_debug(&self, include_values: bool) {
         if let Some(p) = self.root {
-            let mut pages = vec![self.mem.get_page(p)];
+            let mut pages = vec![self.mem.get_page(p).unwrap()];
             while !pages.is_empty() {
                 let mut next_children = vec![];
                 for page in pages.drain(..) {
@@ -223,7 +915,7 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
                             let accessor = BranchAccessor::new(&page);
                             for i in 0..accessor.count_children() {
                                 let child = accessor.child_page(i).unwrap();
-                                next_children.push(self.mem.get_page(child));
+                                next_children.push(self.mem.get_page(child).unwrap());
                             }
                             accessor.print_node::<K>();
                         }
@@ -238,23 +930,23 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
         }
     }
 
-    pub(crate) fn stats(&self) -> BtreeStats {
+    pub(crate) fn stats(&self) -> Result<BtreeStats> {
         if let Some(root) = self.root {
             self.stats_helper(root)
         } else {
-            BtreeStats {
+            Ok(BtreeStats {
                 tree_height: 0,
                 leaf_pages: 0,
                 branch_pages: 0,
                 stored_leaf_bytes: 0,
                 metadata_bytes: 0,
                 fragmented_bytes: 0,
-            }
+            })
         }
     }
 
-    fn stats_helper(&self, page_number: PageNumber) -> BtreeStats {
-        let page = self.mem.get_page(page_number);
+    fn stats_helper(&self, page_number: PageNumber) -> Result<BtreeStats> {
+        let page = self.mem.get_page(page_number)?;
         let node_mem = page.memory();
         match node_mem[0] {
             LEAF => {
@@ -262,14 +954,14 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
                 let leaf_bytes = accessor.length_of_pairs(0, accessor.num_pairs());
                 let overhead_bytes = accessor.total_length() - leaf_bytes;
                 let fragmented_bytes = page.memory().len() - accessor.total_length();
-                BtreeStats {
+                Ok(BtreeStats {
                     tree_height: 1,
                     leaf_pages: 1,
                     branch_pages: 0,
                     stored_leaf_bytes: leaf_bytes,
                     metadata_bytes: overhead_bytes,
                     fragmented_bytes,
-                }
+                })
             }
             BRANCH => {
                 let accessor = BranchAccessor::new(&page);
@@ -281,7 +973,7 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
                 let mut fragmented_bytes = page.memory().len() - accessor.total_length();
                 for i in 0..accessor.count_children() {
                     if let Some(child) = accessor.child_page(i) {
-                        let stats = self.stats_helper(child);
+                        let stats = self.stats_helper(child)?;
                         max_child_height = max(max_child_height, stats.tree_height);
                         leaf_pages += stats.leaf_pages;
                         branch_pages += stats.branch_pages;
@@ -291,14 +983,14 @@ impl<'a, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Btree<'a, K, V> {
                     }
                 }
 
-                BtreeStats {
+                Ok(BtreeStats {
                     tree_height: max_child_height + 1,
                     leaf_pages,
                     branch_pages,
                     stored_leaf_bytes,
                     metadata_bytes,
                     fragmented_bytes,
-                }
+                })
             }
             _ => unreachable!(),
         }
@@ -8,6 +8,21 @@ use std::mem::size_of;
 
 pub(super) const LEAF: u8 = 1;
 pub(super) const BRANCH: u8 = 2;
+// Page memory is aligned to at least this, so values in a leaf can be aligned to as much
+const MAX_VALUE_ALIGNMENT: usize = 8;
+
+fn align_up(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+// Returns the number of entries in the leaves of the subtree rooted at page
+pub(super) fn subtree_entries<T: Page>(page: &T) -> u64 {
+    match page.memory()[0] {
+        LEAF => LeafAccessor::new(page).num_pairs() as u64,
+        BRANCH => BranchAccessor::new(page).total_entries(),
+        _ => unreachable!(),
+    }
+}
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum FreePolicy {
@@ -49,6 +64,8 @@ enum OnDrop {
 enum EitherPage<'a> {
     Immutable(PageImpl<'a>),
     Mutable(PageMut<'a>),
+    // A copy of data which was stored in a page that has since been modified
+    OwnedMemory(OwnedValue),
 }
 
 impl<'a> EitherPage<'a> {
@@ -56,10 +73,41 @@ impl<'a> EitherPage<'a> {
         match self {
             EitherPage::Immutable(page) => page.memory(),
             EitherPage::Mutable(page) => page.memory(),
+            EitherPage::OwnedMemory(data) => data.as_bytes(),
         }
     }
 }
 
+// A copy of a value which was stored in a page. It's stored in u64s, so that it keeps the
+// alignment which the value had in the page
+pub(crate) struct OwnedValue {
+    data: Vec<u64>,
+    len: usize,
+}
+
+impl OwnedValue {
+    pub(crate) fn new(value: &[u8]) -> Self {
+        let mut data = vec![0u64; (value.len() + size_of::<u64>() - 1) / size_of::<u64>()];
+        // Safety: data is at least value.len() bytes long, and does not overlap value
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                value.as_ptr(),
+                data.as_mut_ptr() as *mut u8,
+                value.len(),
+            );
+        }
+        Self {
+            data,
+            len: value.len(),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        // Safety: the first len bytes of data were initialized in new()
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr() as *const u8, self.len) }
+    }
+}
+
 pub struct AccessGuard<'a, V: RedbValue + ?Sized> {
     page: EitherPage<'a>,
     offset: usize,
@@ -94,6 +142,18 @@ impl<'a, V: RedbValue + ?Sized> AccessGuard<'a, V> {
         }
     }
 
+    pub(super) fn with_owned_value(value: &[u8], mem: &'a TransactionalMemory) -> Self {
+        let len = value.len();
+        Self {
+            page: EitherPage::OwnedMemory(OwnedValue::new(value)),
+            offset: 0,
+            len,
+            on_drop: OnDrop::None,
+            mem,
+            _value_type: Default::default(),
+        }
+    }
+
     // Safety: if free_on_drop is true, caller must guarantee that no other references to page exist,
     // and that no references will be created until this AccessGuard is dropped
     pub(super) unsafe fn remove_on_drop(
@@ -114,8 +174,8 @@ impl<'a, V: RedbValue + ?Sized> AccessGuard<'a, V> {
     }
 
     // TODO: implement Deref instead of this to_value() method, when GAT is stable
-    pub fn to_value(&self) -> <<V as RedbValue>::View as WithLifetime>::Out {
-        V::from_bytes(&self.page.memory()[self.offset..(self.offset + self.len)])
+    pub fn to_value(&self) -> Result<<<V as RedbValue>::View as WithLifetime>::Out> {
+        V::try_from_bytes(&self.page.memory()[self.offset..(self.offset + self.len)])
     }
 }
 
@@ -167,7 +227,7 @@ pub struct EntryAccessor<'a> {
 }
 
 impl<'a> EntryAccessor<'a> {
-    fn new(key: &'a [u8], value: &'a [u8]) -> Self {
+    pub(super) fn new(key: &'a [u8], value: &'a [u8]) -> Self {
         EntryAccessor { key, value }
     }
 }
@@ -186,6 +246,7 @@ impl<'a: 'b, 'b> EntryAccessor<'a> {
 pub(super) struct LeafAccessor<'a: 'b, 'b, T: Page + 'a> {
     page: &'b T,
     num_pairs: usize,
+    value_alignment: usize,
     _page_lifetime: PhantomData<&'a ()>,
 }
 
@@ -196,6 +257,7 @@ impl<'a: 'b, 'b, T: Page + 'a> LeafAccessor<'a, 'b, T> {
         LeafAccessor {
             page,
             num_pairs,
+            value_alignment: page.memory()[1] as usize,
             _page_lifetime: Default::default(),
         }
     }
@@ -216,7 +278,8 @@ impl<'a: 'b, 'b, T: Page + 'a> LeafAccessor<'a, 'b, T> {
         eprint!("]");
     }
 
-    pub(super) fn position<K: RedbKey + ?Sized>(&self, query: &[u8]) -> (usize, bool) {
+    // Returns Error::Corrupted if a key in this page is not valid for K
+    pub(super) fn position<K: RedbKey + ?Sized>(&self, query: &[u8]) -> Result<(usize, bool)> {
         // inclusive
         let mut min_entry = 0;
         // inclusive. Start past end, since it might be positioned beyond the end of the leaf
@@ -224,12 +287,12 @@ impl<'a: 'b, 'b, T: Page + 'a> LeafAccessor<'a, 'b, T> {
         while min_entry < max_entry {
             let mid = (min_entry + max_entry) / 2;
             let key = self.key_unchecked(mid);
-            match K::compare(query, key) {
+            match K::try_compare(query, key)? {
                 Ordering::Less => {
                     max_entry = mid;
                 }
                 Ordering::Equal => {
-                    return (mid, true);
+                    return Ok((mid, true));
                 }
                 Ordering::Greater => {
                     min_entry = mid + 1;
@@ -237,15 +300,15 @@ impl<'a: 'b, 'b, T: Page + 'a> LeafAccessor<'a, 'b, T> {
             }
         }
         debug_assert_eq!(min_entry, max_entry);
-        (min_entry, false)
+        Ok((min_entry, false))
     }
 
-    pub(super) fn find_key<K: RedbKey + ?Sized>(&self, query: &[u8]) -> Option<usize> {
-        let (entry, found) = self.position::<K>(query);
+    pub(super) fn find_key<K: RedbKey + ?Sized>(&self, query: &[u8]) -> Result<Option<usize>> {
+        let (entry, found) = self.position::<K>(query)?;
         if found {
-            Some(entry)
+            Ok(Some(entry))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -273,7 +336,10 @@ impl<'a: 'b, 'b, T: Page + 'a> LeafAccessor<'a, 'b, T> {
 
     fn value_start(&self, n: usize) -> Option<usize> {
         if n == 0 {
-            self.key_end(self.num_pairs() - 1)
+            Some(align_up(
+                self.key_end(self.num_pairs() - 1)?,
+                self.value_alignment,
+            ))
         } else {
             self.value_end(n - 1)
         }
@@ -305,6 +371,10 @@ impl<'a: 'b, 'b, T: Page + 'a> LeafAccessor<'a, 'b, T> {
         self.value_start(n)
     }
 
+    pub(super) fn key_range(&self, n: usize) -> Option<(usize, usize)> {
+        Some((self.key_start(n)?, self.key_end(n)?))
+    }
+
     pub(super) fn value_range(&self, n: usize) -> Option<(usize, usize)> {
         Some((self.value_start(n)?, self.value_end(n)?))
     }
@@ -357,25 +427,38 @@ pub(super) struct LeafBuilder<'a, 'b> {
     pairs: Vec<(&'a [u8], &'a [u8])>,
     total_key_bytes: usize,
     total_value_bytes: usize,
+    value_alignment: usize,
     mem: &'b TransactionalMemory,
 }
 
 impl<'a, 'b> LeafBuilder<'a, 'b> {
-    pub(super) fn required_bytes(num_pairs: usize, keys_values_bytes: usize) -> usize {
+    pub(super) fn required_bytes(
+        num_pairs: usize,
+        keys_values_bytes: usize,
+        value_alignment: usize,
+    ) -> usize {
         // Page id & header;
         let mut result = 4;
         // key & value lengths
         result += num_pairs * 2 * size_of::<u32>();
+        // padding before the first value
+        result += value_alignment - 1;
         result += keys_values_bytes;
 
         result
     }
 
-    pub(super) fn new(mem: &'b TransactionalMemory, capacity: usize) -> Self {
+    pub(super) fn new(
+        mem: &'b TransactionalMemory,
+        capacity: usize,
+        value_alignment: usize,
+    ) -> Self {
+        assert!(value_alignment.is_power_of_two() && value_alignment <= MAX_VALUE_ALIGNMENT);
         Self {
             pairs: Vec::with_capacity(capacity),
             total_key_bytes: 0,
             total_value_bytes: 0,
+            value_alignment,
             mem,
         }
     }
@@ -406,8 +489,9 @@ impl<'a, 'b> LeafBuilder<'a, 'b> {
         let required_size = Self::required_bytes(
             self.pairs.len(),
             self.total_key_bytes + self.total_value_bytes,
+            self.value_alignment,
         );
-        required_size > self.mem.get_page_size() && self.pairs.len() > 1
+        required_size > self.mem.get_usable_page_size() && self.pairs.len() > 1
     }
 
     pub(super) fn build_split(self) -> Result<(PageMut<'b>, &'a [u8], PageMut<'b>)> {
@@ -424,10 +508,18 @@ impl<'a, 'b> LeafBuilder<'a, 'b> {
             }
         }
 
-        let required_size =
-            Self::required_bytes(division, first_split_key_bytes + first_split_value_bytes);
+        let required_size = Self::required_bytes(
+            division,
+            first_split_key_bytes + first_split_value_bytes,
+            self.value_alignment,
+        );
         let mut page1 = self.mem.allocate(required_size)?;
-        let mut builder = RawLeafBuilder::new(&mut page1, division, first_split_key_bytes);
+        let mut builder = RawLeafBuilder::new(
+            &mut page1,
+            division,
+            first_split_key_bytes,
+            self.value_alignment,
+        );
         for (key, value) in self.pairs.iter().take(division) {
             builder.append(key, value);
         }
@@ -438,12 +530,14 @@ impl<'a, 'b> LeafBuilder<'a, 'b> {
             self.total_key_bytes + self.total_value_bytes
                 - first_split_key_bytes
                 - first_split_value_bytes,
+            self.value_alignment,
         );
         let mut page2 = self.mem.allocate(required_size)?;
         let mut builder = RawLeafBuilder::new(
             &mut page2,
             self.pairs.len() - division,
             self.total_key_bytes - first_split_key_bytes,
+            self.value_alignment,
         );
         for (key, value) in self.pairs[division..].iter() {
             builder.append(key, value);
@@ -457,9 +551,15 @@ impl<'a, 'b> LeafBuilder<'a, 'b> {
         let required_size = Self::required_bytes(
             self.pairs.len(),
             self.total_key_bytes + self.total_value_bytes,
+            self.value_alignment,
         );
         let mut page = self.mem.allocate(required_size)?;
-        let mut builder = RawLeafBuilder::new(&mut page, self.pairs.len(), self.total_key_bytes);
+        let mut builder = RawLeafBuilder::new(
+            &mut page,
+            self.pairs.len(),
+            self.total_key_bytes,
+            self.value_alignment,
+        );
         for (key, value) in self.pairs {
             builder.append(key, value);
         }
@@ -472,7 +572,7 @@ impl<'a, 'b> LeafBuilder<'a, 'b> {
 // and rewriting all fields if any dynamically sized fields are written
 // Layout is:
 // 1 byte: type
-// 1 byte: reserved (padding to 32bits aligned)
+// 1 byte: value alignment
 // 2 bytes: num_entries (number of pairs)
 // repeating (num_entries times):
 // 4 bytes: key_end
@@ -480,18 +580,26 @@ impl<'a, 'b> LeafBuilder<'a, 'b> {
 // 4 bytes: value_end
 // repeating (num_entries times):
 // * n bytes: key data
+// padding, so that the value data starts at a multiple of the value alignment
 // repeating (num_entries times):
 // * n bytes: value data
 struct RawLeafBuilder<'a: 'b, 'b> {
     page: &'b mut PageMut<'a>,
     num_pairs: usize,
     provisioned_key_bytes: usize,
+    value_alignment: usize,
     pairs_written: usize, // used for debugging
 }
 
 impl<'a: 'b, 'b> RawLeafBuilder<'a, 'b> {
-    fn new(page: &'b mut PageMut<'a>, num_pairs: usize, key_bytes: usize) -> Self {
+    fn new(
+        page: &'b mut PageMut<'a>,
+        num_pairs: usize,
+        key_bytes: usize,
+        value_alignment: usize,
+    ) -> Self {
         page.memory_mut()[0] = LEAF;
+        page.memory_mut()[1] = value_alignment as u8;
         page.memory_mut()[2..4].copy_from_slice(&(num_pairs as u16).to_le_bytes());
         #[cfg(debug_assertions)]
         {
@@ -505,6 +613,7 @@ impl<'a: 'b, 'b> RawLeafBuilder<'a, 'b> {
             page,
             num_pairs,
             provisioned_key_bytes: key_bytes,
+            value_alignment,
             pairs_written: 0,
         }
     }
@@ -534,7 +643,10 @@ impl<'a: 'b, 'b> RawLeafBuilder<'a, 'b> {
             self.key_end(self.pairs_written - 1)
         };
         let value_offset = if self.pairs_written == 0 {
-            4 + 2 * size_of::<u32>() * self.num_pairs + self.provisioned_key_bytes
+            align_up(
+                4 + 2 * size_of::<u32>() * self.num_pairs + self.provisioned_key_bytes,
+                self.value_alignment,
+            )
         } else {
             self.value_end(self.pairs_written - 1)
         };
@@ -579,14 +691,17 @@ impl<'a: 'b, 'b> LeafMutator<'a, 'b> {
         new_value: &[u8],
     ) -> bool {
         let accessor = LeafAccessor::new(page);
+        let remaining = page.memory().len() - accessor.total_length();
         if overwrite {
-            let remaining = page.memory().len() - accessor.total_length();
             let required_delta = (new_key.len() + new_value.len()) as isize
                 - accessor.length_of_pairs(position, position + 1) as isize;
             required_delta <= remaining as isize
         } else {
-            let remaining = page.memory().len() - accessor.total_length();
-            let required_delta = 2 * size_of::<u32>() + new_key.len() + new_value.len();
+            // The values may have to be moved to realign them, after the keys are shifted
+            let required_delta = 2 * size_of::<u32>()
+                + new_key.len()
+                + new_value.len()
+                + (accessor.value_alignment - 1);
             required_delta <= remaining
         }
     }
@@ -604,6 +719,7 @@ impl<'a: 'b, 'b> LeafMutator<'a, 'b> {
         );
 
         let num_pairs = accessor.num_pairs();
+        let values_start = accessor.value_start(0).unwrap();
         let last_key_end = accessor.key_end(accessor.num_pairs() - 1).unwrap();
         let last_value_end = accessor.value_end(accessor.num_pairs() - 1).unwrap();
         let shift_index = if overwrite { i + 1 } else { i };
@@ -694,6 +810,8 @@ impl<'a: 'b, 'b> LeafMutator<'a, 'b> {
             self.page.memory_mut()[dest..(dest + size_of::<u32>())]
                 .copy_from_slice(&inserted_key_end.to_le_bytes());
             debug_assert_eq!(dest, 4 + size_of::<u32>() * i);
+
+            self.realign_values(values_start + 2 * size_of::<u32>() + key.len());
         }
     }
 
@@ -707,6 +825,7 @@ impl<'a: 'b, 'b> LeafMutator<'a, 'b> {
         let value_start = accessor.value_start(i).unwrap();
         let value_end = accessor.value_end(i).unwrap();
         let last_value_end = accessor.value_end(accessor.num_pairs() - 1).unwrap();
+        let values_start = accessor.value_start(0).unwrap();
         drop(accessor);
 
         // Update all the pointers
@@ -767,6 +886,29 @@ impl<'a: 'b, 'b> LeafMutator<'a, 'b> {
         let start = value_end;
         let end = last_value_end;
         self.page.memory_mut().copy_within(start..end, dest);
+
+        self.realign_values(values_start - 2 * size_of::<u32>() - (key_end - key_start));
+    }
+
+    // Shifting the keys moves the value data along with them, so it may no longer be aligned.
+    // values_start is where the value data currently starts
+    fn realign_values(&mut self, values_start: usize) {
+        let accessor = LeafAccessor::new(self.page);
+        let aligned_start = accessor.value_start(0).unwrap();
+        let values_end = accessor.total_length();
+        let num_pairs = accessor.num_pairs();
+        drop(accessor);
+        if aligned_start == values_start {
+            return;
+        }
+        let delta = aligned_start as isize - values_start as isize;
+        assert!(values_end as isize + delta <= self.page.memory().len() as isize);
+        self.page
+            .memory_mut()
+            .copy_within(values_start..values_end, aligned_start);
+        for j in 0..num_pairs {
+            self.update_value_end(j, delta);
+        }
     }
 
     fn update_key_end(&mut self, i: usize, delta: isize) {
@@ -836,17 +978,28 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
         self.key_end(self.num_keys() - 1)
     }
 
-    pub(super) fn child_for_key<K: RedbKey + ?Sized>(&self, query: &[u8]) -> (usize, PageNumber) {
+    // Returns the total number of entries in the leaves below this page
+    pub(super) fn total_entries(&self) -> u64 {
+        (0..self.count_children())
+            .map(|i| self.child_entries(i).unwrap())
+            .sum()
+    }
+
+    // Returns Error::Corrupted if a key in this page is not valid for K
+    pub(super) fn child_for_key<K: RedbKey + ?Sized>(
+        &self,
+        query: &[u8],
+    ) -> Result<(usize, PageNumber)> {
         let mut min_child = 0; // inclusive
         let mut max_child = self.num_keys(); // inclusive
         while min_child < max_child {
             let mid = (min_child + max_child) / 2;
-            match K::compare(query, self.key(mid).unwrap()) {
+            match K::try_compare(query, self.key(mid).unwrap())? {
                 Ordering::Less => {
                     max_child = mid;
                 }
                 Ordering::Equal => {
-                    return (mid, self.child_page(mid).unwrap());
+                    return Ok((mid, self.child_page(mid).unwrap()));
                 }
                 Ordering::Greater => {
                     min_child = mid + 1;
@@ -855,12 +1008,12 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
         }
         debug_assert_eq!(min_child, max_child);
 
-        (min_child, self.child_page(min_child).unwrap())
+        Ok((min_child, self.child_page(min_child).unwrap()))
     }
 
     fn key_offset(&self, n: usize) -> usize {
         if n == 0 {
-            4 + PageNumber::serialized_size() * self.count_children()
+            4 + (PageNumber::serialized_size() + size_of::<u64>()) * self.count_children()
                 + size_of::<u32>() * self.num_keys()
         } else {
             self.key_end(n - 1)
@@ -868,8 +1021,9 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
     }
 
     fn key_end(&self, n: usize) -> usize {
-        let offset =
-            4 + PageNumber::serialized_size() * self.count_children() + size_of::<u32>() * n;
+        let offset = 4
+            + (PageNumber::serialized_size() + size_of::<u64>()) * self.count_children()
+            + size_of::<u32>() * n;
         u32::from_le_bytes(
             self.page.memory()[offset..(offset + size_of::<u32>())]
                 .try_into()
@@ -903,6 +1057,21 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
         ))
     }
 
+    // Returns the number of entries in the leaves below the nth child
+    pub(super) fn child_entries(&self, n: usize) -> Option<u64> {
+        if n >= self.count_children() {
+            return None;
+        }
+
+        let offset =
+            4 + PageNumber::serialized_size() * self.count_children() + size_of::<u64>() * n;
+        Some(u64::from_le_bytes(
+            self.page.memory()[offset..(offset + size_of::<u64>())]
+                .try_into()
+                .unwrap(),
+        ))
+    }
+
     fn num_keys(&self) -> usize {
         self.num_keys
     }
@@ -910,6 +1079,8 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
 
 pub(super) struct BranchBuilder<'a, 'b> {
     children: Vec<PageNumber>,
+    // Number of entries below each child
+    entries: Vec<u64>,
     keys: Vec<&'a [u8]>,
     total_key_bytes: usize,
     mem: &'b TransactionalMemory,
@@ -919,18 +1090,21 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
     pub(super) fn new(mem: &'b TransactionalMemory, child_capacity: usize) -> Self {
         Self {
             children: Vec::with_capacity(child_capacity),
+            entries: Vec::with_capacity(child_capacity),
             keys: Vec::with_capacity(child_capacity - 1),
             total_key_bytes: 0,
             mem,
         }
     }
 
-    pub(super) fn replace_child(&mut self, index: usize, child: PageNumber) {
+    pub(super) fn replace_child(&mut self, index: usize, child: PageNumber, entries: u64) {
         self.children[index] = child;
+        self.entries[index] = entries;
     }
 
-    pub(super) fn push_child(&mut self, child: PageNumber) {
+    pub(super) fn push_child(&mut self, child: PageNumber, entries: u64) {
         self.children.push(child);
+        self.entries.push(entries);
     }
 
     pub(super) fn push_key(&mut self, key: &'a [u8]) {
@@ -940,18 +1114,22 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
 
     pub(super) fn push_all<T: Page>(&mut self, accessor: &'a BranchAccessor<'_, '_, T>) {
         for i in 0..accessor.count_children() {
-            self.push_child(accessor.child_page(i).unwrap());
+            self.push_child(
+                accessor.child_page(i).unwrap(),
+                accessor.child_entries(i).unwrap(),
+            );
         }
         for i in 0..(accessor.count_children() - 1) {
             self.push_key(accessor.key(i).unwrap());
         }
     }
 
-    pub(super) fn to_single_child(&self) -> Option<PageNumber> {
+    // Returns the only child, and the number of entries below it, if there is exactly one
+    pub(super) fn to_single_child(&self) -> Option<(PageNumber, u64)> {
         if self.children.len() > 1 {
             None
         } else {
-            Some(self.children[0])
+            Some((self.children[0], self.entries[0]))
         }
     }
 
@@ -960,10 +1138,10 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
         let size = RawBranchBuilder::required_bytes(self.keys.len(), self.total_key_bytes);
         let mut page = self.mem.allocate(size)?;
         let mut builder = RawBranchBuilder::new(&mut page, self.keys.len());
-        builder.write_first_page(self.children[0]);
+        builder.write_first_page(self.children[0], self.entries[0]);
         for i in 1..self.children.len() {
             let key = &self.keys[i - 1];
-            builder.write_nth_key(key.as_ref(), self.children[i], i - 1);
+            builder.write_nth_key(key.as_ref(), self.children[i], self.entries[i], i - 1);
         }
         drop(builder);
 
@@ -972,7 +1150,7 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
 
     pub(super) fn should_split(&self) -> bool {
         let size = RawBranchBuilder::required_bytes(self.keys.len(), self.total_key_bytes);
-        size > self.mem.get_page_size() && self.keys.len() >= 3
+        size > self.mem.get_usable_page_size() && self.keys.len() >= 3
     }
 
     pub(super) fn build_split(self) -> Result<(PageMut<'b>, &'a [u8], PageMut<'b>)> {
@@ -986,10 +1164,10 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
         let size = RawBranchBuilder::required_bytes(division, first_split_key_len);
         let mut page1 = self.mem.allocate(size)?;
         let mut builder = RawBranchBuilder::new(&mut page1, division);
-        builder.write_first_page(self.children[0]);
+        builder.write_first_page(self.children[0], self.entries[0]);
         for i in 0..division {
             let key = &self.keys[i];
-            builder.write_nth_key(key.as_ref(), self.children[i + 1], i);
+            builder.write_nth_key(key.as_ref(), self.children[i + 1], self.entries[i + 1], i);
         }
         drop(builder);
 
@@ -997,10 +1175,15 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
             RawBranchBuilder::required_bytes(self.keys.len() - division - 1, second_split_key_len);
         let mut page2 = self.mem.allocate(size)?;
         let mut builder = RawBranchBuilder::new(&mut page2, self.keys.len() - division - 1);
-        builder.write_first_page(self.children[division + 1]);
+        builder.write_first_page(self.children[division + 1], self.entries[division + 1]);
         for i in (division + 1)..self.keys.len() {
             let key = &self.keys[i];
-            builder.write_nth_key(key.as_ref(), self.children[i + 1], i - division - 1);
+            builder.write_nth_key(
+                key.as_ref(),
+                self.children[i + 1],
+                self.entries[i + 1],
+                i - division - 1,
+            );
         }
         drop(builder);
 
@@ -1016,6 +1199,8 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
 // 2 bytes: num_keys (number of keys)
 // repeating (num_keys + 1 times):
 // 8 bytes: page number
+// repeating (num_keys + 1 times):
+// 8 bytes: number of entries in the leaves below the page
 // repeating (num_keys times):
 // * 4 bytes: key end. Ending offset of the key, exclusive
 // repeating (num_keys times):
@@ -1028,8 +1213,9 @@ pub(super) struct RawBranchBuilder<'a: 'b, 'b> {
 
 impl<'a: 'b, 'b> RawBranchBuilder<'a, 'b> {
     pub(super) fn required_bytes(num_keys: usize, size_of_keys: usize) -> usize {
-        let fixed_size =
-            4 + PageNumber::serialized_size() * (num_keys + 1) + size_of::<u32>() * num_keys;
+        let fixed_size = 4
+            + (PageNumber::serialized_size() + size_of::<u64>()) * (num_keys + 1)
+            + size_of::<u32>() * num_keys;
         size_of_keys + fixed_size
     }
 
@@ -1040,9 +1226,11 @@ impl<'a: 'b, 'b> RawBranchBuilder<'a, 'b> {
         page.memory_mut()[2..4].copy_from_slice(&(num_keys as u16).to_le_bytes());
         #[cfg(debug_assertions)]
         {
-            // Poison all the child pointers & key offsets, in case the caller forgets to write them
-            let last =
-                4 + PageNumber::serialized_size() * (num_keys + 1) + size_of::<u32>() * num_keys;
+            // Poison all the child pointers, entry counts & key offsets, in case the caller forgets
+            // to write them
+            let last = 4
+                + (PageNumber::serialized_size() + size_of::<u64>()) * (num_keys + 1)
+                + size_of::<u32>() * num_keys;
             for x in &mut page.memory_mut()[4..last] {
                 *x = 0xFF;
             }
@@ -1054,14 +1242,23 @@ impl<'a: 'b, 'b> RawBranchBuilder<'a, 'b> {
         }
     }
 
-    pub(super) fn write_first_page(&mut self, page_number: PageNumber) {
+    pub(super) fn write_first_page(&mut self, page_number: PageNumber, entries: u64) {
         let offset = 4;
         self.page.memory_mut()[offset..(offset + PageNumber::serialized_size())]
             .copy_from_slice(&page_number.to_le_bytes());
+        self.write_entries(0, entries);
+    }
+
+    fn write_entries(&mut self, i: usize, entries: u64) {
+        let offset = 4 + PageNumber::serialized_size() * (self.num_keys + 1) + size_of::<u64>() * i;
+        self.page.memory_mut()[offset..(offset + size_of::<u64>())]
+            .copy_from_slice(&entries.to_le_bytes());
     }
 
     fn key_end(&self, n: usize) -> usize {
-        let offset = 4 + PageNumber::serialized_size() * (self.num_keys + 1) + size_of::<u32>() * n;
+        let offset = 4
+            + (PageNumber::serialized_size() + size_of::<u64>()) * (self.num_keys + 1)
+            + size_of::<u32>() * n;
         u32::from_le_bytes(
             self.page.memory()[offset..(offset + size_of::<u32>())]
                 .try_into()
@@ -1071,21 +1268,30 @@ impl<'a: 'b, 'b> RawBranchBuilder<'a, 'b> {
 
     // Write the nth key and page of values greater than this key, but less than or equal to the next
     // Caller must write keys & pages in increasing order
-    pub(super) fn write_nth_key(&mut self, key: &[u8], page_number: PageNumber, n: usize) {
+    pub(super) fn write_nth_key(
+        &mut self,
+        key: &[u8],
+        page_number: PageNumber,
+        entries: u64,
+        n: usize,
+    ) {
         assert!(n < self.num_keys as usize);
         assert_eq!(n, self.keys_written);
         self.keys_written += 1;
         let offset = 4 + PageNumber::serialized_size() * (n + 1);
         self.page.memory_mut()[offset..(offset + PageNumber::serialized_size())]
             .copy_from_slice(&page_number.to_le_bytes());
+        self.write_entries(n + 1, entries);
 
         let data_offset = if n > 0 {
             self.key_end(n - 1)
         } else {
-            4 + PageNumber::serialized_size() * (self.num_keys + 1)
+            4 + (PageNumber::serialized_size() + size_of::<u64>()) * (self.num_keys + 1)
                 + size_of::<u32>() * self.num_keys
         };
-        let offset = 4 + PageNumber::serialized_size() * (self.num_keys + 1) + size_of::<u32>() * n;
+        let offset = 4
+            + (PageNumber::serialized_size() + size_of::<u64>()) * (self.num_keys + 1)
+            + size_of::<u32>() * n;
         self.page.memory_mut()[offset..(offset + size_of::<u32>())]
             .copy_from_slice(&((data_offset + key.len()) as u32).to_le_bytes());
 
@@ -1120,4 +1326,12 @@ impl<'a: 'b, 'b> BranchMutator<'a, 'b> {
         self.page.memory_mut()[offset..(offset + PageNumber::serialized_size())]
             .copy_from_slice(&page_number.to_le_bytes());
     }
+
+    pub(super) fn write_child_entries(&mut self, i: usize, entries: u64) {
+        debug_assert!(i <= self.num_keys());
+        let offset =
+            4 + PageNumber::serialized_size() * (self.num_keys() + 1) + size_of::<u64>() * i;
+        self.page.memory_mut()[offset..(offset + size_of::<u64>())]
+            .copy_from_slice(&entries.to_le_bytes());
+    }
 }
@@ -3,8 +3,12 @@ use crate::tree_store::btree_base::{BRANCH, LEAF};
 use crate::tree_store::btree_iters::RangeIterState::{Internal, Leaf};
 use crate::tree_store::page_store::{Page, PageImpl, TransactionalMemory};
 use crate::tree_store::PageNumber;
-use crate::types::{RedbKey, RedbValue};
+use crate::types::{
+    AsBytesWithLifetime, RedbKey, RedbValue, RefAsBytesLifetime, RefLifetime, WithLifetime,
+};
+use crate::Result;
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::collections::Bound;
 use std::marker::PhantomData;
 use std::ops::RangeBounds;
@@ -31,8 +35,12 @@ impl<'a> RangeIterState<'a> {
         }
     }
 
-    fn next(self, reverse: bool, manager: &'a TransactionalMemory) -> Option<RangeIterState> {
-        match self {
+    fn next(
+        self,
+        reverse: bool,
+        manager: &'a TransactionalMemory,
+    ) -> Result<Option<RangeIterState<'a>>> {
+        Ok(match self {
             Leaf {
                 page,
                 entry,
@@ -58,7 +66,7 @@ impl<'a> RangeIterState<'a> {
             } => {
                 let accessor = BranchAccessor::new(&page);
                 let child_page = accessor.child_page(child).unwrap();
-                let child_page = manager.get_page(child_page);
+                let child_page = manager.get_page(child_page)?;
                 let direction = if reverse { -1 } else { 1 };
                 let next_child = child as isize + direction;
                 if 0 <= next_child && next_child < accessor.count_children() as isize {
@@ -98,12 +106,23 @@ impl<'a> RangeIterState<'a> {
                     _ => unreachable!(),
                 }
             }
-        }
+        })
     }
 
-    fn get_entry(&self) -> Option<EntryAccessor> {
+    // The returned entry borrows the page memory, rather than the state, so it stays valid for the
+    // lifetime of the transaction
+    fn get_entry(&self) -> Option<EntryAccessor<'a>> {
         match self {
-            Leaf { page, entry, .. } => LeafAccessor::new(page).entry(*entry),
+            Leaf { page, entry, .. } => {
+                let accessor = LeafAccessor::new(page);
+                let (key_start, key_end) = accessor.key_range(*entry)?;
+                let (value_start, value_end) = accessor.value_range(*entry)?;
+                let mem = page.clone().into_memory();
+                Some(EntryAccessor::new(
+                    &mem[key_start..key_end],
+                    &mem[value_start..value_end],
+                ))
+            }
             _ => None,
         }
     }
@@ -115,8 +134,8 @@ pub(crate) struct AllPageNumbersBtreeIter<'a> {
 }
 
 impl<'a> AllPageNumbersBtreeIter<'a> {
-    pub(crate) fn new(root: PageNumber, manager: &'a TransactionalMemory) -> Self {
-        let root_page = manager.get_page(root);
+    pub(crate) fn new(root: PageNumber, manager: &'a TransactionalMemory) -> Result<Self> {
+        let root_page = manager.get_page(root)?;
         let node_mem = root_page.memory();
         let start = match node_mem[0] {
             LEAF => Leaf {
@@ -131,15 +150,15 @@ impl<'a> AllPageNumbersBtreeIter<'a> {
             },
             _ => unreachable!(),
         };
-        Self {
+        Ok(Self {
             next: Some(start),
             manager,
-        }
+        })
     }
 }
 
 impl<'a> Iterator for AllPageNumbersBtreeIter<'a> {
-    type Item = PageNumber;
+    type Item = Result<PageNumber>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -150,20 +169,66 @@ impl<'a> Iterator for AllPageNumbersBtreeIter<'a> {
                 Leaf { entry, .. } => entry == 0,
                 Internal { child, .. } => child == 0,
             };
-            self.next = state.next(false, self.manager);
+            match state.next(false, self.manager) {
+                Ok(next) => {
+                    self.next = next;
+                }
+                Err(err) => {
+                    return Some(Err(err));
+                }
+            }
             if once {
-                return Some(value);
+                return Some(Ok(value));
             }
         }
     }
 }
 
+// Key type used to find the end of a prefix scan: the query compares greater than every key which
+// begins with it, so it is positioned just after the last of them
+struct PrefixEnd<K: RedbKey + ?Sized> {
+    _key_type: PhantomData<K>,
+}
+
+impl<K: RedbKey + ?Sized> RedbValue for PrefixEnd<K> {
+    type View = RefLifetime<[u8]>;
+    type ToBytes = RefAsBytesLifetime<[u8]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        data
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        unreachable!()
+    }
+
+    fn redb_type_name() -> &'static str {
+        unreachable!()
+    }
+}
+
+impl<K: RedbKey + ?Sized> RedbKey for PrefixEnd<K> {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::try_compare(data1, data2).unwrap()
+    }
+
+    // data1 is always the prefix being queried
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        if data2.starts_with(data1) {
+            Ok(Ordering::Greater)
+        } else {
+            K::try_compare(data1, data2)
+        }
+    }
+}
+
+// Iterates over a range from both ends. The two ends meet in the middle, after which the iterator
+// is exhausted
 pub struct BtreeRangeIter<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> {
     left: Option<RangeIterState<'a>>, // Exclusive. The previous element returned
     right: Option<RangeIterState<'a>>, // Exclusive. The previous element returned
     include_left: bool,               // left is inclusive, instead of exclusive
     include_right: bool,              // right is inclusive, instead of exclusive
-    reversed: bool,
     manager: &'a TransactionalMemory,
     _key_type: PhantomData<K>,
     _value_type: PhantomData<V>,
@@ -174,87 +239,118 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> BtreeRangeIter<'a
         query_range: T,
         table_root: Option<PageNumber>,
         manager: &'a TransactionalMemory,
-    ) -> Self {
-        if let Some(root) = table_root {
-            let (include_left, left) = match query_range.start_bound() {
-                Bound::Included(k) => find_iter_left::<K>(
-                    manager.get_page(root),
-                    None,
-                    k.borrow().as_bytes().as_ref(),
-                    true,
-                    manager,
-                ),
-                Bound::Excluded(k) => find_iter_left::<K>(
-                    manager.get_page(root),
-                    None,
-                    k.borrow().as_bytes().as_ref(),
-                    false,
-                    manager,
-                ),
-                Bound::Unbounded => {
-                    let state = find_iter_unbounded(manager.get_page(root), None, false, manager);
-                    (true, state)
-                }
-            };
-            let (include_right, right) = match query_range.end_bound() {
-                Bound::Included(k) => find_iter_right::<K>(
-                    manager.get_page(root),
-                    None,
-                    k.borrow().as_bytes().as_ref(),
-                    true,
-                    manager,
-                ),
-                Bound::Excluded(k) => find_iter_right::<K>(
-                    manager.get_page(root),
-                    None,
-                    k.borrow().as_bytes().as_ref(),
-                    false,
-                    manager,
-                ),
-                Bound::Unbounded => {
-                    let state = find_iter_unbounded(manager.get_page(root), None, true, manager);
-                    (true, state)
-                }
-            };
-            Self {
-                left,
-                right,
-                include_left,
-                include_right,
-                reversed: false,
+    ) -> Result<Self> {
+        let root = if let Some(root) = table_root {
+            root
+        } else {
+            return Ok(Self::empty(manager));
+        };
+        let (include_left, left) = match query_range.start_bound() {
+            Bound::Included(k) => find_iter_left::<K>(
+                manager.get_page(root)?,
+                None,
+                k.borrow().as_bytes().as_ref(),
+                true,
+                manager,
+            )?,
+            Bound::Excluded(k) => find_iter_left::<K>(
+                manager.get_page(root)?,
+                None,
+                k.borrow().as_bytes().as_ref(),
+                false,
                 manager,
-                _key_type: Default::default(),
-                _value_type: Default::default(),
+            )?,
+            Bound::Unbounded => {
+                let state = find_iter_unbounded(manager.get_page(root)?, None, false, manager)?;
+                (true, state)
             }
-        } else {
-            Self {
-                left: None,
-                right: None,
-                include_left: false,
-                include_right: false,
-                reversed: false,
+        };
+        let (include_right, right) = match query_range.end_bound() {
+            Bound::Included(k) => find_iter_right::<K>(
+                manager.get_page(root)?,
+                None,
+                k.borrow().as_bytes().as_ref(),
+                true,
                 manager,
-                _key_type: Default::default(),
-                _value_type: Default::default(),
+            )?,
+            Bound::Excluded(k) => find_iter_right::<K>(
+                manager.get_page(root)?,
+                None,
+                k.borrow().as_bytes().as_ref(),
+                false,
+                manager,
+            )?,
+            Bound::Unbounded => {
+                let state = find_iter_unbounded(manager.get_page(root)?, None, true, manager)?;
+                (true, state)
+            }
+        };
+        // If the range starts after it ends, the left end may be positioned on a later page than the
+        // right end, and the two would never meet
+        let bounded = !matches!(query_range.start_bound(), Bound::Unbounded)
+            && !matches!(query_range.end_bound(), Bound::Unbounded);
+        if let (true, Some(left_entry), Some(right_entry)) = (
+            bounded,
+            left.as_ref().and_then(|x| x.get_entry()),
+            right.as_ref().and_then(|x| x.get_entry()),
+        ) {
+            if K::try_compare(left_entry.key(), right_entry.key())? == Ordering::Greater {
+                return Ok(Self::empty(manager));
             }
         }
+        Ok(Self {
+            left,
+            right,
+            include_left,
+            include_right,
+            manager,
+            _key_type: Default::default(),
+            _value_type: Default::default(),
+        })
+    }
+
+    // Iterates over the keys which begin with `prefix`. Only meaningful for key types that are
+    // ordered lexicographically by their serialized bytes, so that these keys are contiguous
+    pub(crate) fn prefix(
+        prefix: &[u8],
+        table_root: Option<PageNumber>,
+        manager: &'a TransactionalMemory,
+    ) -> Result<Self> {
+        let root = if let Some(root) = table_root {
+            root
+        } else {
+            return Ok(Self::empty(manager));
+        };
+        let (include_left, left) =
+            find_iter_left::<K>(manager.get_page(root)?, None, prefix, true, manager)?;
+        let (include_right, right) =
+            find_iter_right::<PrefixEnd<K>>(manager.get_page(root)?, None, prefix, false, manager)?;
+        Ok(Self {
+            left,
+            right,
+            include_left,
+            include_right,
+            manager,
+            _key_type: Default::default(),
+            _value_type: Default::default(),
+        })
     }
 
-    pub(crate) fn reverse(self) -> Self {
+    fn empty(manager: &'a TransactionalMemory) -> Self {
         Self {
-            left: self.left,
-            right: self.right,
-            include_left: self.include_left,
-            include_right: self.include_right,
-            reversed: !self.reversed,
-            manager: self.manager,
+            left: None,
+            right: None,
+            include_left: false,
+            include_right: false,
+            manager,
             _key_type: Default::default(),
             _value_type: Default::default(),
         }
     }
 
-    // TODO: we need generic-associated-types to implement Iterator
-    pub fn next(&mut self) -> Option<EntryAccessor> {
+    // Returns true if the two ends have met, in which case both are cleared so that the iterator
+    // stays exhausted
+    fn exhausted(&mut self) -> bool {
         if let (
             Some(Leaf {
                 page: left_page,
@@ -272,74 +368,65 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> BtreeRangeIter<'a
                 && (left_entry > right_entry
                     || (left_entry == right_entry && (!self.include_left || !self.include_right)))
             {
-                return None;
+                self.left = None;
+                self.right = None;
+                return true;
             }
         }
+        false
+    }
+}
 
-        loop {
-            if !self.reversed {
-                if !self.include_left {
-                    self.left = self.left.take()?.next(self.reversed, self.manager);
-                }
-                // Return None if the next state is None
-                self.left.as_ref()?;
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> Iterator
+    for BtreeRangeIter<'a, K, V>
+{
+    type Item = Result<EntryAccessor<'a>>;
 
-                if let (
-                    Some(Leaf {
-                        page: left_page,
-                        entry: left_entry,
-                        parent: _,
-                    }),
-                    Some(Leaf {
-                        page: right_page,
-                        entry: right_entry,
-                        parent: _,
-                    }),
-                ) = (&self.left, &self.right)
-                {
-                    if left_page.get_page_number() == right_page.get_page_number()
-                        && (left_entry > right_entry
-                            || (left_entry == right_entry && !self.include_right))
-                    {
-                        return None;
-                    }
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.exhausted() {
+                return None;
+            }
+            if self.include_left {
+                if let Some(entry) = self.left.as_ref()?.get_entry() {
+                    self.include_left = false;
+                    return Some(Ok(entry));
                 }
-
-                self.include_left = false;
-                if self.left.as_ref().unwrap().get_entry().is_some() {
-                    return self.left.as_ref().map(|s| s.get_entry().unwrap());
+            }
+            match self.left.take()?.next(false, self.manager) {
+                Ok(left) => {
+                    self.left = left;
+                    self.include_left = true;
                 }
-            } else {
-                if !self.include_right {
-                    self.right = self.right.take()?.next(self.reversed, self.manager);
+                Err(err) => {
+                    return Some(Err(err));
                 }
-                // Return None if the next state is None
-                self.right.as_ref()?;
+            }
+        }
+    }
+}
 
-                if let (
-                    Some(Leaf {
-                        page: left_page,
-                        entry: left_entry,
-                        parent: _,
-                    }),
-                    Some(Leaf {
-                        page: right_page,
-                        entry: right_entry,
-                        parent: _,
-                    }),
-                ) = (&self.left, &self.right)
-                {
-                    if left_page.get_page_number() == right_page.get_page_number()
-                        && (left_entry > right_entry
-                            || (left_entry == right_entry && !self.include_left))
-                    {
-                        return None;
-                    }
+impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> DoubleEndedIterator
+    for BtreeRangeIter<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.exhausted() {
+                return None;
+            }
+            if self.include_right {
+                if let Some(entry) = self.right.as_ref()?.get_entry() {
+                    self.include_right = false;
+                    return Some(Ok(entry));
                 }
-
-                self.include_right = false;
-                if self.right.as_ref().unwrap().get_entry().is_some() {
-                    return self.right.as_ref().map(|s| s.get_entry().unwrap());
+            }
+            match self.right.take()?.next(true, self.manager) {
+                Ok(right) => {
+                    self.right = right;
+                    self.include_right = true;
+                }
+                Err(err) => {
+                    return Some(Err(err));
                 }
             }
         }
@@ -351,17 +438,17 @@ fn find_iter_unbounded<'a>(
     mut parent: Option<Box<RangeIterState<'a>>>,
     reverse: bool,
     manager: &'a TransactionalMemory,
-) -> Option<RangeIterState<'a>> {
+) -> Result<Option<RangeIterState<'a>>> {
     let node_mem = page.memory();
     match node_mem[0] {
         LEAF => {
             let accessor = LeafAccessor::new(&page);
             let entry = if reverse { accessor.num_pairs() - 1 } else { 0 };
-            Some(Leaf {
+            Ok(Some(Leaf {
                 page,
                 entry,
                 parent,
-            })
+            }))
         }
         BRANCH => {
             let accessor = BranchAccessor::new(&page);
@@ -371,7 +458,7 @@ fn find_iter_unbounded<'a>(
                 0
             };
             let child_page_number = accessor.child_page(child_index).unwrap();
-            let child_page = manager.get_page(child_page_number);
+            let child_page = manager.get_page(child_page_number)?;
             let direction = if reverse { -1isize } else { 1 };
             parent = Some(Box::new(Internal {
                 page,
@@ -392,12 +479,12 @@ fn find_iter_left<'a, K: RedbKey + ?Sized>(
     query: &[u8],
     include_query: bool,
     manager: &'a TransactionalMemory,
-) -> (bool, Option<RangeIterState<'a>>) {
+) -> Result<(bool, Option<RangeIterState<'a>>)> {
     let node_mem = page.memory();
     match node_mem[0] {
         LEAF => {
             let accessor = LeafAccessor::new(&page);
-            let (mut position, found) = accessor.position::<K>(query);
+            let (mut position, found) = accessor.position::<K>(query)?;
             let include = if position < accessor.num_pairs() {
                 include_query || !found
             } else {
@@ -411,12 +498,12 @@ fn find_iter_left<'a, K: RedbKey + ?Sized>(
                 entry: position,
                 parent,
             };
-            (include, Some(result))
+            Ok((include, Some(result)))
         }
         BRANCH => {
             let accessor = BranchAccessor::new(&page);
-            let (child_index, child_page_number) = accessor.child_for_key::<K>(query);
-            let child_page = manager.get_page(child_page_number);
+            let (child_index, child_page_number) = accessor.child_for_key::<K>(query)?;
+            let child_page = manager.get_page(child_page_number)?;
             if child_index < accessor.count_children() - 1 {
                 parent = Some(Box::new(Internal {
                     page,
@@ -436,12 +523,12 @@ fn find_iter_right<'a, K: RedbKey + ?Sized>(
     query: &[u8],
     include_query: bool,
     manager: &'a TransactionalMemory,
-) -> (bool, Option<RangeIterState<'a>>) {
+) -> Result<(bool, Option<RangeIterState<'a>>)> {
     let node_mem = page.memory();
     match node_mem[0] {
         LEAF => {
             let accessor = LeafAccessor::new(&page);
-            let (mut position, found) = accessor.position::<K>(query);
+            let (mut position, found) = accessor.position::<K>(query)?;
             let include = if position < accessor.num_pairs() {
                 include_query && found
             } else {
@@ -455,12 +542,12 @@ fn find_iter_right<'a, K: RedbKey + ?Sized>(
                 entry: position,
                 parent,
             };
-            (include, Some(result))
+            Ok((include, Some(result)))
         }
         BRANCH => {
             let accessor = BranchAccessor::new(&page);
-            let (child_index, child_page_number) = accessor.child_for_key::<K>(query);
-            let child_page = manager.get_page(child_page_number);
+            let (child_index, child_page_number) = accessor.child_for_key::<K>(query)?;
+            let child_page = manager.get_page(child_page_number)?;
             if child_index > 0 && accessor.child_page(child_index - 1).is_some() {
                 parent = Some(Box::new(Internal {
                     page,
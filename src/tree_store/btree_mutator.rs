@@ -1,6 +1,6 @@
 use crate::tree_store::btree_base::{
-    BranchAccessor, BranchBuilder, BranchMutator, FreePolicy, LeafAccessor, LeafBuilder,
-    LeafMutator, BRANCH, LEAF,
+    subtree_entries, BranchAccessor, BranchBuilder, BranchMutator, FreePolicy, LeafAccessor,
+    LeafBuilder, LeafMutator, RawBranchBuilder, BRANCH, LEAF,
 };
 use crate::tree_store::btree_mutator::DeletionResult::{
     DeletedBranch, DeletedLeaf, PartialBranch, PartialLeaf, Subtree,
@@ -8,8 +8,10 @@ use crate::tree_store::btree_mutator::DeletionResult::{
 use crate::tree_store::page_store::{Page, PageImpl};
 use crate::tree_store::{AccessGuardMut, PageNumber, TransactionalMemory};
 use crate::types::{RedbKey, RedbValue};
-use crate::{AccessGuard, Result};
-use std::cmp::{max, min};
+use crate::{AccessGuard, Error, Result};
+use std::borrow::Borrow;
+use std::cmp::{max, min, Ordering};
+use std::collections::Bound;
 use std::marker::PhantomData;
 
 #[derive(Debug)]
@@ -22,10 +24,27 @@ enum DeletionResult {
     PartialLeaf { deleted_pair: usize },
     // A branch page subtree with fewer children than desired
     PartialBranch(PageNumber),
-    // Indicates that the branch node was deleted, and includes the only remaining child
-    DeletedBranch(PageNumber),
+    // Indicates that the branch node was deleted, and includes the only remaining child and the
+    // number of entries below it
+    DeletedBranch(PageNumber, u64),
 }
 
+struct InsertionResult<'a, V: RedbValue + ?Sized> {
+    // the new root page
+    new_root: PageNumber,
+    // Number of entries below the new root page
+    new_root_entries: u64,
+    // Following sibling, and the number of entries below it, if the root had to be split
+    additional_sibling: Option<(Vec<u8>, PageNumber, u64)>,
+    // The inserted value for .insert_reserve() to use
+    inserted_value: AccessGuardMut<'a>,
+    // The previous value, if any
+    old_value: Option<AccessGuard<'a, V>>,
+}
+
+// A page, and the separator key and page which follow it, if the page had to be split in two
+type MaybeSplit = (PageNumber, Option<(Vec<u8>, PageNumber)>);
+
 pub(crate) struct MutateHelper<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     root: &'b mut Option<PageNumber>,
     free_policy: FreePolicy,
@@ -60,21 +79,30 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
 
     // Safety: caller must ensure that no references to uncommitted pages in this table exist
     pub(crate) unsafe fn delete(&mut self, key: &K) -> Result<Option<AccessGuard<'a, V>>> {
+        self.delete_serialized(key.as_bytes().as_ref())
+    }
+
+    // Like delete(), but takes the key in its serialized form
+    // Safety: caller must ensure that no references to uncommitted pages in this table exist
+    pub(crate) unsafe fn delete_serialized(
+        &mut self,
+        key: &[u8],
+    ) -> Result<Option<AccessGuard<'a, V>>> {
         if let Some(p) = *self.root {
-            let (deletion_result, found) =
-                self.delete_helper(self.mem.get_page(p), key.as_bytes().as_ref())?;
+            let (deletion_result, found) = self.delete_helper(self.mem.get_page(p)?, key)?;
             let new_root = match deletion_result {
                 DeletionResult::Subtree(page) => Some(page),
                 DeletionResult::DeletedLeaf => None,
                 DeletionResult::PartialLeaf { deleted_pair } => {
-                    let page = self.mem.get_page(p);
+                    let page = self.mem.get_page(p)?;
                     let accessor = LeafAccessor::new(&page);
-                    let mut builder = LeafBuilder::new(self.mem, accessor.num_pairs() - 1);
+                    let mut builder =
+                        LeafBuilder::new(self.mem, accessor.num_pairs() - 1, V::alignment());
                     builder.push_all_except(&accessor, Some(deleted_pair));
                     Some(builder.build()?.get_page_number())
                 }
                 DeletionResult::PartialBranch(page_number) => Some(page_number),
-                DeletionResult::DeletedBranch(remaining_child) => Some(remaining_child),
+                DeletionResult::DeletedBranch(remaining_child, _) => Some(remaining_child),
             };
             *self.root = new_root;
             Ok(found)
@@ -83,31 +111,36 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
         }
     }
 
+    // Returns the inserted value, and the value which it replaced, if any
     // Safety: caller must ensure that no references to uncommitted pages in this tree exist
-    pub(crate) unsafe fn insert(&mut self, key: &K, value: &V) -> Result<AccessGuardMut<'a>> {
-        let (new_root, guard) = if let Some(p) = *self.root {
-            let (page1, more, guard) = self.insert_helper(
-                self.mem.get_page(p),
+    pub(crate) unsafe fn insert(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(AccessGuardMut<'a>, Option<AccessGuard<'a, V>>)> {
+        let (new_root, guard, old_value) = if let Some(p) = *self.root {
+            let result = self.insert_helper(
+                self.mem.get_page(p)?,
                 key.as_bytes().as_ref(),
                 value.as_bytes().as_ref(),
             )?;
 
-            let new_root = if let Some((key, page2)) = more {
+            let new_root = if let Some((key, page2, page2_entries)) = result.additional_sibling {
                 let mut builder = BranchBuilder::new(self.mem, 2);
-                builder.push_child(page1);
+                builder.push_child(result.new_root, result.new_root_entries);
                 builder.push_key(&key);
-                builder.push_child(page2);
+                builder.push_child(page2, page2_entries);
                 builder.build()?.get_page_number()
             } else {
-                page1
+                result.new_root
             };
-            (new_root, guard)
+            (new_root, result.inserted_value, result.old_value)
         } else {
             let key_bytes = key.as_bytes();
             let value_bytes = value.as_bytes();
             let key_bytes = key_bytes.as_ref();
             let value_bytes = value_bytes.as_ref();
-            let mut builder = LeafBuilder::new(self.mem, 1);
+            let mut builder = LeafBuilder::new(self.mem, 1, V::alignment());
             builder.push(key_bytes, value_bytes);
             let page = builder.build()?;
 
@@ -116,35 +149,132 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
             let page_num = page.get_page_number();
             let guard = AccessGuardMut::new(page, offset, value_bytes.len());
 
-            (page_num, guard)
+            (page_num, guard, None)
         };
         *self.root = Some(new_root);
-        Ok(guard)
+        Ok((guard, old_value))
+    }
+
+    // Copies any committed pages on the path to the leaf which contains, or would contain, key,
+    // so that the leaf can be modified in place.
+    // Returns the leaf page, the position of key within it, and whether key was found
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    pub(crate) unsafe fn prepare_leaf(
+        &mut self,
+        key: &K,
+    ) -> Result<Option<(PageNumber, usize, bool)>> {
+        if let Some(p) = *self.root {
+            let (new_root, leaf, position, found) =
+                self.prepare_leaf_helper(self.mem.get_page(p)?, key.as_bytes().as_ref())?;
+            *self.root = Some(new_root);
+            Ok(Some((leaf, position, found)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    unsafe fn prepare_leaf_helper(
+        &mut self,
+        page: PageImpl<'a>,
+        key: &[u8],
+    ) -> Result<(PageNumber, PageNumber, usize, bool)> {
+        let page_number = page.get_page_number();
+        let (child, leaf, position, found) = match page.memory()[0] {
+            LEAF => {
+                let accessor = LeafAccessor::new(&page);
+                let (position, found) = accessor.position::<K>(key)?;
+                drop(accessor);
+                if self.mem.uncommitted(page_number) {
+                    return Ok((page_number, page_number, position, found));
+                }
+                (None, None, position, found)
+            }
+            BRANCH => {
+                let accessor = BranchAccessor::new(&page);
+                let (child_index, child_page) = accessor.child_for_key::<K>(key)?;
+                drop(accessor);
+                let (new_child, leaf, position, found) =
+                    self.prepare_leaf_helper(self.mem.get_page(child_page)?, key)?;
+                if new_child == child_page {
+                    // NO-OP. The path below this page is already private to this transaction
+                    return Ok((page_number, leaf, position, found));
+                }
+                if self.mem.uncommitted(page_number) {
+                    drop(page);
+                    // Safety: Since the page is uncommitted, no other transactions could have it open
+                    // and we just dropped our reference to it, on the line above
+                    let mut page_mut = self.mem.get_page_mut(page_number);
+                    let mut mutator = BranchMutator::new(&mut page_mut);
+                    mutator.write_child_page(child_index, new_child);
+                    return Ok((page_number, leaf, position, found));
+                }
+                (Some((child_index, new_child)), Some(leaf), position, found)
+            }
+            _ => unreachable!(),
+        };
+
+        let mut new_page = self.mem.allocate(page.memory().len())?;
+        new_page.memory_mut().copy_from_slice(page.memory());
+        if let Some((child_index, new_child)) = child {
+            let mut mutator = BranchMutator::new(&mut new_page);
+            mutator.write_child_page(child_index, new_child);
+        }
+        let new_page_number = new_page.get_page_number();
+        drop(new_page);
+        drop(page);
+        self.free_policy
+            .conditional_free(page_number, self.freed, self.mem)?;
+
+        Ok((
+            new_page_number,
+            leaf.unwrap_or(new_page_number),
+            position,
+            found,
+        ))
     }
 
-    #[allow(clippy::type_complexity)]
     // Safety: caller must ensure that no references to uncommitted pages in this table exist
     unsafe fn insert_helper(
         &mut self,
         page: PageImpl<'a>,
         key: &[u8],
         value: &[u8],
-    ) -> Result<(
-        PageNumber,
-        Option<(Vec<u8>, PageNumber)>,
-        AccessGuardMut<'a>,
-    )> {
+    ) -> Result<InsertionResult<'a, V>> {
         let node_mem = page.memory();
         Ok(match node_mem[0] {
             LEAF => {
                 let accessor = LeafAccessor::new(&page);
-                let (position, found) = accessor.position::<K>(key);
+                let (position, found) = accessor.position::<K>(key)?;
+
+                let old_value = if found {
+                    let (start, end) = accessor.value_range(position).unwrap();
+                    if self.mem.uncommitted(page.get_page_number()) {
+                        // The page is about to be modified in place, or freed, so copy the value
+                        Some(AccessGuard::with_owned_value(
+                            &page.memory()[start..end],
+                            self.mem,
+                        ))
+                    } else {
+                        // Committed pages are left intact until the transaction commits
+                        let old_page = self.mem.get_page(page.get_page_number())?;
+                        Some(AccessGuard::new(
+                            old_page,
+                            start,
+                            end - start,
+                            false,
+                            self.mem,
+                        ))
+                    }
+                } else {
+                    None
+                };
 
                 // Fast-path to avoid re-building and splitting pages with a single large value
                 let single_large_value = accessor.num_pairs() == 1
-                    && accessor.total_length() >= self.mem.get_page_size();
+                    && accessor.total_length() >= self.mem.get_usable_page_size();
                 if !found && single_large_value {
-                    let mut builder = LeafBuilder::new(self.mem, 1);
+                    let mut builder = LeafBuilder::new(self.mem, 1, V::alignment());
                     builder.push(key, value);
                     let new_page = builder.build()?;
                     let new_page_number = new_page.get_page_number();
@@ -153,18 +283,22 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                     drop(new_page_accessor);
                     let guard = AccessGuardMut::new(new_page, offset, value.len());
                     return if position == 0 {
-                        Ok((
-                            new_page_number,
-                            Some((key.to_vec(), page.get_page_number())),
-                            guard,
-                        ))
+                        Ok(InsertionResult {
+                            new_root: new_page_number,
+                            new_root_entries: 1,
+                            additional_sibling: Some((key.to_vec(), page.get_page_number(), 1)),
+                            inserted_value: guard,
+                            old_value,
+                        })
                     } else {
                         let split_key = accessor.last_entry().key().to_vec();
-                        Ok((
-                            page.get_page_number(),
-                            Some((split_key, new_page_number)),
-                            guard,
-                        ))
+                        Ok(InsertionResult {
+                            new_root: page.get_page_number(),
+                            new_root_entries: 1,
+                            additional_sibling: Some((split_key, new_page_number, 1)),
+                            inserted_value: guard,
+                            old_value,
+                        })
                     };
                 }
 
@@ -181,12 +315,20 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                     mutator.insert(position, found, key, value);
                     let new_page_accessor = LeafAccessor::new(&page_mut);
                     let offset = new_page_accessor.offset_of_value(position).unwrap();
+                    let entries = new_page_accessor.num_pairs() as u64;
                     drop(new_page_accessor);
                     let guard = AccessGuardMut::new(page_mut, offset, value.len());
-                    return Ok((page_number, None, guard));
+                    return Ok(InsertionResult {
+                        new_root: page_number,
+                        new_root_entries: entries,
+                        additional_sibling: None,
+                        inserted_value: guard,
+                        old_value,
+                    });
                 }
 
-                let mut builder = LeafBuilder::new(self.mem, accessor.num_pairs() + 1);
+                let mut builder =
+                    LeafBuilder::new(self.mem, accessor.num_pairs() + 1, V::alignment());
                 for i in 0..accessor.num_pairs() {
                     if i == position {
                         builder.push(key, value);
@@ -210,9 +352,16 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                     let new_page_number = new_page.get_page_number();
                     let accessor = LeafAccessor::new(&new_page);
                     let offset = accessor.offset_of_value(position).unwrap();
+                    let entries = accessor.num_pairs() as u64;
                     let guard = AccessGuardMut::new(new_page, offset, value.len());
 
-                    (new_page_number, None, guard)
+                    InsertionResult {
+                        new_root: new_page_number,
+                        new_root_entries: entries,
+                        additional_sibling: None,
+                        inserted_value: guard,
+                        old_value,
+                    }
                 } else {
                     let (new_page1, split_key, new_page2) = builder.build_split()?;
                     let page_number = page.get_page_number();
@@ -225,6 +374,7 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                     let new_page_number2 = new_page2.get_page_number();
                     let accessor = LeafAccessor::new(&new_page1);
                     let division = accessor.num_pairs();
+                    let entries2 = LeafAccessor::new(&new_page2).num_pairs() as u64;
                     let guard = if position < division {
                         let accessor = LeafAccessor::new(&new_page1);
                         let offset = accessor.offset_of_value(position).unwrap();
@@ -235,20 +385,39 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                         AccessGuardMut::new(new_page2, offset, value.len())
                     };
 
-                    (new_page_number, Some((split_key, new_page_number2)), guard)
+                    InsertionResult {
+                        new_root: new_page_number,
+                        new_root_entries: division as u64,
+                        additional_sibling: Some((split_key, new_page_number2, entries2)),
+                        inserted_value: guard,
+                        old_value,
+                    }
                 }
             }
             BRANCH => {
                 let accessor = BranchAccessor::new(&page);
-                let (child_index, child_page) = accessor.child_for_key::<K>(key);
-                let (page1, more, guard) =
-                    self.insert_helper(self.mem.get_page(child_page), key, value)?;
+                let (child_index, child_page) = accessor.child_for_key::<K>(key)?;
+                let result = self.insert_helper(self.mem.get_page(child_page)?, key, value)?;
+                let page1 = result.new_root;
+                let page1_entries = result.new_root_entries;
+                let more = result.additional_sibling;
+                let old_entries = accessor.total_entries();
+                let new_entries = old_entries - accessor.child_entries(child_index).unwrap()
+                    + page1_entries
+                    + more.as_ref().map_or(0, |(_, _, entries)| *entries);
 
                 if more.is_none() {
                     // Check fast-path if no children were added
-                    if page1 == child_page {
-                        // NO-OP. One of our descendants is uncommitted, so there was no change
-                        return Ok((page.get_page_number(), None, guard));
+                    if page1 == child_page && new_entries == old_entries {
+                        // NO-OP. One of our descendants is uncommitted, and an existing value was
+                        // replaced, so there was no change
+                        return Ok(InsertionResult {
+                            new_root: page.get_page_number(),
+                            new_root_entries: old_entries,
+                            additional_sibling: None,
+                            inserted_value: result.inserted_value,
+                            old_value: result.old_value,
+                        });
                     } else if self.mem.uncommitted(page.get_page_number()) {
                         let page_number = page.get_page_number();
                         drop(page);
@@ -257,32 +426,45 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                         let mut mutpage = self.mem.get_page_mut(page_number);
                         let mut mutator = BranchMutator::new(&mut mutpage);
                         mutator.write_child_page(child_index, page1);
-                        return Ok((mutpage.get_page_number(), None, guard));
+                        mutator.write_child_entries(child_index, page1_entries);
+                        return Ok(InsertionResult {
+                            new_root: mutpage.get_page_number(),
+                            new_root_entries: new_entries,
+                            additional_sibling: None,
+                            inserted_value: result.inserted_value,
+                            old_value: result.old_value,
+                        });
                     }
                 }
 
                 // A child was added, or we couldn't use the fast-path above
                 let mut builder = BranchBuilder::new(self.mem, accessor.count_children() + 1);
                 if child_index == 0 {
-                    builder.push_child(page1);
-                    if let Some((ref index_key2, page2)) = more {
+                    builder.push_child(page1, page1_entries);
+                    if let Some((ref index_key2, page2, page2_entries)) = more {
                         builder.push_key(index_key2);
-                        builder.push_child(page2);
+                        builder.push_child(page2, page2_entries);
                     }
                 } else {
-                    builder.push_child(accessor.child_page(0).unwrap());
+                    builder.push_child(
+                        accessor.child_page(0).unwrap(),
+                        accessor.child_entries(0).unwrap(),
+                    );
                 }
                 for i in 1..accessor.count_children() {
                     if let Some(key) = accessor.key(i - 1) {
                         builder.push_key(key);
                         if i == child_index {
-                            builder.push_child(page1);
-                            if let Some((ref index_key2, page2)) = more {
+                            builder.push_child(page1, page1_entries);
+                            if let Some((ref index_key2, page2, page2_entries)) = more {
                                 builder.push_key(index_key2);
-                                builder.push_child(page2);
+                                builder.push_child(page2, page2_entries);
                             }
                         } else {
-                            builder.push_child(accessor.child_page(i).unwrap());
+                            builder.push_child(
+                                accessor.child_page(i).unwrap(),
+                                accessor.child_entries(i).unwrap(),
+                            );
                         }
                     } else {
                         unreachable!();
@@ -291,14 +473,27 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
 
                 let result = if builder.should_split() {
                     let (new_page1, split_key, new_page2) = builder.build_split()?;
-                    (
-                        new_page1.get_page_number(),
-                        Some((split_key.to_vec(), new_page2.get_page_number())),
-                        guard,
-                    )
+                    let entries1 = subtree_entries(&new_page1);
+                    InsertionResult {
+                        new_root: new_page1.get_page_number(),
+                        new_root_entries: entries1,
+                        additional_sibling: Some((
+                            split_key.to_vec(),
+                            new_page2.get_page_number(),
+                            new_entries - entries1,
+                        )),
+                        inserted_value: result.inserted_value,
+                        old_value: result.old_value,
+                    }
                 } else {
                     let new_page = builder.build()?;
-                    (new_page.get_page_number(), None, guard)
+                    InsertionResult {
+                        new_root: new_page.get_page_number(),
+                        new_root_entries: new_entries,
+                        additional_sibling: None,
+                        inserted_value: result.inserted_value,
+                        old_value: result.old_value,
+                    }
                 };
                 // Free the original page, since we've replaced it
                 let page_number = page.get_page_number();
@@ -321,19 +516,19 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
         key: &[u8],
     ) -> Result<(DeletionResult, Option<AccessGuard<'a, V>>)> {
         let accessor = LeafAccessor::new(&page);
-        let (position, found) = accessor.position::<K>(key);
+        let (position, found) = accessor.position::<K>(key)?;
         if !found {
             return Ok((Subtree(page.get_page_number()), None));
         }
         let new_kv_bytes = accessor.length_of_pairs(0, accessor.num_pairs())
             - accessor.length_of_pairs(position, position + 1);
         let new_required_bytes =
-            LeafBuilder::required_bytes(accessor.num_pairs() - 1, new_kv_bytes);
+            LeafBuilder::required_bytes(accessor.num_pairs() - 1, new_kv_bytes, V::alignment());
         let uncommitted = self.mem.uncommitted(page.get_page_number());
 
         // Fast-path for dirty pages
         if uncommitted
-            && new_required_bytes >= self.mem.get_page_size() / 2
+            && new_required_bytes >= self.mem.get_usable_page_size() / 2
             && accessor.num_pairs() > 1
         {
             let (start, end) = accessor.value_range(position).unwrap();
@@ -349,14 +544,14 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
 
         let result = if accessor.num_pairs() == 1 {
             DeletedLeaf
-        } else if new_required_bytes < self.mem.get_page_size() / 3 {
+        } else if new_required_bytes < self.mem.get_usable_page_size() / 3 {
             // Merge when less than 33% full. Splits occur when a page is full and produce two 50%
             // full pages, so we use 33% instead of 50% to avoid oscillating
             PartialLeaf {
                 deleted_pair: position,
             }
         } else {
-            let mut builder = LeafBuilder::new(self.mem, accessor.num_pairs() - 1);
+            let mut builder = LeafBuilder::new(self.mem, accessor.num_pairs() - 1, V::alignment());
             for i in 0..accessor.num_pairs() {
                 if i == position {
                     continue;
@@ -386,21 +581,23 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
     }
 
     fn finalize_branch_builder(&self, builder: BranchBuilder<'_, '_>) -> Result<DeletionResult> {
-        Ok(if let Some(only_child) = builder.to_single_child() {
-            DeletedBranch(only_child)
-        } else {
-            // TODO: can we optimize away this page allocation?
-            // The PartialInternal gets returned, and then the caller has to merge it immediately
-            let new_page = builder.build()?;
-            let accessor = BranchAccessor::new(&new_page);
-            // Merge when less than 33% full. Splits occur when a page is full and produce two 50%
-            // full pages, so we use 33% instead of 50% to avoid oscillating
-            if accessor.total_length() < self.mem.get_page_size() / 3 {
-                PartialBranch(new_page.get_page_number())
+        Ok(
+            if let Some((only_child, entries)) = builder.to_single_child() {
+                DeletedBranch(only_child, entries)
             } else {
-                Subtree(new_page.get_page_number())
-            }
-        })
+                // TODO: can we optimize away this page allocation?
+                // The PartialInternal gets returned, and then the caller has to merge it immediately
+                let new_page = builder.build()?;
+                let accessor = BranchAccessor::new(&new_page);
+                // Merge when less than 33% full. Splits occur when a page is full and produce two 50%
+                // full pages, so we use 33% instead of 50% to avoid oscillating
+                if accessor.total_length() < self.mem.get_usable_page_size() / 3 {
+                    PartialBranch(new_page.get_page_number())
+                } else {
+                    Subtree(new_page.get_page_number())
+                }
+            },
+        )
     }
 
     // Safety: caller must ensure that no references to uncommitted pages in this table exist
@@ -411,27 +608,30 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
     ) -> Result<(DeletionResult, Option<AccessGuard<'a, V>>)> {
         let accessor = BranchAccessor::new(&page);
         let original_page_number = page.get_page_number();
-        let (child_index, child_page_number) = accessor.child_for_key::<K>(key);
-        let (result, found) = self.delete_helper(self.mem.get_page(child_page_number), key)?;
+        let (child_index, child_page_number) = accessor.child_for_key::<K>(key)?;
+        let (result, found) = self.delete_helper(self.mem.get_page(child_page_number)?, key)?;
         if found.is_none() {
             return Ok((Subtree(original_page_number), None));
         }
         if let Subtree(new_child) = result {
-            let result_page_number = if new_child == child_page_number {
-                // NO-OP. One of our descendants is uncommitted, so there was no change
-                original_page_number
-            } else if self.mem.uncommitted(original_page_number) {
+            let new_child_entries = accessor.child_entries(child_index).unwrap() - 1;
+            // If the child was modified in place, then it's uncommitted and so are we
+            debug_assert!(
+                new_child != child_page_number || self.mem.uncommitted(original_page_number)
+            );
+            let result_page_number = if self.mem.uncommitted(original_page_number) {
                 drop(page);
                 // Safety: Caller guarantees there are no references to uncommitted pages,
                 // and we just dropped our reference to it on the line above
                 let mut mutpage = self.mem.get_page_mut(original_page_number);
                 let mut mutator = BranchMutator::new(&mut mutpage);
                 mutator.write_child_page(child_index, new_child);
+                mutator.write_child_entries(child_index, new_child_entries);
                 original_page_number
             } else {
                 let mut builder = BranchBuilder::new(self.mem, accessor.count_children());
                 builder.push_all(&accessor);
-                builder.replace_child(child_index, new_child);
+                builder.replace_child(child_index, new_child, new_child_entries);
                 let new_page = builder.build()?;
                 self.free_policy
                     .conditional_free(original_page_number, self.freed, self.mem)?;
@@ -453,7 +653,10 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                     if i == child_index {
                         continue;
                     }
-                    builder.push_child(accessor.child_page(i).unwrap());
+                    builder.push_child(
+                        accessor.child_page(i).unwrap(),
+                        accessor.child_entries(i).unwrap(),
+                    );
                 }
                 let end = if child_index == accessor.count_children() - 1 {
                     // Skip the last key, which precedes the child
@@ -470,25 +673,34 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                 self.finalize_branch_builder(builder)?
             }
             PartialLeaf { deleted_pair } => {
-                let partial_child_page = self.mem.get_page(child_page_number);
+                let partial_child_page = self.mem.get_page(child_page_number)?;
                 let partial_child_accessor = LeafAccessor::new(&partial_child_page);
                 debug_assert!(partial_child_accessor.num_pairs() > 1);
 
                 let merge_with = if child_index == 0 { 1 } else { child_index - 1 };
                 debug_assert!(merge_with < accessor.count_children());
-                let merge_with_page = self.mem.get_page(accessor.child_page(merge_with).unwrap());
+                let merge_with_page = self
+                    .mem
+                    .get_page(accessor.child_page(merge_with).unwrap())?;
                 let merge_with_accessor = LeafAccessor::new(&merge_with_page);
 
                 let single_large_value = merge_with_accessor.num_pairs() == 1
-                    && merge_with_accessor.total_length() >= self.mem.get_page_size();
+                    && merge_with_accessor.total_length() >= self.mem.get_usable_page_size();
                 // Don't try to merge or rebalance, if the sibling contains a single large value
                 if single_large_value {
-                    let mut child_builder =
-                        LeafBuilder::new(self.mem, partial_child_accessor.num_pairs() - 1);
+                    let mut child_builder = LeafBuilder::new(
+                        self.mem,
+                        partial_child_accessor.num_pairs() - 1,
+                        V::alignment(),
+                    );
                     child_builder.push_all_except(&partial_child_accessor, Some(deleted_pair));
                     let new_page = child_builder.build()?;
                     builder.push_all(&accessor);
-                    builder.replace_child(child_index, new_page.get_page_number());
+                    builder.replace_child(
+                        child_index,
+                        new_page.get_page_number(),
+                        subtree_entries(&new_page),
+                    );
 
                     let result = self.finalize_branch_builder(builder)?;
 
@@ -514,6 +726,7 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                             self.mem,
                             partial_child_accessor.num_pairs() - 1
                                 + merge_with_accessor.num_pairs(),
+                            V::alignment(),
                         );
                         if child_index < merge_with {
                             child_builder
@@ -527,11 +740,18 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                         if child_builder.should_split() {
                             let (new_page1, split_key, new_page2) = child_builder.build_split()?;
                             builder.push_key(split_key);
-                            builder.push_child(new_page1.get_page_number());
-                            builder.push_child(new_page2.get_page_number());
+                            builder.push_child(
+                                new_page1.get_page_number(),
+                                subtree_entries(&new_page1),
+                            );
+                            builder.push_child(
+                                new_page2.get_page_number(),
+                                subtree_entries(&new_page2),
+                            );
                         } else {
                             let new_page = child_builder.build()?;
-                            builder.push_child(new_page.get_page_number());
+                            builder
+                                .push_child(new_page.get_page_number(), subtree_entries(&new_page));
                         }
 
                         let merged_key_index = max(child_index, merge_with);
@@ -539,7 +759,7 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                             builder.push_key(accessor.key(merged_key_index).unwrap());
                         }
                     } else {
-                        builder.push_child(page_number);
+                        builder.push_child(page_number, accessor.child_entries(i).unwrap());
                         if i < accessor.count_children() - 1 {
                             builder.push_key(accessor.key(i).unwrap());
                         }
@@ -557,9 +777,11 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
 
                 result
             }
-            DeletionResult::DeletedBranch(only_grandchild) => {
+            DeletionResult::DeletedBranch(only_grandchild, grandchild_entries) => {
                 let merge_with = if child_index == 0 { 1 } else { child_index - 1 };
-                let merge_with_page = self.mem.get_page(accessor.child_page(merge_with).unwrap());
+                let merge_with_page = self
+                    .mem
+                    .get_page(accessor.child_page(merge_with).unwrap())?;
                 let merge_with_accessor = BranchAccessor::new(&merge_with_page);
                 debug_assert!(merge_with < accessor.count_children());
                 for i in 0..accessor.count_children() {
@@ -572,22 +794,29 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                             BranchBuilder::new(self.mem, merge_with_accessor.count_children() + 1);
                         let separator_key = accessor.key(min(child_index, merge_with)).unwrap();
                         if child_index < merge_with {
-                            child_builder.push_child(only_grandchild);
+                            child_builder.push_child(only_grandchild, grandchild_entries);
                             child_builder.push_key(separator_key);
                         }
                         child_builder.push_all(&merge_with_accessor);
                         if child_index > merge_with {
                             child_builder.push_key(separator_key);
-                            child_builder.push_child(only_grandchild);
+                            child_builder.push_child(only_grandchild, grandchild_entries);
                         }
                         if child_builder.should_split() {
                             let (new_page1, separator, new_page2) = child_builder.build_split()?;
-                            builder.push_child(new_page1.get_page_number());
+                            builder.push_child(
+                                new_page1.get_page_number(),
+                                subtree_entries(&new_page1),
+                            );
                             builder.push_key(separator);
-                            builder.push_child(new_page2.get_page_number());
+                            builder.push_child(
+                                new_page2.get_page_number(),
+                                subtree_entries(&new_page2),
+                            );
                         } else {
                             let new_page = child_builder.build()?;
-                            builder.push_child(new_page.get_page_number());
+                            builder
+                                .push_child(new_page.get_page_number(), subtree_entries(&new_page));
                         }
 
                         let merged_key_index = max(child_index, merge_with);
@@ -595,7 +824,7 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                             builder.push_key(accessor.key(merged_key_index).unwrap());
                         }
                     } else {
-                        builder.push_child(page_number);
+                        builder.push_child(page_number, accessor.child_entries(i).unwrap());
                         if i < accessor.count_children() - 1 {
                             builder.push_key(accessor.key(i).unwrap());
                         }
@@ -611,10 +840,12 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                 result
             }
             PartialBranch(partial_child) => {
-                let partial_child_page = self.mem.get_page(partial_child);
+                let partial_child_page = self.mem.get_page(partial_child)?;
                 let partial_child_accessor = BranchAccessor::new(&partial_child_page);
                 let merge_with = if child_index == 0 { 1 } else { child_index - 1 };
-                let merge_with_page = self.mem.get_page(accessor.child_page(merge_with).unwrap());
+                let merge_with_page = self
+                    .mem
+                    .get_page(accessor.child_page(merge_with).unwrap())?;
                 let merge_with_accessor = BranchAccessor::new(&merge_with_page);
                 debug_assert!(merge_with < accessor.count_children());
                 for i in 0..accessor.count_children() {
@@ -640,12 +871,19 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                         }
                         if child_builder.should_split() {
                             let (new_page1, separator, new_page2) = child_builder.build_split()?;
-                            builder.push_child(new_page1.get_page_number());
+                            builder.push_child(
+                                new_page1.get_page_number(),
+                                subtree_entries(&new_page1),
+                            );
                             builder.push_key(separator);
-                            builder.push_child(new_page2.get_page_number());
+                            builder.push_child(
+                                new_page2.get_page_number(),
+                                subtree_entries(&new_page2),
+                            );
                         } else {
                             let new_page = child_builder.build()?;
-                            builder.push_child(new_page.get_page_number());
+                            builder
+                                .push_child(new_page.get_page_number(), subtree_entries(&new_page));
                         }
 
                         let merged_key_index = max(child_index, merge_with);
@@ -653,7 +891,7 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
                             builder.push_key(accessor.key(merged_key_index).unwrap());
                         }
                     } else {
-                        builder.push_child(page_number);
+                        builder.push_child(page_number, accessor.child_entries(i).unwrap());
                         if i < accessor.count_children() - 1 {
                             builder.push_key(accessor.key(i).unwrap());
                         }
@@ -695,4 +933,542 @@ impl<'a, 'b, K: RedbKey + ?Sized, V: RedbValue + ?Sized> MutateHelper<'a, 'b, K,
             _ => unreachable!(),
         }
     }
+
+    // Removes all keys in the given range. Subtrees which are entirely within the range are freed
+    // without being rebalanced, and the remaining pieces are joined back together
+    //
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    pub(crate) unsafe fn delete_range(&mut self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result {
+        if let Some(p) = *self.root {
+            let height = self.height(p)?;
+            let result = self.delete_range_helper(self.mem.get_page(p)?, height, start, end)?;
+            *self.root = result.map(|(page, _)| page);
+        }
+        Ok(())
+    }
+
+    fn height(&self, root: PageNumber) -> Result<usize> {
+        let mut height = 1;
+        let mut page = self.mem.get_page(root)?;
+        while page.memory()[0] == BRANCH {
+            let accessor = BranchAccessor::new(&page);
+            let child = accessor.child_page(0).unwrap();
+            drop(accessor);
+            page = self.mem.get_page(child)?;
+            height += 1;
+        }
+        Ok(height)
+    }
+
+    // Number of entries in the subtree rooted at the given page
+    fn entries_below(&self, page_number: PageNumber) -> Result<u64> {
+        Ok(subtree_entries(&self.mem.get_page(page_number)?))
+    }
+
+    fn below_start(key: &[u8], start: Bound<&[u8]>) -> Result<bool> {
+        Ok(match start {
+            Bound::Included(start) => K::try_compare(key, start)? == Ordering::Less,
+            Bound::Excluded(start) => K::try_compare(key, start)? != Ordering::Greater,
+            Bound::Unbounded => false,
+        })
+    }
+
+    fn above_end(key: &[u8], end: Bound<&[u8]>) -> Result<bool> {
+        Ok(match end {
+            Bound::Included(end) => K::try_compare(key, end)? == Ordering::Greater,
+            Bound::Excluded(end) => K::try_compare(key, end)? != Ordering::Less,
+            Bound::Unbounded => false,
+        })
+    }
+
+    // Returns the remaining subtree, and its height, which may differ from the original height
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    unsafe fn delete_range_helper(
+        &mut self,
+        page: PageImpl<'a>,
+        height: usize,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<Option<(PageNumber, usize)>> {
+        let page_number = page.get_page_number();
+        if page.memory()[0] == LEAF {
+            let accessor = LeafAccessor::new(&page);
+            let mut builder = LeafBuilder::new(self.mem, accessor.num_pairs(), V::alignment());
+            let mut remaining = 0;
+            for i in 0..accessor.num_pairs() {
+                let entry = accessor.entry(i).unwrap();
+                if Self::below_start(entry.key(), start)? || Self::above_end(entry.key(), end)? {
+                    builder.push(entry.key(), entry.value());
+                    remaining += 1;
+                }
+            }
+            if remaining == accessor.num_pairs() {
+                return Ok(Some((page_number, height)));
+            }
+            let result = if remaining > 0 {
+                Some((builder.build()?.get_page_number(), height))
+            } else {
+                None
+            };
+            drop(accessor);
+            drop(page);
+            self.free_policy
+                .conditional_free(page_number, self.freed, self.mem)?;
+            return Ok(result);
+        }
+
+        let accessor = BranchAccessor::new(&page);
+        let num_children = accessor.count_children();
+        // Remaining subtrees, and the keys which separate them
+        let mut pieces: Vec<(PageNumber, usize)> = vec![];
+        let mut separators: Vec<&[u8]> = vec![];
+        let mut changed = false;
+        for i in 0..num_children {
+            let child = accessor.child_page(i).unwrap();
+            // The child contains keys greater than lower, and less than or equal to upper
+            let lower = if i > 0 { accessor.key(i - 1) } else { None };
+            let upper = accessor.key(i);
+            let before_range = match upper {
+                Some(key) => Self::below_start(key, start)?,
+                None => false,
+            };
+            let after_range = match (lower, end) {
+                (Some(key), Bound::Included(end)) | (Some(key), Bound::Excluded(end)) => {
+                    K::try_compare(key, end)? != Ordering::Less
+                }
+                _ => false,
+            };
+            let after_start = match (lower, start) {
+                (_, Bound::Unbounded) => true,
+                (Some(key), Bound::Included(start)) | (Some(key), Bound::Excluded(start)) => {
+                    K::try_compare(key, start)? != Ordering::Less
+                }
+                (None, _) => false,
+            };
+            let before_end = match (upper, end) {
+                (_, Bound::Unbounded) => true,
+                (Some(key), Bound::Included(end)) => K::try_compare(key, end)? != Ordering::Greater,
+                (Some(key), Bound::Excluded(end)) => K::try_compare(key, end)? == Ordering::Less,
+                (None, _) => false,
+            };
+
+            let piece = if before_range || after_range {
+                Some((child, height - 1))
+            } else if after_start && before_end {
+                self.free_subtree(child, height - 1)?;
+                changed = true;
+                None
+            } else {
+                let result =
+                    self.delete_range_helper(self.mem.get_page(child)?, height - 1, start, end)?;
+                if result != Some((child, height - 1)) {
+                    changed = true;
+                }
+                result
+            };
+
+            if let Some(piece) = piece {
+                if !pieces.is_empty() {
+                    // Any key between the two pieces is a valid separator
+                    separators.push(lower.unwrap());
+                }
+                pieces.push(piece);
+            }
+        }
+
+        if !changed {
+            return Ok(Some((page_number, height)));
+        }
+
+        // Children which are still full height are kept together in a branch, and shorter
+        // subtrees are joined onto their neighbors
+        let mut result: Option<(PageNumber, usize)> = None;
+        let mut i = 0;
+        while i < pieces.len() {
+            let mut next = i + 1;
+            let piece = if pieces[i].1 == height - 1 {
+                while next < pieces.len() && pieces[next].1 == height - 1 {
+                    next += 1;
+                }
+                if next - i > 1 {
+                    let mut builder = BranchBuilder::new(self.mem, next - i);
+                    for j in i..next {
+                        builder.push_child(pieces[j].0, self.entries_below(pieces[j].0)?);
+                        if j + 1 < next {
+                            builder.push_key(separators[j]);
+                        }
+                    }
+                    // This is a subset of the original children, so it fits in a single page
+                    (builder.build()?.get_page_number(), height)
+                } else {
+                    pieces[i]
+                }
+            } else {
+                pieces[i]
+            };
+            result = if let Some(left) = result {
+                Some(self.join(left, separators[i - 1], piece)?)
+            } else {
+                Some(piece)
+            };
+            i = next;
+        }
+
+        drop(separators);
+        drop(accessor);
+        drop(page);
+        self.free_policy
+            .conditional_free(page_number, self.freed, self.mem)?;
+
+        Ok(result)
+    }
+
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    unsafe fn free_subtree(&mut self, page_number: PageNumber, height: usize) -> Result {
+        if height > 1 {
+            let page = self.mem.get_page(page_number)?;
+            let accessor = BranchAccessor::new(&page);
+            for i in 0..accessor.count_children() {
+                self.free_subtree(accessor.child_page(i).unwrap(), height - 1)?;
+            }
+        }
+        self.free_policy
+            .conditional_free(page_number, self.freed, self.mem)
+    }
+
+    // Joins two subtrees of any height. Every key in left must be less than or equal to separator,
+    // and every key in right greater than it
+    //
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    unsafe fn join(
+        &mut self,
+        left: (PageNumber, usize),
+        separator: &[u8],
+        right: (PageNumber, usize),
+    ) -> Result<(PageNumber, usize)> {
+        let (left_page, left_height) = left;
+        let (right_page, right_height) = right;
+        let (page, more, height) = match left_height.cmp(&right_height) {
+            Ordering::Equal => {
+                let (page, more) = self.merge_nodes(left_page, separator, right_page)?;
+                (page, more, left_height)
+            }
+            Ordering::Greater => {
+                let (page, more) = self.attach(left_page, left_height, separator, right, true)?;
+                (page, more, left_height)
+            }
+            Ordering::Less => {
+                let (page, more) = self.attach(right_page, right_height, separator, left, false)?;
+                (page, more, right_height)
+            }
+        };
+
+        if let Some((key, page2)) = more {
+            let mut builder = BranchBuilder::new(self.mem, 2);
+            builder.push_child(page, self.entries_below(page)?);
+            builder.push_key(&key);
+            builder.push_child(page2, self.entries_below(page2)?);
+            Ok((builder.build()?.get_page_number(), height + 1))
+        } else {
+            Ok((page, height))
+        }
+    }
+
+    // Merges two pages of the same height, splitting the result if it does not fit in one page
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    unsafe fn merge_nodes(
+        &mut self,
+        left: PageNumber,
+        separator: &[u8],
+        right: PageNumber,
+    ) -> Result<MaybeSplit> {
+        let left_page = self.mem.get_page(left)?;
+        let right_page = self.mem.get_page(right)?;
+        let result = if left_page.memory()[0] == LEAF {
+            let left_accessor = LeafAccessor::new(&left_page);
+            let right_accessor = LeafAccessor::new(&right_page);
+            let mut builder = LeafBuilder::new(
+                self.mem,
+                left_accessor.num_pairs() + right_accessor.num_pairs(),
+                V::alignment(),
+            );
+            builder.push_all_except(&left_accessor, None);
+            builder.push_all_except(&right_accessor, None);
+            if builder.should_split() {
+                let (page1, split_key, page2) = builder.build_split()?;
+                (
+                    page1.get_page_number(),
+                    Some((split_key.to_vec(), page2.get_page_number())),
+                )
+            } else {
+                (builder.build()?.get_page_number(), None)
+            }
+        } else {
+            let left_accessor = BranchAccessor::new(&left_page);
+            let right_accessor = BranchAccessor::new(&right_page);
+            let mut builder = BranchBuilder::new(
+                self.mem,
+                left_accessor.count_children() + right_accessor.count_children(),
+            );
+            builder.push_all(&left_accessor);
+            builder.push_key(separator);
+            builder.push_all(&right_accessor);
+            if builder.should_split() {
+                let (page1, split_key, page2) = builder.build_split()?;
+                (
+                    page1.get_page_number(),
+                    Some((split_key.to_vec(), page2.get_page_number())),
+                )
+            } else {
+                (builder.build()?.get_page_number(), None)
+            }
+        };
+        drop(left_page);
+        drop(right_page);
+        self.free_policy
+            .conditional_free(left, self.freed, self.mem)?;
+        self.free_policy
+            .conditional_free(right, self.freed, self.mem)?;
+
+        Ok(result)
+    }
+
+    // Attaches subtree, which must be shorter than the tree rooted at page_number, to the right
+    // (or left) edge of that tree
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    unsafe fn attach(
+        &mut self,
+        page_number: PageNumber,
+        height: usize,
+        separator: &[u8],
+        subtree: (PageNumber, usize),
+        on_right: bool,
+    ) -> Result<MaybeSplit> {
+        let page = self.mem.get_page(page_number)?;
+        let accessor = BranchAccessor::new(&page);
+        let num_children = accessor.count_children();
+        let child_index = if on_right { num_children - 1 } else { 0 };
+        let child = accessor.child_page(child_index).unwrap();
+        let (new_child, more) = if height - 1 == subtree.1 {
+            if on_right {
+                self.merge_nodes(child, separator, subtree.0)?
+            } else {
+                self.merge_nodes(subtree.0, separator, child)?
+            }
+        } else {
+            self.attach(child, height - 1, separator, subtree, on_right)?
+        };
+
+        let mut builder = BranchBuilder::new(self.mem, num_children + 1);
+        for i in 0..num_children {
+            if i == child_index {
+                builder.push_child(new_child, self.entries_below(new_child)?);
+                if let Some((ref key, page2)) = more {
+                    builder.push_key(key);
+                    builder.push_child(page2, self.entries_below(page2)?);
+                }
+            } else {
+                builder.push_child(
+                    accessor.child_page(i).unwrap(),
+                    accessor.child_entries(i).unwrap(),
+                );
+            }
+            if i + 1 < num_children {
+                builder.push_key(accessor.key(i).unwrap());
+            }
+        }
+        let result = if builder.should_split() {
+            let (page1, split_key, page2) = builder.build_split()?;
+            (
+                page1.get_page_number(),
+                Some((split_key.to_vec(), page2.get_page_number())),
+            )
+        } else {
+            (builder.build()?.get_page_number(), None)
+        };
+        drop(accessor);
+        drop(page);
+        self.free_policy
+            .conditional_free(page_number, self.freed, self.mem)?;
+
+        Ok(result)
+    }
+
+    // Inserts entries, which must be in strictly ascending key order. If they all follow the
+    // existing keys, the new pages are built bottom-up and each filled to fill_factor of its
+    // usable size, instead of being split as they fill up. Otherwise they're collected, so that
+    // their order can be checked before any is inserted
+    //
+    // Returns Error::UnsortedInput, and leaves the tree unchanged, if the keys are not ascending
+    //
+    // Safety: caller must ensure that no references to uncommitted pages in this tree exist
+    pub(crate) unsafe fn bulk_load<I, KR, VR>(&mut self, entries: I, fill_factor: f32) -> Result
+    where
+        I: Iterator<Item = (KR, VR)>,
+        KR: Borrow<K>,
+        VR: Borrow<V>,
+    {
+        if !(fill_factor > 0.0 && fill_factor <= 1.0) {
+            return Err(Error::InvalidFillFactor(fill_factor));
+        }
+        let mut entries = entries.peekable();
+        let existing = if let Some(root) = *self.root {
+            Some((root, self.height(root)?, self.last_key(root)?))
+        } else {
+            None
+        };
+
+        if let (Some((_, _, last_key)), Some((first, _))) = (&existing, entries.peek()) {
+            if K::try_compare(first.borrow().as_bytes().as_ref(), last_key)? != Ordering::Greater {
+                // The entries overlap the existing keys, so they have to be inserted one at a time
+                let entries: Vec<(KR, VR)> = entries.collect();
+                for pair in entries.windows(2) {
+                    Self::check_ascending(
+                        pair[0].0.borrow().as_bytes().as_ref(),
+                        pair[1].0.borrow().as_bytes().as_ref(),
+                    )?;
+                }
+                for (key, value) in entries {
+                    self.insert(key.borrow(), value.borrow())?;
+                }
+                return Ok(());
+            }
+        }
+
+        let target_bytes = (self.mem.get_usable_page_size() as f32 * fill_factor) as usize;
+        // The pages of the level being built, along with the last key stored in each
+        let mut level: Vec<(PageNumber, Vec<u8>)> = vec![];
+        let mut pairs: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+        let mut pair_bytes = 0;
+        for (key, value) in entries {
+            let key = key.borrow().as_bytes().as_ref().to_vec();
+            let value = value.borrow().as_bytes().as_ref().to_vec();
+            let previous = match (pairs.last(), level.last()) {
+                (Some((previous, _)), _) | (None, Some((_, previous))) => Some(previous),
+                (None, None) => None,
+            };
+            if let Some(previous) = previous {
+                if let Err(err) = Self::check_ascending(previous, &key) {
+                    // The leaves aren't referenced by the tree yet, so they're simply freed
+                    for (page, _) in level {
+                        self.mem.free(page)?;
+                    }
+                    return Err(err);
+                }
+            }
+            let required_bytes = LeafBuilder::required_bytes(
+                pairs.len() + 1,
+                pair_bytes + key.len() + value.len(),
+                V::alignment(),
+            );
+            if !pairs.is_empty() && required_bytes > target_bytes {
+                level.push(self.build_leaf(&pairs)?);
+                pairs.clear();
+                pair_bytes = 0;
+            }
+            pair_bytes += key.len() + value.len();
+            pairs.push((key, value));
+        }
+        if !pairs.is_empty() {
+            level.push(self.build_leaf(&pairs)?);
+        }
+
+        let mut height = 1;
+        while level.len() > 1 {
+            level = self.build_branches(&level, target_bytes)?;
+            height += 1;
+        }
+
+        if let Some((root, _)) = level.pop() {
+            let new_root = if let Some((existing_root, existing_height, last_key)) = existing {
+                self.join((existing_root, existing_height), &last_key, (root, height))?
+                    .0
+            } else {
+                root
+            };
+            *self.root = Some(new_root);
+        }
+
+        Ok(())
+    }
+
+    fn check_ascending(previous: &[u8], key: &[u8]) -> Result {
+        if K::try_compare(previous, key)? == Ordering::Less {
+            Ok(())
+        } else {
+            Err(Error::UnsortedInput)
+        }
+    }
+
+    fn last_key(&self, root: PageNumber) -> Result<Vec<u8>> {
+        let mut page = self.mem.get_page(root)?;
+        while page.memory()[0] == BRANCH {
+            let accessor = BranchAccessor::new(&page);
+            let child = accessor.child_page(accessor.count_children() - 1).unwrap();
+            drop(accessor);
+            page = self.mem.get_page(child)?;
+        }
+        let accessor = LeafAccessor::new(&page);
+        Ok(accessor.last_entry().key().to_vec())
+    }
+
+    fn build_leaf(&self, pairs: &[(Vec<u8>, Vec<u8>)]) -> Result<(PageNumber, Vec<u8>)> {
+        let mut builder = LeafBuilder::new(self.mem, pairs.len(), V::alignment());
+        for (key, value) in pairs {
+            builder.push(key, value);
+        }
+        let page = builder.build()?;
+        Ok((page.get_page_number(), pairs.last().unwrap().0.clone()))
+    }
+
+    // Builds the next level up, by grouping the given pages into branches
+    fn build_branches(
+        &self,
+        children: &[(PageNumber, Vec<u8>)],
+        target_bytes: usize,
+    ) -> Result<Vec<(PageNumber, Vec<u8>)>> {
+        // Start index of each branch
+        let mut starts = vec![0];
+        let mut key_bytes = 0;
+        for i in 1..children.len() {
+            let start = *starts.last().unwrap();
+            let num_keys = i - start;
+            let new_key_bytes = key_bytes + children[i - 1].1.len();
+            // Every branch needs at least two children
+            if num_keys > 1
+                && RawBranchBuilder::required_bytes(num_keys, new_key_bytes) > target_bytes
+            {
+                starts.push(i);
+                key_bytes = 0;
+            } else {
+                key_bytes = new_key_bytes;
+            }
+        }
+        if starts.len() > 1 && *starts.last().unwrap() == children.len() - 1 {
+            // Take a child from the previous branch, so that the last one has two. If that would
+            // leave the previous branch with only one child, they're combined instead
+            let previous_start = starts[starts.len() - 2];
+            if children.len() - 1 - previous_start > 2 {
+                *starts.last_mut().unwrap() -= 1;
+            } else {
+                starts.pop();
+            }
+        }
+
+        let mut result = vec![];
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(children.len());
+            let mut builder = BranchBuilder::new(self.mem, end - start);
+            for (j, (child, last_key)) in children[start..end].iter().enumerate() {
+                builder.push_child(*child, self.entries_below(*child)?);
+                if start + j + 1 < end {
+                    builder.push_key(last_key);
+                }
+            }
+            let page = builder.build()?;
+            result.push((page.get_page_number(), children[end - 1].1.clone()));
+        }
+
+        Ok(result)
+    }
 }
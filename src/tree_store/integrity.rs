@@ -0,0 +1,526 @@
+use crate::tree_store::btree_base::{BranchAccessor, LeafAccessor, BRANCH, LEAF};
+use crate::tree_store::page_store::{Page, RegionAllocationState, TransactionalMemory};
+use crate::tree_store::{FreedTableKey, InternalTableDefinition, PageNumber, TableType};
+use crate::types::{RedbKey, RedbValue};
+use crate::{Error, Result};
+use std::cmp::Ordering;
+use std::mem::size_of;
+
+/// Identifies the owner of a page, in an [`IntegrityViolation`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PageOwner {
+    /// The internal tree which maps table names to their definitions
+    TableTree,
+    /// The internal tree which stores the pages freed by each transaction
+    FreedTree,
+    /// The internal tree which stores the persistent snapshots
+    SnapshotTree,
+    /// A page which has been freed, but is retained until no read transaction can reference it
+    PendingFree,
+    /// The data tree of the named table
+    Table(String),
+}
+
+/// Location of a page in the database file
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PageLocation {
+    /// Region which contains the page
+    pub region: u32,
+    /// Index of the page, in units of its order
+    pub index: u32,
+    /// The page spans `2^order` of the database's pages
+    pub order: u8,
+}
+
+impl From<PageNumber> for PageLocation {
+    fn from(page: PageNumber) -> Self {
+        Self {
+            region: page.region,
+            index: page.page_index,
+            order: page.page_order,
+        }
+    }
+}
+
+/// A single problem found by [`crate::Database::check_integrity`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IntegrityViolation {
+    /// The keys stored in a page are out of order, or outside the range its parent page assigns it
+    KeyOrder {
+        owner: PageOwner,
+        page: PageLocation,
+    },
+    /// A branch page records a different number of entries for one of its children, than that
+    /// child's subtree contains
+    EntryCount {
+        owner: PageOwner,
+        page: PageLocation,
+    },
+    /// A page is referenced more than once, or overlaps another referenced page
+    DoubleReference {
+        page: PageLocation,
+        first: PageOwner,
+        second: PageOwner,
+    },
+    /// A page is referenced, but lies outside the allocated regions of the file
+    InvalidReference {
+        owner: PageOwner,
+        page: PageLocation,
+    },
+    /// A page could not be read, for example because its checksum is invalid
+    UnreadablePage {
+        owner: PageOwner,
+        page: PageLocation,
+        message: String,
+    },
+    /// A page is marked allocated, but is not referenced by any owner
+    LeakedPage { page: PageLocation },
+    /// A page is referenced, but is marked free by the allocator
+    AllocatorMismatch {
+        owner: PageOwner,
+        page: PageLocation,
+    },
+    /// A region is marked full, but its allocator has free pages
+    RegionMarkedFull { region: u32 },
+}
+
+/// Result of [`crate::Database::check_integrity`]
+#[derive(Debug)]
+pub struct IntegrityReport {
+    pub(crate) violations: Vec<IntegrityViolation>,
+    pub(crate) tables: usize,
+    pub(crate) unordered_tables: Vec<String>,
+    pub(crate) referenced_pages: usize,
+}
+
+impl IntegrityReport {
+    /// Returns true if no violations were found
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// All violations that were found
+    pub fn violations(&self) -> &[IntegrityViolation] {
+        &self.violations
+    }
+
+    /// Number of tables that were checked
+    pub fn tables(&self) -> usize {
+        self.tables
+    }
+
+    /// Names of the tables whose key ordering could not be checked, because they are multimap
+    /// tables, or their key type is not one of redb's non-generic key types, or a byte array
+    pub fn unordered_tables(&self) -> &[String] {
+        &self.unordered_tables
+    }
+
+    /// Number of pages, in units of the page size, referenced by all trees and pending frees
+    pub fn referenced_pages(&self) -> usize {
+        self.referenced_pages
+    }
+}
+
+type KeyComparator = fn(&[u8], &[u8]) -> Result<Ordering>;
+
+// Only the built in key types can be checked, since the comparator of a user defined type can't be
+// recovered from its name
+fn builtin_comparator(type_name: &str) -> Option<KeyComparator> {
+    let comparator: KeyComparator = match type_name {
+        "[u8]" => <[u8]>::try_compare,
+        "str" => str::try_compare,
+        "u8" => u8::try_compare,
+        "u16" => u16::try_compare,
+        "u32" => u32::try_compare,
+        "u64" => u64::try_compare,
+        "u128" => u128::try_compare,
+        "i8" => i8::try_compare,
+        "i16" => i16::try_compare,
+        "i32" => i32::try_compare,
+        "i64" => i64::try_compare,
+        "i128" => i128::try_compare,
+        "f32" => f32::try_compare,
+        "f64" => f64::try_compare,
+        "bool" => bool::try_compare,
+        "char" => char::try_compare,
+        "()" => <()>::try_compare,
+        "String" => String::try_compare,
+        "Vec<u8>" => <Vec<u8>>::try_compare,
+        // Byte arrays of any length are ordered like byte slices
+        name if name.starts_with("[u8;") => <[u8]>::try_compare,
+        _ => {
+            return None;
+        }
+    };
+    Some(comparator)
+}
+
+pub(crate) struct IntegrityChecker<'a> {
+    mem: &'a TransactionalMemory,
+    regions: Vec<RegionAllocationState>,
+    // Indexed by region, then order 0 page. Stores the index into owners of the page's owner
+    references: Vec<Vec<Option<usize>>>,
+    owners: Vec<PageOwner>,
+    violations: Vec<IntegrityViolation>,
+}
+
+impl<'a> IntegrityChecker<'a> {
+    // Must not be called while there are uncommitted writes
+    pub(crate) fn new(mem: &'a TransactionalMemory) -> Self {
+        let regions = mem.allocation_state();
+        let references = regions
+            .iter()
+            .map(|region| vec![None; region.free_pages.len()])
+            .collect();
+        Self {
+            mem,
+            regions,
+            references,
+            owners: vec![],
+            violations: vec![],
+        }
+    }
+
+    pub(crate) fn check(
+        mut self,
+        master_root: Option<PageNumber>,
+        freed_root: Option<PageNumber>,
+        snapshot_root: Option<PageNumber>,
+    ) -> Result<IntegrityReport> {
+        let mut tables = vec![];
+        if let Some(root) = master_root {
+            let owner = self.add_owner(PageOwner::TableTree);
+            self.check_tree(owner, root, Some(str::try_compare), &mut |key, value| {
+                tables.push((
+                    str::from_bytes(key).to_string(),
+                    InternalTableDefinition::from_bytes(value),
+                ));
+            })?;
+        }
+
+        let mut unordered_tables = vec![];
+        for (name, definition) in tables.iter() {
+            if let Some(root) = definition.get_root() {
+                // Multimap tables are ordered by (key, value) pairs, which we don't check
+                let comparator = match definition.get_type() {
+                    TableType::Normal => builtin_comparator(definition.get_key_type()),
+                    TableType::Multimap => None,
+                };
+                if comparator.is_none() {
+                    unordered_tables.push(name.clone());
+                }
+                let owner = self.add_owner(PageOwner::Table(name.clone()));
+                self.check_tree(owner, root, comparator, &mut |_, _| {})?;
+            }
+        }
+
+        // Pages referenced only by a snapshot are retained as pending frees, so only the snapshot
+        // table itself needs to be walked
+        if let Some(root) = snapshot_root {
+            let owner = self.add_owner(PageOwner::SnapshotTree);
+            self.check_tree(owner, root, Some(str::try_compare), &mut |_, _| {})?;
+        }
+
+        let mut pending_free = vec![];
+        if let Some(root) = freed_root {
+            let owner = self.add_owner(PageOwner::FreedTree);
+            self.check_tree(
+                owner,
+                root,
+                Some(FreedTableKey::try_compare),
+                &mut |_, value| {
+                    let length = u64::from_le_bytes(value[..size_of::<u64>()].try_into().unwrap());
+                    // 1..=length because the array is length prefixed
+                    for i in 1..=(length as usize) {
+                        let page = PageNumber::from_le_bytes(
+                            value[i * 8..(i + 1) * 8].try_into().unwrap(),
+                        );
+                        pending_free.push(page);
+                    }
+                },
+            )?;
+        }
+        let owner = self.add_owner(PageOwner::PendingFree);
+        for page in pending_free {
+            self.reference(owner, page);
+        }
+
+        let mut referenced_pages = 0;
+        for (region, state) in self.regions.iter().enumerate() {
+            let mut has_free_pages = false;
+            for (index, free) in state.free_pages.iter().enumerate() {
+                has_free_pages |= *free;
+                if self.references[region][index].is_some() {
+                    referenced_pages += 1;
+                } else if !*free {
+                    self.violations.push(IntegrityViolation::LeakedPage {
+                        page: PageNumber::new(region as u32, index as u32, 0).into(),
+                    });
+                }
+            }
+            if state.marked_full && has_free_pages {
+                self.violations.push(IntegrityViolation::RegionMarkedFull {
+                    region: region as u32,
+                });
+            }
+        }
+
+        Ok(IntegrityReport {
+            violations: self.violations,
+            tables: tables.len(),
+            unordered_tables,
+            referenced_pages,
+        })
+    }
+
+    fn add_owner(&mut self, owner: PageOwner) -> usize {
+        self.owners.push(owner);
+        self.owners.len() - 1
+    }
+
+    // Records a reference to the page. Returns false if the page is invalid, or was already referenced,
+    // in which case it must not be read
+    fn reference(&mut self, owner: usize, page: PageNumber) -> bool {
+        let region = page.region as usize;
+        let start = (page.page_index as usize) << page.page_order;
+        let end = (page.page_index as usize + 1) << page.page_order;
+        if region >= self.regions.len() || end > self.regions[region].free_pages.len() {
+            self.violations.push(IntegrityViolation::InvalidReference {
+                owner: self.owners[owner].clone(),
+                page: page.into(),
+            });
+            return false;
+        }
+
+        if let Some(first) = self.references[region][start..end].iter().find_map(|x| *x) {
+            self.violations.push(IntegrityViolation::DoubleReference {
+                page: page.into(),
+                first: self.owners[first].clone(),
+                second: self.owners[owner].clone(),
+            });
+            return false;
+        }
+
+        if self.regions[region].free_pages[start..end]
+            .iter()
+            .any(|free| *free)
+        {
+            self.violations.push(IntegrityViolation::AllocatorMismatch {
+                owner: self.owners[owner].clone(),
+                page: page.into(),
+            });
+        }
+        for reference in self.references[region][start..end].iter_mut() {
+            *reference = Some(owner);
+        }
+
+        true
+    }
+
+    fn check_tree(
+        &mut self,
+        owner: usize,
+        root: PageNumber,
+        comparator: Option<KeyComparator>,
+        visitor: &mut dyn FnMut(&[u8], &[u8]),
+    ) -> Result {
+        self.check_helper(owner, root, comparator, None, None, visitor)?;
+        Ok(())
+    }
+
+    // Keys in the subtree rooted at page_number must be greater than lower, and less than or equal to upper.
+    // Returns the number of entries in the subtree, or None if it could not be fully read
+    fn check_helper(
+        &mut self,
+        owner: usize,
+        page_number: PageNumber,
+        comparator: Option<KeyComparator>,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        visitor: &mut dyn FnMut(&[u8], &[u8]),
+    ) -> Result<Option<u64>> {
+        if !self.reference(owner, page_number) {
+            return Ok(None);
+        }
+        let page = match self.mem.get_page(page_number) {
+            Ok(page) => page,
+            Err(Error::Corrupted(message)) => {
+                self.violations.push(IntegrityViolation::UnreadablePage {
+                    owner: self.owners[owner].clone(),
+                    page: page_number.into(),
+                    message,
+                });
+                return Ok(None);
+            }
+            Err(err) => {
+                return Err(err);
+            }
+        };
+
+        let in_order = |keys: &mut dyn Iterator<Item = &[u8]>| -> bool {
+            let compare = if let Some(compare) = comparator {
+                compare
+            } else {
+                return true;
+            };
+            let mut previous = lower;
+            for key in keys {
+                // A key which can't be compared is reported as out of order
+                if let Some(previous) = previous {
+                    if !matches!(compare(previous, key), Ok(Ordering::Less)) {
+                        return false;
+                    }
+                }
+                previous = Some(key);
+            }
+            match (previous, upper) {
+                (Some(last), Some(upper)) => {
+                    matches!(compare(last, upper), Ok(Ordering::Less | Ordering::Equal))
+                }
+                _ => true,
+            }
+        };
+
+        match page.memory()[0] {
+            LEAF => {
+                let accessor = LeafAccessor::new(&page);
+                let mut keys = (0..accessor.num_pairs()).map(|i| accessor.entry(i).unwrap().key());
+                if !in_order(&mut keys) {
+                    self.violations.push(IntegrityViolation::KeyOrder {
+                        owner: self.owners[owner].clone(),
+                        page: page_number.into(),
+                    });
+                }
+                for i in 0..accessor.num_pairs() {
+                    let entry = accessor.entry(i).unwrap();
+                    visitor(entry.key(), entry.value());
+                }
+                Ok(Some(accessor.num_pairs() as u64))
+            }
+            BRANCH => {
+                let accessor = BranchAccessor::new(&page);
+                let num_keys = accessor.count_children() - 1;
+                let mut keys = (0..num_keys).map(|i| accessor.key(i).unwrap());
+                if !in_order(&mut keys) {
+                    self.violations.push(IntegrityViolation::KeyOrder {
+                        owner: self.owners[owner].clone(),
+                        page: page_number.into(),
+                    });
+                }
+                let mut total = Some(0);
+                let mut counts_match = true;
+                for i in 0..accessor.count_children() {
+                    let child = accessor.child_page(i).unwrap();
+                    let child_lower = if i == 0 { lower } else { accessor.key(i - 1) };
+                    let child_upper = if i == num_keys {
+                        upper
+                    } else {
+                        accessor.key(i)
+                    };
+                    let entries = self.check_helper(
+                        owner,
+                        child,
+                        comparator,
+                        child_lower,
+                        child_upper,
+                        visitor,
+                    )?;
+                    if let Some(entries) = entries {
+                        if entries != accessor.child_entries(i).unwrap() {
+                            counts_match = false;
+                        }
+                    }
+                    total = total.and_then(|total| entries.map(|entries| total + entries));
+                }
+                if !counts_match {
+                    self.violations.push(IntegrityViolation::EntryCount {
+                        owner: self.owners[owner].clone(),
+                        page: page_number.into(),
+                    });
+                }
+                Ok(total)
+            }
+            _ => {
+                self.violations.push(IntegrityViolation::UnreadablePage {
+                    owner: self.owners[owner].clone(),
+                    page: page_number.into(),
+                    message: format!("unknown page type {}", page.memory()[0]),
+                });
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tree_store::page_store::Page;
+    use crate::tree_store::{IntegrityViolation, PageLocation, PageOwner};
+    use crate::{Database, TableDefinition};
+    use tempfile::NamedTempFile;
+
+    const X: TableDefinition<[u8], [u8]> = TableDefinition::new("x");
+
+    #[test]
+    fn leaked_page() {
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(X).unwrap();
+            table.insert(b"hello", b"world").unwrap();
+        }
+        write_txn.commit().unwrap();
+        assert!(db.check_integrity().unwrap().is_ok());
+
+        // Allocate a page, and commit without referencing it
+        let mem = db.get_memory();
+        let page_number = mem.allocate(1).unwrap().get_page_number();
+        let transaction_id = mem.get_last_committed_transaction_id().unwrap() + 1;
+        mem.commit(
+            mem.get_data_root(),
+            mem.get_freed_root(),
+            mem.get_snapshot_root(),
+            transaction_id,
+            false,
+        )
+        .unwrap();
+
+        let report = db.check_integrity().unwrap();
+        assert_eq!(
+            report.violations(),
+            &[IntegrityViolation::LeakedPage {
+                page: PageLocation::from(page_number)
+            }]
+        );
+    }
+
+    #[test]
+    fn corrupted_page() {
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(X).unwrap();
+            table.insert(b"hello", b"world").unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        let mem = db.get_memory();
+        let root = mem.get_data_root().unwrap();
+        // Flip a bit, without updating the page's checksum
+        unsafe {
+            mem.get_page_mut(root).memory_mut()[0] ^= 1;
+        }
+
+        let report = db.check_integrity().unwrap();
+        assert!(!report.is_ok());
+        assert!(matches!(
+            &report.violations()[0],
+            IntegrityViolation::UnreadablePage {
+                owner: PageOwner::TableTree,
+                ..
+            }
+        ));
+    }
+}
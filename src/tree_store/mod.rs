@@ -2,12 +2,17 @@ mod btree;
 mod btree_base;
 mod btree_iters;
 mod btree_mutator;
+mod integrity;
 mod page_store;
 mod table_tree;
 
-pub(crate) use btree::{Btree, BtreeMut};
+pub(crate) use btree::{Btree, BtreeMut, LeafPosition};
 pub use btree_base::AccessGuard;
 pub(crate) use btree_base::AccessGuardMut;
 pub(crate) use btree_iters::{AllPageNumbersBtreeIter, BtreeRangeIter};
-pub(crate) use page_store::{get_db_size, PageNumber, TransactionalMemory};
-pub(crate) use table_tree::{FreedTableKey, InternalTableDefinition, TableTree, TableType};
+pub(crate) use integrity::IntegrityChecker;
+pub use integrity::{IntegrityReport, IntegrityViolation, PageLocation, PageOwner};
+pub(crate) use page_store::{get_db_size, PageNumber, TransactionalMemory, MAX_CAPACITY};
+pub(crate) use table_tree::{
+    FreedTableKey, InternalTableDefinition, SnapshotRecord, TableTree, TableType,
+};
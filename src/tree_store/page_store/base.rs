@@ -94,6 +94,7 @@ pub(crate) trait Page {
     fn get_page_number(&self) -> PageNumber;
 }
 
+#[derive(Clone)]
 pub struct PageImpl<'a> {
     pub(super) mem: &'a [u8],
     pub(super) page_number: PageNumber,
@@ -134,6 +134,23 @@ impl BuddyAllocator {
         pages
     }
 
+    /// Returns true if the order 0 page is free, either directly or as part of a larger free page
+    pub(crate) fn is_free(&self, data: &[u8], page_number: u64) -> bool {
+        for (order, allocator) in self.orders.iter().enumerate() {
+            let order_page = page_number >> order;
+            // Trailing pages which don't form a complete page of this order
+            if order_page >= allocator.get_num_pages() {
+                break;
+            }
+            let order_data = Self::get_order_bytes(data, order);
+            if !allocator.is_allocated(order_data, order_page) {
+                return true;
+            }
+        }
+
+        false
+    }
+
     fn get_order_offset_and_length(data: &[u8], order: usize) -> (usize, usize) {
         let max_order = u64::from_le_bytes(data[..size_of::<u64>()].try_into().unwrap()) as usize;
         assert!(order <= max_order);
@@ -311,4 +328,22 @@ mod test {
         }
         assert_eq!(allocator.count_free_pages(&data), num_pages);
     }
+
+    #[test]
+    fn is_free() {
+        let num_pages = 130;
+        let max_order = 7;
+        let mut data = vec![0; BuddyAllocator::required_space(num_pages, max_order)];
+        let allocator = BuddyAllocator::init_new(&mut data, num_pages, num_pages, max_order);
+        assert!((0..num_pages).all(|page| allocator.is_free(&data, page as u64)));
+
+        let page = allocator.alloc(&mut data, 2).unwrap();
+        for i in 0..num_pages as u64 {
+            let allocated = (page * 4..(page + 1) * 4).contains(&i);
+            assert_eq!(allocator.is_free(&data, i), !allocated);
+        }
+
+        allocator.free(&mut data, page, 2);
+        assert!((0..num_pages).all(|page| allocator.is_free(&data, page as u64)));
+    }
 }
@@ -0,0 +1,95 @@
+// CRC-32C (Castagnoli), as used by iSCSI, ext4 and others. Reflected polynomial.
+const POLYNOMIAL: u32 = 0x82F6_3B78;
+
+// Slicing-by-8 lookup tables. TABLES[0] is the standard byte-at-a-time table
+const TABLES: [[u32; 256]; 8] = build_tables();
+
+const fn build_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < 256 {
+        let mut t = 1;
+        while t < 8 {
+            let previous = tables[t - 1][i];
+            tables[t][i] = (previous >> 8) ^ tables[0][(previous & 0xFF) as usize];
+            t += 1;
+        }
+        i += 1;
+    }
+
+    tables
+}
+
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ crc;
+        let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        crc = TABLES[7][(low & 0xFF) as usize]
+            ^ TABLES[6][((low >> 8) & 0xFF) as usize]
+            ^ TABLES[5][((low >> 16) & 0xFF) as usize]
+            ^ TABLES[4][(low >> 24) as usize]
+            ^ TABLES[3][(high & 0xFF) as usize]
+            ^ TABLES[2][((high >> 8) & 0xFF) as usize]
+            ^ TABLES[1][((high >> 16) & 0xFF) as usize]
+            ^ TABLES[0][(high >> 24) as usize];
+    }
+    for byte in chunks.remainder() {
+        crc = (crc >> 8) ^ TABLES[0][((crc ^ *byte as u32) & 0xFF) as usize];
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tree_store::page_store::checksum::crc32c;
+
+    #[test]
+    fn known_values() {
+        // Check values from RFC 3720, appendix B.4
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(&[0u8; 32]), 0x8A91_36AA);
+        assert_eq!(crc32c(&[0xFFu8; 32]), 0x62A8_AB43);
+        let ascending: Vec<u8> = (0..32).collect();
+        assert_eq!(crc32c(&ascending), 0x46DD_794E);
+    }
+
+    #[test]
+    fn unaligned_lengths() {
+        let data: Vec<u8> = (0..100u8).collect();
+        for len in 0..data.len() {
+            let mut crc = !0u32;
+            for byte in &data[..len] {
+                crc ^= *byte as u32;
+                for _ in 0..8 {
+                    crc = if crc & 1 != 0 {
+                        (crc >> 1) ^ 0x82F6_3B78
+                    } else {
+                        crc >> 1
+                    };
+                }
+            }
+            assert_eq!(crc32c(&data[..len]), !crc);
+        }
+    }
+}
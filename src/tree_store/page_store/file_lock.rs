@@ -0,0 +1,277 @@
+use crate::{Error, Result};
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io;
+use std::mem::{self, ManuallyDrop};
+use std::os::raw::c_short;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Mutex, Once};
+
+// Instances which have the same database file open, in this or other processes, coordinate through
+// locks on single bytes of the file. These are far past the end of any database, since locked bytes
+// don't need to exist
+const LOCK_BASE: u64 = 1 << 62;
+// Held exclusively by the instance which has the database open for writing
+const WRITER_LOCK: u64 = LOCK_BASE;
+// Held shared by every instance which has the file mapped, and exclusively while truncating it
+const MAPPED_LOCK: u64 = LOCK_BASE + 1;
+// Held shared by read-only instances, at this offset plus the transaction id of each commit that
+// they're reading
+const READER_LOCK_BASE: u64 = LOCK_BASE + 2;
+
+// libc declares the lock types as c_int on some platforms, and c_short on others
+#[allow(clippy::unnecessary_cast)]
+const READ_LOCK: c_short = libc::F_RDLCK as c_short;
+#[allow(clippy::unnecessary_cast)]
+const WRITE_LOCK: c_short = libc::F_WRLCK as c_short;
+#[allow(clippy::unnecessary_cast)]
+const UNLOCK: c_short = libc::F_UNLCK as c_short;
+
+// (device, inode) of a database file
+type FileId = (u64, u64);
+
+// POSIX record locks are owned by the process, rather than by a file descriptor: a process never
+// conflicts with its own locks, and closing any descriptor of the file releases all of them. So
+// every instance of a file in this process is tracked here, and the locks held are the union of
+// those they need
+#[derive(Default)]
+struct FileState {
+    instances: usize,
+    writer: bool,
+    // Number of read transactions reading each commit, across all the read-only instances
+    pinned: BTreeMap<u64, usize>,
+    // Descriptors of instances which have been closed. They're only closed once every instance is,
+    // since closing them would release the locks of the others
+    closed: Vec<File>,
+}
+
+fn registry() -> &'static Mutex<HashMap<FileId, FileState>> {
+    static INIT: Once = Once::new();
+    static REGISTRY: AtomicPtr<Mutex<HashMap<FileId, FileState>>> = AtomicPtr::new(ptr::null_mut());
+    INIT.call_once(|| {
+        let registry = Box::new(Mutex::new(HashMap::new()));
+        REGISTRY.store(Box::into_raw(registry), Ordering::Release);
+    });
+    // Safety: the registry is initialized above, and never freed
+    unsafe { &*REGISTRY.load(Ordering::Acquire) }
+}
+
+// A database file, and the locks which this instance holds on it
+pub(super) struct LockedFile {
+    file: ManuallyDrop<File>,
+    id: FileId,
+    writer: bool,
+}
+
+impl LockedFile {
+    // Returns Error::DatabaseAlreadyOpen if another instance, in this or another process, has the
+    // file open for writing
+    pub(super) fn new(file: File, writer: bool) -> Result<Self> {
+        let metadata = file.metadata()?;
+        let mut locked = Self {
+            file: ManuallyDrop::new(file),
+            id: (metadata.dev(), metadata.ino()),
+            writer: false,
+        };
+        let mut registry = registry().lock()?;
+        let state = registry.entry(locked.id).or_default();
+        state.instances += 1;
+        // From here on, the file is released by drop()
+        let result = locked.lock(state, writer);
+        drop(registry);
+        result?;
+
+        Ok(locked)
+    }
+
+    fn lock(&mut self, state: &mut FileState, writer: bool) -> Result {
+        if state.instances == 1 {
+            // Wait, rather than fail, if the writer is truncating the file
+            set_lock(&self.file, READ_LOCK, MAPPED_LOCK, true)?;
+        }
+        if writer {
+            if state.writer {
+                return Err(Error::DatabaseAlreadyOpen);
+            }
+            set_lock(&self.file, WRITE_LOCK, WRITER_LOCK, false)?;
+            state.writer = true;
+            self.writer = true;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn file(&self) -> &File {
+        &self.file
+    }
+
+    // Runs `f` while holding the mapped lock exclusively, so that no other instance has the file
+    // mapped. Returns None, without running it, if another instance, in this or another process,
+    // has the file open
+    pub(super) fn with_exclusive<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<Option<T>> {
+        // Hold the registry lock throughout, so that no instance is opened in this process
+        let registry = registry().lock()?;
+        if registry[&self.id].instances > 1 {
+            return Ok(None);
+        }
+        match set_lock(&self.file, WRITE_LOCK, MAPPED_LOCK, false) {
+            Ok(()) => {}
+            Err(Error::DatabaseAlreadyOpen) => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let result = f();
+        // Converting the lock is atomic, so no other instance can take the exclusive lock between
+        let downgraded = set_lock(&self.file, READ_LOCK, MAPPED_LOCK, false);
+        drop(registry);
+        let result = result?;
+        downgraded?;
+
+        Ok(Some(result))
+    }
+
+    // Returns true if an instance, in this or another process, has the file open for writing
+    pub(super) fn has_writer(&self) -> Result<bool> {
+        let registry = registry().lock()?;
+        if registry[&self.id].writer {
+            return Ok(true);
+        }
+        drop(registry);
+
+        Ok(find_lock(&self.file, WRITER_LOCK, WRITER_LOCK + 1)?.is_some())
+    }
+
+    // Prevents the writer from reusing the pages of the commit with the given transaction id,
+    // until a matching call to unpin()
+    pub(super) fn pin(&self, transaction_id: u64) -> Result {
+        let mut registry = registry().lock()?;
+        let pinned = &mut registry.get_mut(&self.id).unwrap().pinned;
+        let count = pinned.entry(transaction_id).or_default();
+        if *count == 0 {
+            if let Err(err) = set_lock(
+                &self.file,
+                READ_LOCK,
+                READER_LOCK_BASE + transaction_id,
+                false,
+            ) {
+                pinned.remove(&transaction_id);
+                return Err(err);
+            }
+        }
+        *count += 1;
+
+        Ok(())
+    }
+
+    pub(super) fn unpin(&self, transaction_id: u64) -> Result {
+        let mut registry = registry().lock()?;
+        let pinned = &mut registry.get_mut(&self.id).unwrap().pinned;
+        let count = pinned.get_mut(&transaction_id).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            pinned.remove(&transaction_id);
+            set_lock(&self.file, UNLOCK, READER_LOCK_BASE + transaction_id, false)?;
+        }
+
+        Ok(())
+    }
+
+    // Returns the oldest commit, before `end`, which is pinned by a read-only instance in this or
+    // another process
+    pub(super) fn oldest_pinned(&self, end: u64) -> Result<Option<u64>> {
+        let registry = registry().lock()?;
+        let mut oldest = registry[&self.id]
+            .pinned
+            .keys()
+            .next()
+            .copied()
+            .filter(|id| *id < end);
+        drop(registry);
+
+        // Locks held by other processes are found one at a time, in no particular order, so keep
+        // searching below the oldest found so far
+        let mut end = oldest.unwrap_or(end);
+        while end > 0 {
+            match find_lock(&self.file, READER_LOCK_BASE, READER_LOCK_BASE + end)? {
+                Some(offset) => {
+                    end = offset - READER_LOCK_BASE;
+                    oldest = Some(end);
+                }
+                None => break,
+            }
+        }
+
+        Ok(oldest)
+    }
+}
+
+impl Drop for LockedFile {
+    fn drop(&mut self) {
+        // Safety: self.file is not used again
+        let file = unsafe { ManuallyDrop::take(&mut self.file) };
+        let mut registry = match registry().lock() {
+            Ok(registry) => registry,
+            // Leak the file, rather than release the locks of other instances
+            Err(_) => return mem::forget(file),
+        };
+        let state = registry.get_mut(&self.id).unwrap();
+        state.instances -= 1;
+        if state.instances == 0 {
+            // Closing every descriptor releases all of the locks
+            registry.remove(&self.id);
+            return;
+        }
+        if self.writer {
+            state.writer = false;
+            // If this fails, no other process can open the database for writing until this one
+            // closes it, which is safe
+            let _ = set_lock(&file, UNLOCK, WRITER_LOCK, false);
+        }
+        state.closed.push(file);
+    }
+}
+
+fn lock_range(lock_type: c_short, start: u64, end: u64) -> libc::flock {
+    // Safety: flock is a plain C struct, so all zeros is a valid value
+    let mut lock: libc::flock = unsafe { mem::zeroed() };
+    lock.l_type = lock_type;
+    lock.l_whence = libc::SEEK_SET as c_short;
+    lock.l_start = start as libc::off_t;
+    lock.l_len = (end - start) as libc::off_t;
+    lock
+}
+
+// Returns Error::DatabaseAlreadyOpen if `wait` is false, and the lock is held by another process
+fn set_lock(file: &File, lock_type: c_short, offset: u64, wait: bool) -> Result {
+    let lock = lock_range(lock_type, offset, offset + 1);
+    let command = if wait { libc::F_SETLKW } else { libc::F_SETLK };
+    loop {
+        let result = unsafe { libc::fcntl(file.as_raw_fd(), command, &lock) };
+        if result == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => {}
+            Some(libc::EAGAIN) | Some(libc::EACCES) => return Err(Error::DatabaseAlreadyOpen),
+            _ => return Err(err.into()),
+        }
+    }
+}
+
+// Returns the offset of a byte in [start, end) which is locked by another process, if any
+fn find_lock(file: &File, start: u64, end: u64) -> Result<Option<u64>> {
+    let mut lock = lock_range(WRITE_LOCK, start, end);
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) };
+    if result != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    if lock.l_type == UNLOCK {
+        Ok(None)
+    } else {
+        Ok(Some(max(lock.l_start as u64, start)))
+    }
+}
@@ -15,6 +15,12 @@ impl<'a> U64GroupedBitMap<'a> {
     pub(crate) fn count_unset(&self) -> usize {
         self.data.iter().map(|x| x.count_zeros() as usize).sum()
     }
+
+    pub(crate) fn get(&self, bit: usize) -> bool {
+        let index = (bit / 64) * size_of::<u64>();
+        let group = u64::from_le_bytes(self.data[index..(index + 8)].try_into().unwrap());
+        group & (1u64 << (bit % 64)) != 0
+    }
 }
 
 pub(crate) struct U64GroupedBitMapMut<'a> {
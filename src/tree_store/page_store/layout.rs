@@ -275,6 +275,29 @@ impl DatabaseLayout {
         Ok(result)
     }
 
+    // Returns this layout, with the region allocator sized for a database of the given capacity.
+    // Returns None if that would require a larger database header, since the regions would have to move
+    pub(super) fn with_capacity(
+        &self,
+        db_capacity: usize,
+        max_usable_region_bytes: usize,
+        page_size: usize,
+    ) -> Result<Option<Self>> {
+        let sized = Self::calculate(
+            db_capacity,
+            self.usable_bytes(),
+            max_usable_region_bytes,
+            page_size,
+        )?;
+        if sized.db_header_bytes != self.db_header_bytes {
+            return Ok(None);
+        }
+        let mut result = self.clone();
+        result.region_allocator_range = sized.region_allocator_range;
+
+        Ok(Some(result))
+    }
+
     pub(super) fn full_region_layout(&self) -> &RegionLayout {
         &self.full_region_layout
     }
@@ -1,17 +1,23 @@
+use crate::tree_store::page_store::file_lock::LockedFile;
 use crate::Result;
+use std::cmp::max;
 use std::fs::File;
 use std::io;
 use std::ops::Range;
 use std::os::unix::io::AsRawFd;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::{ptr, slice};
 
 pub(crate) struct Mmap {
-    #[allow(dead_code)]
-    file: File,
-    mmap: *mut u8,
+    file: LockedFile,
+    writable: bool,
+    mmap: AtomicPtr<u8>,
     len: AtomicUsize,
-    capacity: usize,
+    capacity: AtomicUsize,
+    // Mappings which have been replaced by a larger one. These are kept until drop, because
+    // references into them may still be held
+    retired: Mutex<Vec<(*mut u8, usize)>>,
 }
 
 // mmap() is documented as being multi-thread safe
@@ -19,28 +25,68 @@ unsafe impl Send for Mmap {}
 unsafe impl Sync for Mmap {}
 
 impl Mmap {
-    pub(crate) fn new(file: File, max_capacity: usize) -> Result<Self> {
-        let len = file.metadata()?.len();
-        assert!(len <= max_capacity as u64);
+    // capacity: the amount of address space to reserve initially. The mapping is moved if the file
+    // grows beyond it
+    //
+    // Returns Error::DatabaseAlreadyOpen if another instance has the file open for writing
+    pub(crate) fn new(file: File, capacity: usize) -> Result<Self> {
+        Self::map_file(LockedFile::new(file, true)?, capacity, true)
+    }
+
+    // Maps the file without write access
+    pub(crate) fn new_read_only(file: File) -> Result<Self> {
+        Self::map_file(LockedFile::new(file, false)?, 0, false)
+    }
+
+    fn map_file(file: LockedFile, capacity: usize, writable: bool) -> Result<Self> {
+        let len = file.file().metadata()?.len() as usize;
+        let capacity = max(len, capacity);
+        let mmap = Self::map(file.file(), writable, capacity, ptr::null_mut())?;
+        Ok(Self {
+            file,
+            writable,
+            mmap: AtomicPtr::new(mmap),
+            len: AtomicUsize::new(len),
+            capacity: AtomicUsize::new(capacity),
+            retired: Mutex::new(vec![]),
+        })
+    }
+
+    fn map(
+        file: &File,
+        writable: bool,
+        capacity: usize,
+        fixed_address: *mut u8,
+    ) -> Result<*mut u8> {
+        let flags = if fixed_address.is_null() {
+            libc::MAP_SHARED
+        } else {
+            libc::MAP_SHARED | libc::MAP_FIXED
+        };
+        let protection = if writable {
+            libc::PROT_READ | libc::PROT_WRITE
+        } else {
+            libc::PROT_READ
+        };
         let mmap = unsafe {
             libc::mmap(
-                ptr::null_mut(),
-                max_capacity as libc::size_t,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_SHARED,
+                fixed_address as *mut libc::c_void,
+                capacity as libc::size_t,
+                protection,
+                flags,
                 file.as_raw_fd(),
                 0,
             )
         };
         if mmap == libc::MAP_FAILED {
-            return Err(io::Error::last_os_error().into());
+            Err(io::Error::last_os_error().into())
+        } else {
+            Ok(mmap as *mut u8)
         }
-        Ok(Self {
-            mmap: mmap as *mut u8,
-            file,
-            len: AtomicUsize::new(len as usize),
-            capacity: max_capacity,
-        })
+    }
+
+    pub(crate) fn locks(&self) -> &LockedFile {
+        &self.file
     }
 
     pub(crate) fn len(&self) -> usize {
@@ -48,26 +94,39 @@ impl Mmap {
     }
 
     // Safety: if new_len < len(), caller must ensure that no references to memory in new_len..len() exist
+    //
+    // If the mapping is read-only, the file is not resized. Only the mapping is updated, to match a
+    // file which has been resized by another process
     pub(crate) unsafe fn resize(&self, new_len: usize) -> Result<()> {
-        assert!(new_len <= self.capacity);
-        self.file.set_len(new_len as u64)?;
-
-        let mmap = libc::mmap(
-            self.mmap as *mut libc::c_void,
-            self.capacity as libc::size_t,
-            libc::PROT_READ | libc::PROT_WRITE,
-            libc::MAP_SHARED | libc::MAP_FIXED,
-            self.file.as_raw_fd(),
-            0,
-        );
+        if self.writable {
+            self.file.file().set_len(new_len as u64)?;
+        }
 
-        if mmap == libc::MAP_FAILED {
-            Err(io::Error::last_os_error().into())
+        let old_mmap = self.mmap.load(Ordering::Acquire);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        if new_len <= capacity {
+            let mmap = Self::map(self.file.file(), self.writable, capacity, old_mmap)?;
+            assert_eq!(mmap, old_mmap);
         } else {
-            assert_eq!(mmap as *mut u8, self.mmap);
-            self.len.store(new_len, Ordering::Release);
-            Ok(())
+            // Map the file again, at a new address with room to grow. Both mappings are of the same
+            // file, so writes through either are visible through the other, and the old one can
+            // be retired rather than unmapped
+            let new_capacity = max(new_len, 2 * capacity);
+            let mmap = Self::map(
+                self.file.file(),
+                self.writable,
+                new_capacity,
+                ptr::null_mut(),
+            )?;
+            self.retired.lock().unwrap().push((old_mmap, capacity));
+            self.capacity.store(new_capacity, Ordering::Release);
+            self.mmap.store(mmap, Ordering::Release);
         }
+        // Must be stored after the mapping, so that a reader which observes the new length also
+        // observes a mapping that contains it
+        self.len.store(new_len, Ordering::Release);
+
+        Ok(())
     }
 
     #[cfg(not(target_os = "macos"))]
@@ -77,7 +136,7 @@ impl Mmap {
         {
             let result = unsafe {
                 libc::msync(
-                    self.mmap as *mut libc::c_void,
+                    self.mmap.load(Ordering::Acquire) as *mut libc::c_void,
                     self.len() as libc::size_t,
                     libc::MS_SYNC,
                 )
@@ -93,7 +152,7 @@ impl Mmap {
     pub(crate) fn flush(&self) -> Result {
         #[cfg(not(fuzzing))]
         {
-            let code = unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_FULLFSYNC) };
+            let code = unsafe { libc::fcntl(self.file.file().as_raw_fd(), libc::F_FULLFSYNC) };
             if code == -1 {
                 return Err(io::Error::last_os_error().into());
             }
@@ -112,7 +171,7 @@ impl Mmap {
         //       Investigate switching to `write()`
         #[cfg(not(fuzzing))]
         {
-            let code = unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_BARRIERFSYNC) };
+            let code = unsafe { libc::fcntl(self.file.file().as_raw_fd(), libc::F_BARRIERFSYNC) };
             if code == -1 {
                 return Err(io::Error::last_os_error().into());
             }
@@ -124,7 +183,7 @@ impl Mmap {
     // from .get_memory_mut()
     pub(crate) unsafe fn get_memory(&self, range: Range<usize>) -> &[u8] {
         assert!(range.end <= self.len());
-        let ptr = self.mmap.add(range.start);
+        let ptr = self.mmap.load(Ordering::Acquire).add(range.start);
         slice::from_raw_parts(ptr, range.len())
     }
 
@@ -132,19 +191,20 @@ impl Mmap {
     // from .get_memory() or .get_memory_mut()
     #[allow(clippy::mut_from_ref)]
     pub(crate) unsafe fn get_memory_mut(&self, range: Range<usize>) -> &mut [u8] {
+        assert!(self.writable);
         assert!(range.end <= self.len());
-        let ptr = self.mmap.add(range.start);
+        let ptr = self.mmap.load(Ordering::Acquire).add(range.start);
         slice::from_raw_parts_mut(ptr, range.len())
     }
 }
 
 impl Drop for Mmap {
     fn drop(&mut self) {
-        unsafe {
-            libc::munmap(
-                self.mmap as *mut libc::c_void,
-                self.capacity as libc::size_t,
-            );
+        let current = (*self.mmap.get_mut(), *self.capacity.get_mut());
+        for (mmap, capacity) in self.retired.get_mut().unwrap().drain(..).chain([current]) {
+            unsafe {
+                libc::munmap(mmap as *mut libc::c_void, capacity as libc::size_t);
+            }
         }
     }
 }
@@ -161,4 +221,24 @@ mod test {
             Mmap::new(tmpfile.into_file(), 1024 * 1024).unwrap();
         }
     }
+
+    #[test]
+    fn grow_past_capacity() {
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let mmap = Mmap::new(tmpfile.into_file(), 4096).unwrap();
+        unsafe {
+            mmap.resize(4096).unwrap();
+            mmap.get_memory_mut(0..1)[0] = 1;
+            let old = mmap.get_memory(0..4096);
+
+            // Moves the mapping
+            mmap.resize(1024 * 1024).unwrap();
+            let new = mmap.get_memory_mut(1..(1024 * 1024));
+            new[0] = 2;
+            new[1024 * 1024 - 2] = 3;
+
+            // References into the old mapping are still valid, and see the same data
+            assert_eq!(&old[0..2], &[1, 2]);
+        }
+    }
 }
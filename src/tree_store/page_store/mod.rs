@@ -1,13 +1,18 @@
 mod base;
 mod buddy_allocator;
+mod checksum;
+mod file_lock;
 mod grouped_bitmap;
 mod layout;
 mod mmap;
 mod page_allocator;
 mod page_manager;
 mod utils;
+mod verified_pages;
 
 pub(crate) use base::PageNumber;
-pub(crate) use page_manager::{get_db_size, TransactionalMemory};
+pub(crate) use page_manager::{
+    get_db_size, RegionAllocationState, TransactionalMemory, MAX_CAPACITY,
+};
 
 pub(super) use base::{Page, PageImpl, PageMut};
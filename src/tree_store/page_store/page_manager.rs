@@ -1,8 +1,10 @@
 use crate::tree_store::page_store::buddy_allocator::BuddyAllocator;
-use crate::tree_store::page_store::grouped_bitmap::U64GroupedBitMapMut;
+use crate::tree_store::page_store::checksum::crc32c;
+use crate::tree_store::page_store::grouped_bitmap::{U64GroupedBitMap, U64GroupedBitMapMut};
 use crate::tree_store::page_store::layout::DatabaseLayout;
 use crate::tree_store::page_store::mmap::Mmap;
 use crate::tree_store::page_store::utils::get_page_size;
+use crate::tree_store::page_store::verified_pages::VerifiedPages;
 use crate::tree_store::page_store::{PageImpl, PageMut};
 use crate::tree_store::PageNumber;
 use crate::Error;
@@ -12,11 +14,13 @@ use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io;
-use std::io::{Read, Seek, SeekFrom};
 use std::mem::size_of;
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, Ordering};
 use std::sync::{Mutex, MutexGuard};
+use std::thread;
 
 // Database layout:
 // Header (first 128 bytes):
@@ -31,36 +35,50 @@ use std::sync::{Mutex, MutexGuard};
 // 1 byte: version
 // 1 byte: != 0 if root page is non-null
 // 1 byte: != 0 if freed table root page is non-null
-// 5 bytes: padding
+// 1 byte: != 0 if snapshot table root page is non-null
+// 4 bytes: padding
 // 8 bytes: root page
 // 8 bytes: freed table root page
 // 8 bytes: last committed transaction id
 // (db layout size) bytes: active database layout
+// 8 bytes: snapshot table root page
 //
 // Commit slot 1 (next 128 bytes):
 // Same layout as slot 0
+//
+// Each page begins with a header, which is excluded from the memory returned by get_page():
+// 4 bytes: CRC-32C checksum of the remainder of the page
+// 4 bytes: padding to 64-bit aligned
 
 // Regions have a maximum size of 4GiB. A `4GiB - overhead` value is the largest that can be represented,
 // because the leaf node format uses 32bit offsets
 const MAX_USABLE_REGION_SPACE: usize = 4 * 1024 * 1024 * 1024;
+// Requested capacities are clamped to this, so that a database can be created with no practical limit
+// on its size. Only address space for the current file is reserved, so this costs little
+pub(crate) const MAX_CAPACITY: usize = 256 * 1024 * 1024 * 1024 * 1024;
+// Address space reserved up front. The file is remapped if it grows larger
+const INITIAL_MAPPING_CAPACITY: usize = 1024 * 1024 * 1024;
 pub(crate) const MAX_PAGE_ORDER: usize = 20;
 pub(super) const MIN_USABLE_PAGES: usize = 10;
 const MIN_DESIRED_USABLE_BYTES: usize = 1024 * 1024;
 
 // TODO: set to 1, when version 1.0 is released
-const FILE_FORMAT_VERSION: u8 = 100;
+const FILE_FORMAT_VERSION: u8 = 106;
 
 // Inspired by PNG's magic number
 const MAGICNUMBER: [u8; 9] = [b'r', b'e', b'd', b'b', 0x1A, 0x0A, 0xA9, 0x0D, 0x0A];
 const PAGE_SIZE_OFFSET: usize = MAGICNUMBER.len();
 const GOD_BYTE_OFFSET: usize = PAGE_SIZE_OFFSET + size_of::<u8>();
-const RESERVED: usize = 5;
-const REGION_MAX_USABLE_OFFSET: usize = GOD_BYTE_OFFSET + size_of::<u8>() + RESERVED;
+const RECLAIMING_OFFSET: usize = GOD_BYTE_OFFSET + size_of::<u8>();
+const GENERATION_OFFSET: usize = RECLAIMING_OFFSET + size_of::<u8>();
+const REGION_MAX_USABLE_OFFSET: usize = GENERATION_OFFSET + size_of::<u32>();
 const DB_SIZE_OFFSET: usize = REGION_MAX_USABLE_OFFSET + size_of::<u64>();
 const TRANSACTION_SIZE: usize = 128;
 const TRANSACTION_0_OFFSET: usize = 128;
 const TRANSACTION_1_OFFSET: usize = TRANSACTION_0_OFFSET + TRANSACTION_SIZE;
 pub(super) const DB_HEADER_SIZE: usize = TRANSACTION_1_OFFSET + TRANSACTION_SIZE;
+const PAGE_CHECKSUM_OFFSET: usize = 0;
+const PAGE_HEADER_SIZE: usize = 8;
 
 // God byte flags
 const PRIMARY_BIT: u8 = 1;
@@ -70,11 +88,22 @@ const ALLOCATOR_STATE_DIRTY: u8 = 2;
 const VERSION_OFFSET: usize = 0;
 const ROOT_NON_NULL_OFFSET: usize = size_of::<u8>();
 const FREED_ROOT_NON_NULL_OFFSET: usize = ROOT_NON_NULL_OFFSET + size_of::<u8>();
-const PADDING: usize = 5;
-const ROOT_PAGE_OFFSET: usize = FREED_ROOT_NON_NULL_OFFSET + size_of::<u8>() + PADDING;
+const SNAPSHOT_ROOT_NON_NULL_OFFSET: usize = FREED_ROOT_NON_NULL_OFFSET + size_of::<u8>();
+const PADDING: usize = 4;
+const ROOT_PAGE_OFFSET: usize = SNAPSHOT_ROOT_NON_NULL_OFFSET + size_of::<u8>() + PADDING;
 const FREED_ROOT_OFFSET: usize = ROOT_PAGE_OFFSET + size_of::<u64>();
 const TRANSACTION_ID_OFFSET: usize = FREED_ROOT_OFFSET + size_of::<u64>();
 const DATA_LAYOUT_OFFSET: usize = TRANSACTION_ID_OFFSET + size_of::<u64>();
+const SNAPSHOT_ROOT_OFFSET: usize = DATA_LAYOUT_OFFSET + DatabaseLayout::serialized_size();
+
+// The header may be written concurrently by another process
+fn read_generation(header: &[u8]) -> u32 {
+    // Safety: the offset is aligned, since the header is at the start of the mapping
+    unsafe {
+        let ptr = header[GENERATION_OFFSET..].as_ptr() as *const u32;
+        u32::from_le(ptr::read_volatile(ptr))
+    }
+}
 
 fn ceil_log2(x: usize) -> usize {
     if x.is_power_of_two() {
@@ -84,11 +113,9 @@ fn ceil_log2(x: usize) -> usize {
     }
 }
 
-pub(crate) fn get_db_size(path: impl AsRef<Path>) -> Result<usize, io::Error> {
+pub(crate) fn get_db_size(file: &File) -> Result<usize, io::Error> {
     let mut db_size = [0u8; size_of::<u64>()];
-    let mut file = File::open(path)?;
-    file.seek(SeekFrom::Start(DB_SIZE_OFFSET as u64))?;
-    file.read_exact(&mut db_size)?;
+    file.read_exact_at(&mut db_size, DB_SIZE_OFFSET as u64)?;
     Ok(u64::from_le_bytes(db_size) as usize)
 }
 
@@ -156,6 +183,25 @@ impl<'a> MetadataAccessor<'a> {
         } else {
             self.header[GOD_BYTE_OFFSET] &= !PRIMARY_BIT;
         }
+        // Read-only instances compare this before and after reading the primary slot, since it may
+        // be swapped twice while they read it. See read_primary_slot()
+        let generation = read_generation(self.header).wrapping_add(1);
+        // Safety: the offset is aligned, since the header is at the start of the mapping
+        unsafe {
+            let ptr = self.header[GENERATION_OFFSET..].as_mut_ptr() as *mut u32;
+            ptr::write_volatile(ptr, generation.to_le());
+        }
+        fence(Ordering::SeqCst);
+    }
+
+    // Set while a commit is freeing pages, until it has been made the primary. See
+    // TransactionalMemory::is_last_commit()
+    fn set_reclaiming(&mut self, reclaiming: bool) {
+        // Safety: header is a valid &mut reference
+        unsafe {
+            ptr::write_volatile(&mut self.header[RECLAIMING_OFFSET], reclaiming as u8);
+        }
+        fence(Ordering::SeqCst);
     }
 
     fn get_max_capacity(&self) -> usize {
@@ -306,6 +352,20 @@ impl<'a> TransactionAccessor<'a> {
         }
     }
 
+    fn get_snapshot_root_page(&self) -> Option<PageNumber> {
+        if self.mem[SNAPSHOT_ROOT_NON_NULL_OFFSET] == 0 {
+            None
+        } else {
+            let num = PageNumber::from_le_bytes(
+                self.mem
+                    [SNAPSHOT_ROOT_OFFSET..(SNAPSHOT_ROOT_OFFSET + PageNumber::serialized_size())]
+                    .try_into()
+                    .unwrap(),
+            );
+            Some(num)
+        }
+    }
+
     fn get_last_committed_transaction_id(&self) -> u64 {
         u64::from_le_bytes(
             self.mem[TRANSACTION_ID_OFFSET..(TRANSACTION_ID_OFFSET + size_of::<u64>())]
@@ -356,6 +416,16 @@ impl<'a> TransactionMutator<'a> {
         }
     }
 
+    fn set_snapshot_root(&mut self, page_number: Option<PageNumber>) {
+        if let Some(num) = page_number {
+            self.mem[SNAPSHOT_ROOT_OFFSET..(SNAPSHOT_ROOT_OFFSET + PageNumber::serialized_size())]
+                .copy_from_slice(&num.to_le_bytes());
+            self.mem[SNAPSHOT_ROOT_NON_NULL_OFFSET] = 1;
+        } else {
+            self.mem[SNAPSHOT_ROOT_NON_NULL_OFFSET] = 0;
+        }
+    }
+
     fn set_last_committed_transaction_id(&mut self, transaction_id: u64) {
         self.mem[TRANSACTION_ID_OFFSET..(TRANSACTION_ID_OFFSET + size_of::<u64>())]
             .copy_from_slice(&transaction_id.to_le_bytes());
@@ -371,6 +441,22 @@ impl<'a> TransactionMutator<'a> {
     }
 }
 
+// Allocation state of a region, as recorded by the region allocator and the region's own allocator
+pub(crate) struct RegionAllocationState {
+    pub(crate) marked_full: bool,
+    // Indexed by order 0 page. True if the page is free
+    pub(crate) free_pages: Vec<bool>,
+}
+
+// The primary commit slot, as last read by a read-only instance
+struct CommittedState {
+    root: Option<PageNumber>,
+    freed_root: Option<PageNumber>,
+    snapshot_root: Option<PageNumber>,
+    transaction_id: u64,
+    layout: DatabaseLayout,
+}
+
 enum AllocationOp {
     Allocate(PageNumber),
     Free(PageNumber),
@@ -380,6 +466,12 @@ enum AllocationOp {
 pub(crate) struct TransactionalMemory {
     // Pages allocated since the last commit
     allocated_since_commit: Mutex<HashSet<PageNumber>>,
+    // Pages which get_page() doesn't need to verify, because they were allocated by this instance
+    // or already verified. Lock free, since it's accessed on the get_page() code path
+    verified_pages: VerifiedPages,
+    // Pages allocated since the last commit, which are reachable from a savepoint. These are
+    // treated like committed pages: they're copied on write, and only freed once the transaction commits
+    savepoint_pages: Mutex<HashSet<PageNumber>>,
     log_since_commit: Mutex<Vec<AllocationOp>>,
     // Metadata guard lock should be held when using this to modify the page allocator state
     // May be None, if the allocator state was corrupted when the file was opened
@@ -395,6 +487,9 @@ pub(crate) struct TransactionalMemory {
     open_dirty_pages: Mutex<HashSet<PageNumber>>,
     // Indicates that a non-durable commit has been made, so reads should be served from the secondary meta page
     read_from_secondary: AtomicBool,
+    // Set if the file is opened read-only. The header is then never locked for writing, and reads
+    // are served from the state captured by the last call to pin_last_commit()
+    read_only_state: Option<Mutex<CommittedState>>,
     page_size: usize,
     // We store these separately from the layout because they're static, and accessed on the get_page()
     // code path where there is no locking
@@ -412,11 +507,14 @@ impl TransactionalMemory {
     ) -> Result<Self> {
         let page_size = requested_page_size.unwrap_or_else(get_page_size);
         assert!(page_size.is_power_of_two());
+        let max_capacity = min(max_capacity, MAX_CAPACITY);
+        // Take the file's locks before anything can fail, since dropping a file which another
+        // instance has open releases that instance's locks. See LockedFile
+        let mapping_capacity = max_capacity.clamp(DB_HEADER_SIZE, INITIAL_MAPPING_CAPACITY);
+        let mmap = Mmap::new(file, mapping_capacity)?;
         if max_capacity < DB_HEADER_SIZE + page_size * MIN_USABLE_PAGES {
             return Err(Error::OutOfSpace);
         }
-
-        let mmap = Mmap::new(file, max_capacity)?;
         if mmap.len() < DB_HEADER_SIZE {
             // Safety: We're growing the mmap
             unsafe {
@@ -479,7 +577,7 @@ impl TransactionalMemory {
             // dirtied it
             metadata.set_allocator_dirty(false);
 
-            // Store the page & db size. These are immutable
+            // Store the page & db size. The page size is immutable, but the db size may be raised later
             metadata.set_page_size(page_size);
             metadata.set_max_capacity(max_capacity);
             metadata.set_region_max_usable_bytes(max_usable_region_bytes);
@@ -487,6 +585,7 @@ impl TransactionalMemory {
             let mut mutator = metadata.secondary_slot_mut();
             mutator.set_root_page(None);
             mutator.set_freed_root(None);
+            mutator.set_snapshot_root(None);
             mutator.set_last_committed_transaction_id(0);
             mutator.set_data_section_layout(&layout);
             mutator.set_version(FILE_FORMAT_VERSION);
@@ -513,7 +612,9 @@ impl TransactionalMemory {
         }
         assert_eq!(metadata.primary_slot().get_version(), FILE_FORMAT_VERSION);
         assert_eq!(metadata.secondary_slot().get_version(), FILE_FORMAT_VERSION);
-        let layout = metadata.primary_slot().get_data_section_layout();
+        let layout = Self::raise_max_capacity(&mut metadata, max_capacity)?;
+        // Left set if the last writer crashed while committing
+        metadata.set_reclaiming(false);
         let region_size = layout.full_region_layout().len();
         let region_header_size = layout.full_region_layout().data_section().start;
 
@@ -541,6 +642,8 @@ impl TransactionalMemory {
 
         Ok(TransactionalMemory {
             allocated_since_commit: Mutex::new(HashSet::new()),
+            verified_pages: VerifiedPages::new(mmap.len() / page_size + 1),
+            savepoint_pages: Mutex::new(HashSet::new()),
             log_since_commit: Mutex::new(vec![]),
             regional_allocators: Mutex::new(regional_allocators),
             mmap,
@@ -549,6 +652,7 @@ impl TransactionalMemory {
             #[cfg(debug_assertions)]
             open_dirty_pages: Mutex::new(HashSet::new()),
             read_from_secondary: AtomicBool::new(false),
+            read_only_state: None,
             page_size,
             region_size,
             region_header_with_padding_size: region_header_size,
@@ -556,13 +660,259 @@ impl TransactionalMemory {
         })
     }
 
+    // Opens an existing database without write access. The allocator state is not loaded, since it
+    // may be modified at any time by the process which has the database open for writing
+    pub(crate) fn new_read_only(file: File) -> Result<Self> {
+        let mmap = Mmap::new_read_only(file)?;
+        if mmap.len() < DB_HEADER_SIZE {
+            return Err(Error::Corrupted("File is too short".to_string()));
+        }
+        // Safety: the mapping is read-only, so there are no mutable references to the header
+        let header = unsafe { mmap.get_memory(0..DB_HEADER_SIZE) };
+        if header[..MAGICNUMBER.len()] != MAGICNUMBER {
+            return Err(Error::Corrupted("Invalid magic number".to_string()));
+        }
+        let page_size = 1usize << header[PAGE_SIZE_OFFSET];
+
+        let mutex = Mutex::new(MetadataGuard {});
+        let state = Self::read_primary_slot(&mmap, &mutex.lock().unwrap())?;
+        let layout = state.layout.clone();
+        if mmap.len() < layout.len() {
+            return Err(Error::Corrupted("File is too short".to_string()));
+        }
+
+        Ok(TransactionalMemory {
+            allocated_since_commit: Mutex::new(HashSet::new()),
+            verified_pages: VerifiedPages::new(mmap.len() / page_size + 1),
+            savepoint_pages: Mutex::new(HashSet::new()),
+            log_since_commit: Mutex::new(vec![]),
+            regional_allocators: Mutex::new(None),
+            mmap,
+            metadata_guard: mutex,
+            layout: Mutex::new(layout.clone()),
+            #[cfg(debug_assertions)]
+            open_dirty_pages: Mutex::new(HashSet::new()),
+            read_from_secondary: AtomicBool::new(false),
+            read_only_state: Some(Mutex::new(state)),
+            page_size,
+            region_size: layout.full_region_layout().len(),
+            region_header_with_padding_size: layout.full_region_layout().data_section().start,
+            db_header_size: layout.header_bytes(),
+        })
+    }
+
+    // Reads the primary commit slot of a database which may be concurrently written by another process
+    fn read_primary_slot(mmap: &Mmap, guard: &MutexGuard<MetadataGuard>) -> Result<CommittedState> {
+        // Safety: the mapping is read-only, so there are no mutable references to the header
+        let header = unsafe { mmap.get_memory(0..DB_HEADER_SIZE) };
+        loop {
+            // The writer fills the secondary slot before making it the primary, so the slot is
+            // consistent as long as the primary wasn't swapped while we were reading it. Checking the
+            // god byte isn't enough, since it may have been swapped and then swapped back
+            let generation = read_generation(header);
+            fence(Ordering::SeqCst);
+            let god_byte = unsafe { ptr::read_volatile(&header[GOD_BYTE_OFFSET]) };
+            let start = if god_byte & PRIMARY_BIT == 0 {
+                TRANSACTION_0_OFFSET
+            } else {
+                TRANSACTION_1_OFFSET
+            };
+            let slot = TransactionAccessor::new(&header[start..(start + TRANSACTION_SIZE)], guard);
+            let version = slot.get_version();
+            let state = CommittedState {
+                root: slot.get_root_page(),
+                freed_root: slot.get_freed_root_page(),
+                snapshot_root: slot.get_snapshot_root_page(),
+                transaction_id: slot.get_last_committed_transaction_id(),
+                layout: slot.get_data_section_layout(),
+            };
+            fence(Ordering::SeqCst);
+            if read_generation(header) != generation {
+                continue;
+            }
+            if version != FILE_FORMAT_VERSION {
+                return Err(Error::Corrupted(format!(
+                    "Unsupported file format version {}",
+                    version
+                )));
+            }
+            return Ok(state);
+        }
+    }
+
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_only_state.is_some()
+    }
+
+    // Reads the last durable commit made by the writer, and pins it so that the writer won't reuse
+    // its pages until unpin() is called. Only valid for read-only instances
+    //
+    // Returns the transaction id of the commit, and the roots of its table and snapshot trees
+    pub(crate) fn pin_last_commit(&self) -> Result<(u64, Option<PageNumber>, Option<PageNumber>)> {
+        let mut guard = self.metadata_guard.lock().unwrap();
+        let state = loop {
+            let state = Self::read_primary_slot(&self.mmap, &guard)?;
+            drop(guard);
+            self.pin(state.transaction_id)?;
+            if self.is_last_commit(state.transaction_id)? {
+                break state;
+            }
+            self.unpin(state.transaction_id)?;
+            // The writer is part way through a commit
+            thread::yield_now();
+            guard = self.metadata_guard.lock().unwrap();
+        };
+
+        let guard = self.metadata_guard.lock().unwrap();
+        if state.layout.len() > self.mmap.len() {
+            // Safety: We're growing the mmap
+            unsafe {
+                self.mmap.resize(state.layout.len())?;
+            }
+        }
+        drop(guard);
+        let result = (state.transaction_id, state.root, state.snapshot_root);
+        *self.layout.lock().unwrap() = state.layout.clone();
+        let mut read_only_state = self.read_only_state.as_ref().unwrap().lock().unwrap();
+        if read_only_state.transaction_id != state.transaction_id {
+            // The writer may have reused pages which were verified as part of an earlier commit
+            self.verified_pages.clear();
+            self.verified_pages
+                .reserve(self.mmap.len() / self.page_size + 1);
+        }
+        *read_only_state = state;
+
+        Ok(result)
+    }
+
+    // Returns true if the commit with the given transaction id is still the last durable one, and
+    // every pin taken before calling this will be seen by the writer before it frees any more pages.
+    // Only valid for read-only instances
+    pub(crate) fn is_last_commit(&self, transaction_id: u64) -> Result<bool> {
+        // The writer sets the reclaiming flag before it looks for pinned commits, and clears it once
+        // the pages it freed are no longer referenced by the primary. So if the flag is clear, any
+        // pages of this commit which were freed without seeing our pins were freed by a commit which
+        // has replaced it
+        fence(Ordering::SeqCst);
+        // Safety: the mapping is read-only, so there are no mutable references to the header
+        let header = unsafe { self.mmap.get_memory(0..DB_HEADER_SIZE) };
+        let reclaiming = unsafe { ptr::read_volatile(&header[RECLAIMING_OFFSET]) } != 0;
+        // The flag is left set if the writer crashes, but then nothing is freeing pages
+        if reclaiming && self.mmap.locks().has_writer()? {
+            return Ok(false);
+        }
+        let guard = self.metadata_guard.lock().unwrap();
+        let state = Self::read_primary_slot(&self.mmap, &guard)?;
+
+        Ok(state.transaction_id == transaction_id)
+    }
+
+    // Prevents the writer from reusing the pages of the commit with the given transaction id, or of
+    // any later one, until a matching call to unpin()
+    pub(crate) fn pin(&self, transaction_id: u64) -> Result {
+        self.mmap.locks().pin(transaction_id)
+    }
+
+    pub(crate) fn unpin(&self, transaction_id: u64) -> Result {
+        self.mmap.locks().unpin(transaction_id)
+    }
+
+    // Returns the oldest commit, before `end`, which is pinned by a read-only instance in this or
+    // another process
+    pub(crate) fn oldest_pinned_transaction(&self, end: u64) -> Result<Option<u64>> {
+        self.mmap.locks().oldest_pinned(end)
+    }
+
+    // Must be set while the writer is freeing pages, until the commit which frees them has been
+    // made the primary
+    pub(crate) fn set_reclaiming(&self, reclaiming: bool) {
+        self.lock_metadata().set_reclaiming(reclaiming);
+    }
+
+    // Raises the maximum capacity of the database to the requested one. Returns the layout to use,
+    // or an error if there isn't room in the header to track the additional regions
+    fn raise_max_capacity(
+        metadata: &mut MetadataAccessor,
+        requested_capacity: usize,
+    ) -> Result<DatabaseLayout> {
+        let page_size = metadata.get_page_size();
+        let region_max_usable = metadata.get_region_max_usable_bytes();
+        let layout = metadata.primary_slot().get_data_section_layout();
+        let mut capacity = metadata.get_max_capacity();
+        let mut new_layout = layout
+            .with_capacity(capacity, region_max_usable, page_size)?
+            .unwrap();
+        if requested_capacity > capacity {
+            new_layout = layout
+                .with_capacity(requested_capacity, region_max_usable, page_size)?
+                .ok_or(Error::DbSizeNotRaisable {
+                    size: capacity,
+                    requested_size: requested_capacity,
+                })?;
+            capacity = requested_capacity;
+        }
+        // Also checks that the region allocator matches the stored capacity, in case we crashed
+        // part way through raising it
+        if capacity == metadata.get_max_capacity()
+            && new_layout.region_allocator_address_range()
+                == layout.region_allocator_address_range()
+        {
+            return Ok(layout);
+        }
+
+        // The region allocator is only a cache, so it's safe to reset it. Regions are marked full
+        // lazily. The allocator state is marked dirty first, so that it will be repaired if we
+        // crash before the new layout is committed
+        let was_dirty = metadata.get_allocator_dirty();
+        metadata.set_allocator_dirty(true);
+        metadata.mmap.flush()?;
+        let (mut region_allocator, _) = metadata.allocators_mut(&new_layout)?;
+        for i in 0..new_layout.num_regions() {
+            region_allocator.clear(i);
+        }
+        for i in new_layout.num_regions()..region_allocator.len() {
+            region_allocator.set(i);
+        }
+        drop(region_allocator);
+        metadata.set_max_capacity(capacity);
+
+        // Commit the new layout like any other transaction: copy the primary into the secondary
+        // slot, and promote it once it's durable
+        let primary = metadata.primary_slot();
+        let root = primary.get_root_page();
+        let freed_root = primary.get_freed_root_page();
+        let snapshot_root = primary.get_snapshot_root_page();
+        let transaction_id = primary.get_last_committed_transaction_id();
+        drop(primary);
+        let mut secondary = metadata.secondary_slot_mut();
+        secondary.set_root_page(root);
+        secondary.set_freed_root(freed_root);
+        secondary.set_snapshot_root(snapshot_root);
+        secondary.set_last_committed_transaction_id(transaction_id);
+        secondary.set_data_section_layout(&new_layout);
+        drop(secondary);
+        metadata.mmap.flush()?;
+        metadata.swap_primary();
+        metadata.mmap.flush()?;
+
+        // The regional allocators were not modified, so they're still valid
+        metadata.set_allocator_dirty(was_dirty);
+        metadata.mmap.flush()?;
+
+        Ok(new_layout)
+    }
+
+    pub(crate) fn get_max_capacity(&self) -> usize {
+        self.lock_metadata().get_max_capacity()
+    }
+
     pub(crate) fn needs_repair(&self) -> Result<bool> {
         Ok(self.lock_metadata().get_allocator_dirty())
     }
 
     pub(crate) fn repair_allocator(
         &self,
-        allocated_pages: impl Iterator<Item = PageNumber>,
+        allocated_pages: impl Iterator<Item = Result<PageNumber>>,
     ) -> Result<()> {
         let mut metadata = self.lock_metadata();
         let layout = self.layout.lock().unwrap();
@@ -573,8 +923,11 @@ impl TransactionalMemory {
         for i in num_regions..region_allocator.len() {
             region_allocator.set(i);
         }
-        // Since the region allocator is lazily set, we can leave it all free, and it will be
+        // Since the region allocator is lazily set, we can mark it all free, and it will be
         // populated lazily when a region is discovered to be full
+        for i in 0..num_regions {
+            region_allocator.clear(i);
+        }
 
         // Initialize all the regional allocators
         let mut regional_allocators = vec![];
@@ -590,6 +943,7 @@ impl TransactionalMemory {
         }
 
         for page_number in allocated_pages {
+            let page_number = page_number?;
             let region = page_number.region as usize;
             let mem = regions.get_regional_allocator_mut(region);
             regional_allocators[region].record_alloc(
@@ -635,6 +989,7 @@ impl TransactionalMemory {
         &self,
         data_root: Option<PageNumber>,
         freed_root: Option<PageNumber>,
+        snapshot_root: Option<PageNumber>,
         transaction_id: u64,
         eventual: bool,
     ) -> Result {
@@ -645,12 +1000,15 @@ impl TransactionalMemory {
         debug_assert!(self.open_dirty_pages.lock().unwrap().is_empty());
         assert!(self.regional_allocators.lock().unwrap().is_some());
 
+        self.write_page_checksums();
+
         let mut metadata = self.lock_metadata();
         let layout = self.layout.lock().unwrap();
         let mut secondary = metadata.secondary_slot_mut();
         secondary.set_last_committed_transaction_id(transaction_id);
         secondary.set_root_page(data_root);
         secondary.set_freed_root(freed_root);
+        secondary.set_snapshot_root(snapshot_root);
         secondary.set_data_section_layout(&layout);
 
         if eventual {
@@ -669,6 +1027,7 @@ impl TransactionalMemory {
 
         self.log_since_commit.lock().unwrap().clear();
         self.allocated_since_commit.lock().unwrap().clear();
+        self.savepoint_pages.lock().unwrap().clear();
         self.read_from_secondary.store(false, Ordering::Release);
 
         Ok(())
@@ -679,6 +1038,7 @@ impl TransactionalMemory {
         &self,
         data_root: Option<PageNumber>,
         freed_root: Option<PageNumber>,
+        snapshot_root: Option<PageNumber>,
         transaction_id: u64,
     ) -> Result {
         // All mutable pages must be dropped, this ensures that when a transaction completes
@@ -688,16 +1048,20 @@ impl TransactionalMemory {
         debug_assert!(self.open_dirty_pages.lock().unwrap().is_empty());
         assert!(self.regional_allocators.lock().unwrap().is_some());
 
+        self.write_page_checksums();
+
         let mut metadata = self.lock_metadata();
         let layout = self.layout.lock().unwrap();
         let mut secondary = metadata.secondary_slot_mut();
         secondary.set_last_committed_transaction_id(transaction_id);
         secondary.set_root_page(data_root);
         secondary.set_freed_root(freed_root);
+        secondary.set_snapshot_root(snapshot_root);
         secondary.set_data_section_layout(&layout);
 
         self.log_since_commit.lock().unwrap().clear();
         self.allocated_since_commit.lock().unwrap().clear();
+        self.savepoint_pages.lock().unwrap().clear();
         self.read_from_secondary.store(true, Ordering::Release);
 
         Ok(())
@@ -707,10 +1071,59 @@ impl TransactionalMemory {
         #[cfg(debug_assertions)]
         debug_assert!(self.open_dirty_pages.lock().unwrap().is_empty());
         let mut metadata = self.lock_metadata();
-        let regional_guard = self.regional_allocators.lock().unwrap();
         let mut layout = self.layout.lock().unwrap();
-        let (mut region_allocator, mut regions) = metadata.allocators_mut(&layout)?;
-        for op in self.log_since_commit.lock().unwrap().drain(..).rev() {
+        self.undo_log(&mut metadata, &layout, 0)?;
+        self.allocated_since_commit.lock().unwrap().clear();
+        self.savepoint_pages.lock().unwrap().clear();
+        // Reset the layout, in case it changed during the writes
+        if self.read_from_secondary.load(Ordering::Acquire) {
+            *layout = metadata.secondary_slot().get_data_section_layout();
+        } else {
+            *layout = metadata.primary_slot().get_data_section_layout();
+        }
+
+        Ok(())
+    }
+
+    // Freezes all pages allocated so far in this transaction, so that they are left intact by
+    // further writes. Returns the position in the allocation log to pass to restore_savepoint()
+    pub(crate) fn create_savepoint(&self) -> usize {
+        let allocated = self.allocated_since_commit.lock().unwrap();
+        self.savepoint_pages
+            .lock()
+            .unwrap()
+            .extend(allocated.iter().copied());
+        self.log_since_commit.lock().unwrap().len()
+    }
+
+    // Undoes all allocations and frees made after the given log position. The layout is left
+    // as is, even if it grew since the savepoint was created
+    pub(crate) fn restore_savepoint(&self, log_position: usize) -> Result {
+        #[cfg(debug_assertions)]
+        debug_assert!(self.open_dirty_pages.lock().unwrap().is_empty());
+        let mut metadata = self.lock_metadata();
+        let layout = self.layout.lock().unwrap();
+        self.undo_log(&mut metadata, &layout, log_position)
+    }
+
+    // Reverts the allocator log back to the given position, in reverse order
+    fn undo_log(
+        &self,
+        metadata: &mut MetadataAccessor,
+        layout: &DatabaseLayout,
+        log_position: usize,
+    ) -> Result {
+        let regional_guard = self.regional_allocators.lock().unwrap();
+        let (mut region_allocator, mut regions) = metadata.allocators_mut(layout)?;
+        let mut allocated = self.allocated_since_commit.lock().unwrap();
+        let mut savepoint_pages = self.savepoint_pages.lock().unwrap();
+        for op in self
+            .log_since_commit
+            .lock()
+            .unwrap()
+            .drain(log_position..)
+            .rev()
+        {
             match op {
                 AllocationOp::Allocate(page_number) => {
                     let region = page_number.region as usize;
@@ -721,6 +1134,8 @@ impl TransactionalMemory {
                         page_number.page_index as u64,
                         page_number.page_order as usize,
                     );
+                    allocated.remove(&page_number);
+                    savepoint_pages.remove(&page_number);
                 }
                 AllocationOp::Free(page_number) | AllocationOp::FreeUncommitted(page_number) => {
                     let region = page_number.region as usize;
@@ -730,21 +1145,64 @@ impl TransactionalMemory {
                         page_number.page_index as u64,
                         page_number.page_order as usize,
                     );
+                    if let AllocationOp::FreeUncommitted(_) = op {
+                        allocated.insert(page_number);
+                    }
                 }
             }
         }
-        self.allocated_since_commit.lock().unwrap().clear();
-        // Reset the layout, in case it changed during the writes
-        if self.read_from_secondary.load(Ordering::Acquire) {
-            *layout = metadata.secondary_slot().get_data_section_layout();
-        } else {
-            *layout = metadata.primary_slot().get_data_section_layout();
+
+        Ok(())
+    }
+
+    // Stores the checksum of every page allocated since the last commit. Must only be called
+    // when committing, after all PageMut have been dropped
+    fn write_page_checksums(&self) {
+        for page_number in self.allocated_since_commit.lock().unwrap().iter() {
+            // Safety: all PageMut have been dropped, and uncommitted pages are only accessible
+            // to the write transaction that is being committed
+            let mem = unsafe {
+                self.mmap
+                    .get_memory_mut(self.page_address_range(*page_number))
+            };
+            let checksum = crc32c(&mem[PAGE_HEADER_SIZE..]);
+            mem[PAGE_CHECKSUM_OFFSET..(PAGE_CHECKSUM_OFFSET + size_of::<u32>())]
+                .copy_from_slice(&checksum.to_le_bytes());
+        }
+    }
+
+    fn verify_page_checksum(page_number: PageNumber, mem: &[u8]) -> Result {
+        let expected = u32::from_le_bytes(
+            mem[PAGE_CHECKSUM_OFFSET..(PAGE_CHECKSUM_OFFSET + size_of::<u32>())]
+                .try_into()
+                .unwrap(),
+        );
+        let actual = crc32c(&mem[PAGE_HEADER_SIZE..]);
+        if expected != actual {
+            return Err(Error::Corrupted(format!(
+                "checksum mismatch on page {:?}: expected {:#010x}, found {:#010x}",
+                page_number, expected, actual
+            )));
         }
 
         Ok(())
     }
 
-    pub(crate) fn get_page(&self, page_number: PageNumber) -> PageImpl {
+    fn page_address_range(&self, page_number: PageNumber) -> Range<usize> {
+        page_number.address_range(
+            self.db_header_size,
+            self.region_size,
+            self.region_header_with_padding_size,
+            self.page_size,
+        )
+    }
+
+    // Index of the first page-sized block occupied by the page
+    fn page_block(&self, page_number: PageNumber) -> usize {
+        self.page_address_range(page_number).start / self.page_size
+    }
+
+    pub(crate) fn get_page(&self, page_number: PageNumber) -> Result<PageImpl> {
         // We must not retrieve an immutable reference to a page which already has a mutable ref to it
         #[cfg(debug_assertions)]
         debug_assert!(
@@ -754,16 +1212,19 @@ impl TransactionalMemory {
         );
 
         // Safety: we asserted that no mutable references are open
-        let mem = unsafe {
-            self.mmap.get_memory(page_number.address_range(
-                self.db_header_size,
-                self.region_size,
-                self.region_header_with_padding_size,
-                self.page_size,
-            ))
-        };
+        let mem = unsafe { self.mmap.get_memory(self.page_address_range(page_number)) };
+        // Checksums of uncommitted pages are not computed until they are committed, but those
+        // pages are always in verified_pages
+        let block = self.page_block(page_number);
+        if !self.verified_pages.contains(block) {
+            Self::verify_page_checksum(page_number, mem)?;
+            self.verified_pages.insert(block);
+        }
 
-        PageImpl { mem, page_number }
+        Ok(PageImpl {
+            mem: &mem[PAGE_HEADER_SIZE..],
+            page_number,
+        })
     }
 
     // Safety: the caller must ensure that no references to the memory in `page` exist
@@ -771,23 +1232,30 @@ impl TransactionalMemory {
         #[cfg(debug_assertions)]
         self.open_dirty_pages.lock().unwrap().insert(page_number);
 
-        let address_range = page_number.address_range(
-            self.db_header_size,
-            self.region_size,
-            self.region_header_with_padding_size,
-            self.page_size,
-        );
-        let mem = self.mmap.get_memory_mut(address_range);
+        let mem = self
+            .mmap
+            .get_memory_mut(self.page_address_range(page_number));
 
         PageMut {
-            mem,
+            mem: &mut mem[PAGE_HEADER_SIZE..],
             page_number,
             #[cfg(debug_assertions)]
             open_pages: &self.open_dirty_pages,
         }
     }
 
+    // Safety: the caller must ensure that no references to the memory in `page` exist
+    unsafe fn zero_fill(&self, page_number: PageNumber) {
+        // Zero fill the page, including its header, to ensure that deleted data is not stored in the file
+        self.mmap
+            .get_memory_mut(self.page_address_range(page_number))
+            .fill(0);
+    }
+
     pub(crate) fn get_data_root(&self) -> Option<PageNumber> {
+        if let Some(state) = self.read_only_state.as_ref() {
+            return state.lock().unwrap().root;
+        }
         let metadata = self.lock_metadata();
         if self.read_from_secondary.load(Ordering::Acquire) {
             metadata.secondary_slot().get_root_page()
@@ -797,6 +1265,9 @@ impl TransactionalMemory {
     }
 
     pub(crate) fn get_freed_root(&self) -> Option<PageNumber> {
+        if let Some(state) = self.read_only_state.as_ref() {
+            return state.lock().unwrap().freed_root;
+        }
         let metadata = self.lock_metadata();
         if self.read_from_secondary.load(Ordering::Acquire) {
             metadata.secondary_slot().get_freed_root_page()
@@ -805,7 +1276,22 @@ impl TransactionalMemory {
         }
     }
 
+    pub(crate) fn get_snapshot_root(&self) -> Option<PageNumber> {
+        if let Some(state) = self.read_only_state.as_ref() {
+            return state.lock().unwrap().snapshot_root;
+        }
+        let metadata = self.lock_metadata();
+        if self.read_from_secondary.load(Ordering::Acquire) {
+            metadata.secondary_slot().get_snapshot_root_page()
+        } else {
+            metadata.primary_slot().get_snapshot_root_page()
+        }
+    }
+
     pub(crate) fn get_last_committed_transaction_id(&self) -> Result<u64> {
+        if let Some(state) = self.read_only_state.as_ref() {
+            return Ok(state.lock().unwrap().transaction_id);
+        }
         let metadata = self.lock_metadata();
         if self.read_from_secondary.load(Ordering::Acquire) {
             Ok(metadata
@@ -818,9 +1304,11 @@ impl TransactionalMemory {
 
     // Safety: the caller must ensure that no references to the memory in `page` exist
     pub(crate) unsafe fn free(&self, page: PageNumber) -> Result {
-        // Zero fill the page to ensure that deleted data is not stored in the file
-        let mut mut_page = self.get_page_mut(page);
-        mut_page.memory_mut().fill(0);
+        self.zero_fill(page);
+        // The page may have been allocated by this transaction. Forget it, so that it's not
+        // checksummed on commit
+        self.allocated_since_commit.lock().unwrap().remove(&page);
+        self.savepoint_pages.lock().unwrap().remove(&page);
 
         let mut metadata = self.lock_metadata();
         let layout = self.layout.lock().unwrap();
@@ -846,11 +1334,11 @@ impl TransactionalMemory {
     // Frees the page if it was allocated since the last commit. Returns true, if the page was freed
     // Safety: the caller must ensure that no references to the memory in `page` exist
     pub(crate) unsafe fn free_if_uncommitted(&self, page: PageNumber) -> Result<bool> {
-        // Zero fill the page to ensure that deleted data is not stored in the file
-        let mut mut_page = self.get_page_mut(page);
-        mut_page.memory_mut().fill(0);
-
-        if self.allocated_since_commit.lock().unwrap().remove(&page) {
+        if self.uncommitted(page) {
+            self.allocated_since_commit.lock().unwrap().remove(&page);
+            // Committed pages may still be read by other transactions, or be needed after a
+            // rollback, so only zero the page if it's actually being freed
+            self.zero_fill(page);
             let mut metadata = self.lock_metadata();
             let layout = self.layout.lock().unwrap();
             let (mut region_allocator, mut regions) = metadata.allocators_mut(&layout)?;
@@ -875,9 +1363,10 @@ impl TransactionalMemory {
         }
     }
 
-    // Page has not been committed
+    // Page has not been committed, and is not part of a savepoint, so it may be modified in place
     pub(crate) fn uncommitted(&self, page: PageNumber) -> bool {
         self.allocated_since_commit.lock().unwrap().contains(&page)
+            && !self.savepoint_pages.lock().unwrap().contains(&page)
     }
 
     fn allocate_helper(
@@ -1004,8 +1493,103 @@ impl TransactionalMemory {
         Ok(())
     }
 
+    // Shrinks the layout to the smallest one that still contains every allocated page.
+    // The file itself is not truncated, since the committed layouts still refer to the old length.
+    // See truncate()
+    //
+    // Returns true if the layout was shrunk
+    pub(crate) fn shrink(&self) -> Result<bool> {
+        let mut metadata = self.lock_metadata();
+        let mut layout = self.layout.lock().unwrap();
+        let mut regional_guard = self.regional_allocators.lock().unwrap();
+        let allocators = regional_guard.as_ref().unwrap();
+
+        let mut last_allocated = (0, 0);
+        for region in (0..layout.num_regions()).rev() {
+            let mem = metadata.get_regional_allocator(region, &layout);
+            let num_pages = layout.region_layout(region).num_pages();
+            if let Some(page) =
+                (0..num_pages).rfind(|page| !allocators[region].is_free(mem, *page as u64))
+            {
+                last_allocated = (region, page);
+                break;
+            }
+        }
+        let (last_region, last_page) = last_allocated;
+
+        let max_region_size = metadata.get_region_max_usable_bytes();
+        let desired_size = max(
+            last_region * max_region_size + max(last_page + 1, MIN_USABLE_PAGES) * self.page_size,
+            MIN_DESIRED_USABLE_BYTES,
+        );
+        let new_layout = DatabaseLayout::calculate(
+            metadata.get_max_capacity(),
+            desired_size,
+            max_region_size,
+            self.page_size,
+        )?;
+        assert_eq!(new_layout.header_bytes(), layout.header_bytes());
+        if new_layout.len() >= layout.len() {
+            return Ok(false);
+        }
+        assert!(new_layout.num_regions() > last_region);
+        assert!(new_layout.region_layout(last_region).num_pages() > last_page);
+
+        let (mut region_allocator, mut regions) = metadata.allocators_mut(&new_layout)?;
+        let mut new_allocators = vec![];
+        for (i, old_allocator) in allocators.iter().enumerate().take(new_layout.num_regions()) {
+            let new_region = new_layout.region_layout(i);
+            if new_region.num_pages() == layout.region_layout(i).num_pages() {
+                new_allocators.push(old_allocator.clone());
+                continue;
+            }
+            // The buddy allocator can't be shrunk in place, so rebuild it from the allocated pages
+            let mem = regions.get_regional_allocator_mut(i);
+            let allocated: Vec<u64> = (0..new_region.num_pages() as u64)
+                .filter(|page| !old_allocator.is_free(mem, *page))
+                .collect();
+            let allocator = BuddyAllocator::init_new(
+                mem,
+                new_region.num_pages(),
+                new_layout.full_region_layout().num_pages(),
+                new_region.max_order(),
+            );
+            for page in allocated {
+                allocator.record_alloc(mem, page, 0);
+            }
+            region_allocator.clear(i);
+            new_allocators.push(allocator);
+        }
+        // Regions past the end of the layout are always marked full
+        for i in new_layout.num_regions()..region_allocator.len() {
+            region_allocator.set(i);
+        }
+
+        *regional_guard = Some(new_allocators);
+        *layout = new_layout;
+
+        Ok(true)
+    }
+
+    // Truncates the file to the length of the current layout. Returns false if it's already that
+    // length, or if another instance has the file open
+    //
+    // Safety: the current layout must have been committed to both commit slots, and the caller must
+    // ensure that no references to the truncated memory exist
+    pub(crate) unsafe fn truncate(&self) -> Result<bool> {
+        let len = self.layout.lock().unwrap().len();
+        if len >= self.mmap.len() {
+            return Ok(false);
+        }
+        // Other instances may have the file mapped, and would fault on access past its new end
+        let truncated = self.mmap.locks().with_exclusive(|| self.mmap.resize(len))?;
+
+        Ok(truncated.is_some())
+    }
+
     pub(crate) fn allocate(&self, allocation_size: usize) -> Result<PageMut> {
-        let required_pages = (allocation_size + self.page_size - 1) / self.page_size;
+        let required_pages =
+            (allocation_size + PAGE_HEADER_SIZE + self.page_size - 1) / self.page_size;
         let required_order = ceil_log2(required_pages);
 
         let mut metadata = self.lock_metadata();
@@ -1024,10 +1608,54 @@ impl TransactionalMemory {
             }
         };
 
+        let page = self.register_allocation(page_number);
+        debug_assert!(page.mem.len() >= allocation_size);
+
+        Ok(page)
+    }
+
+    // Allocates a page of the same order as the given one, but only if one is free at a lower
+    // address. The database is never grown
+    pub(crate) fn allocate_lower(&self, page: PageNumber) -> Result<Option<PageMut>> {
+        let mut metadata = self.lock_metadata();
+        let layout = self.layout.lock().unwrap();
+
+        let page_number =
+            match self.allocate_helper(&mut metadata, &layout, page.page_order as usize) {
+                Ok(page_number) => page_number,
+                Err(Error::OutOfSpace) => {
+                    return Ok(None);
+                }
+                Err(err) => {
+                    return Err(err);
+                }
+            };
+
+        if self.page_address_range(page_number).start >= self.page_address_range(page).start {
+            // Not an improvement, so return the page to the allocator
+            let (mut region_allocator, mut regions) = metadata.allocators_mut(&layout)?;
+            let mem = regions.get_regional_allocator_mut(page_number.region as usize);
+            self.regional_allocators.lock().unwrap().as_ref().unwrap()[page_number.region as usize]
+                .free(
+                    mem,
+                    page_number.page_index as u64,
+                    page_number.page_order as usize,
+                );
+            region_allocator.clear(page_number.region as usize);
+            return Ok(None);
+        }
+
+        Ok(Some(self.register_allocation(page_number)))
+    }
+
+    // Records a page which was just taken from the allocator, and returns it
+    fn register_allocation(&self, page_number: PageNumber) -> PageMut {
         self.allocated_since_commit
             .lock()
             .unwrap()
             .insert(page_number);
+        self.verified_pages
+            .insert_allocated(self.page_block(page_number));
         self.log_since_commit
             .lock()
             .unwrap()
@@ -1035,23 +1663,19 @@ impl TransactionalMemory {
         #[cfg(debug_assertions)]
         self.open_dirty_pages.lock().unwrap().insert(page_number);
 
-        let address_range = page_number.address_range(
-            self.db_header_size,
-            self.region_size,
-            self.region_header_with_padding_size,
-            self.page_size,
-        );
         // Safety:
         // The address range we're returning was just allocated, so no other references exist
-        let mem = unsafe { self.mmap.get_memory_mut(address_range) };
-        debug_assert!(mem.len() >= allocation_size);
+        let mem = unsafe {
+            self.mmap
+                .get_memory_mut(self.page_address_range(page_number))
+        };
 
-        Ok(PageMut {
-            mem,
+        PageMut {
+            mem: &mut mem[PAGE_HEADER_SIZE..],
             page_number,
             #[cfg(debug_assertions)]
             open_pages: &self.open_dirty_pages,
-        })
+        }
     }
 
     pub(crate) fn count_free_pages(&self) -> Result<usize> {
@@ -1078,9 +1702,42 @@ impl TransactionalMemory {
         Ok(count + potential_growth_pages)
     }
 
+    // Must not be called while there are uncommitted allocations, or they will be reported as
+    // allocated pages
+    pub(crate) fn allocation_state(&self) -> Vec<RegionAllocationState> {
+        let mut metadata = self.lock_metadata();
+        let regional_guard = self.regional_allocators.lock().unwrap();
+        let layout = self.layout.lock().unwrap();
+        // Safety: We own the metadata lock, and the region allocator is only read here
+        let region_allocator = U64GroupedBitMap::new(unsafe {
+            self.mmap
+                .get_memory(layout.region_allocator_address_range())
+        });
+
+        let mut result = vec![];
+        for i in 0..layout.num_regions() {
+            let mem = metadata.get_regional_allocator(i, &layout);
+            let allocator = &regional_guard.as_ref().unwrap()[i];
+            let free_pages = (0..layout.region_layout(i).num_pages())
+                .map(|page| allocator.is_free(mem, page as u64))
+                .collect();
+            result.push(RegionAllocationState {
+                marked_full: region_allocator.get(i),
+                free_pages,
+            });
+        }
+
+        result
+    }
+
     pub(crate) fn get_page_size(&self) -> usize {
         self.page_size
     }
+
+    // Number of bytes available to the caller in a page of the smallest order
+    pub(crate) fn get_usable_page_size(&self) -> usize {
+        self.page_size - PAGE_HEADER_SIZE
+    }
 }
 
 impl Drop for TransactionalMemory {
@@ -1090,8 +1747,15 @@ impl Drop for TransactionalMemory {
             if let Ok(non_durable_transaction_id) = self.get_last_committed_transaction_id() {
                 let root = self.get_data_root();
                 let freed_root = self.get_freed_root();
+                let snapshot_root = self.get_snapshot_root();
                 if self
-                    .commit(root, freed_root, non_durable_transaction_id, false)
+                    .commit(
+                        root,
+                        freed_root,
+                        snapshot_root,
+                        non_durable_transaction_id,
+                        false,
+                    )
                     .is_err()
                 {
                     eprintln!(
@@ -1127,7 +1791,7 @@ mod test {
     };
     use crate::tree_store::page_store::utils::get_page_size;
     use crate::tree_store::page_store::TransactionalMemory;
-    use crate::{Database, Error};
+    use crate::{Database, Error, ReadableTable};
     use memmap2::MmapMut;
     use std::fs::OpenOptions;
     use tempfile::NamedTempFile;
@@ -1138,7 +1802,7 @@ mod test {
     fn repair_allocator() {
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
         let max_size = 1024 * 1024;
-        let db = unsafe { Database::create(tmpfile.path(), max_size).unwrap() };
+        let db = Database::create(tmpfile.path(), max_size).unwrap();
         let write_txn = db.begin_write().unwrap();
         {
             let mut table = write_txn.open_table(X).unwrap();
@@ -1167,7 +1831,7 @@ mod test {
             .needs_repair()
             .unwrap());
 
-        let db2 = unsafe { Database::create(tmpfile.path(), max_size).unwrap() };
+        let db2 = Database::create(tmpfile.path(), max_size).unwrap();
         let write_txn = db2.begin_write().unwrap();
         assert_eq!(free_pages, write_txn.stats().unwrap().free_pages());
         {
@@ -1177,32 +1841,122 @@ mod test {
         write_txn.commit().unwrap();
     }
 
+    #[test]
+    fn repair_allocator_with_snapshot() {
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let max_size = 1024 * 1024;
+        let db = Database::create(tmpfile.path(), max_size).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(X).unwrap();
+            table.insert(b"hello", b"world").unwrap();
+        }
+        write_txn.commit().unwrap();
+        let write_txn = db.begin_write().unwrap();
+        write_txn.create_snapshot("snapshot").unwrap();
+        {
+            let mut table = write_txn.open_table(X).unwrap();
+            table.insert(b"hello", b"world2").unwrap();
+        }
+        write_txn.commit().unwrap();
+        drop(db);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(tmpfile.path())
+            .unwrap();
+        let mut mmap = unsafe { MmapMut::map_mut(&file) }.unwrap();
+        mmap[GOD_BYTE_OFFSET] |= ALLOCATOR_STATE_DIRTY;
+        mmap.flush().unwrap();
+        drop(mmap);
+        drop(file);
+
+        // The pages only referenced by the snapshot must survive the repair
+        let db2 = Database::create(tmpfile.path(), max_size).unwrap();
+        assert!(db2.check_integrity().unwrap().is_ok());
+        let read_txn = db2.begin_read_snapshot("snapshot").unwrap();
+        let table = read_txn.open_table(X).unwrap();
+        assert_eq!(b"world", table.get(b"hello").unwrap().unwrap());
+        drop(table);
+        drop(read_txn);
+
+        // and be released once it's deleted
+        let write_txn = db2.begin_write().unwrap();
+        let free_pages = write_txn.stats().unwrap().free_pages();
+        assert!(write_txn.delete_snapshot("snapshot").unwrap());
+        write_txn.commit().unwrap();
+        db2.begin_write().unwrap().commit().unwrap();
+        let write_txn = db2.begin_write().unwrap();
+        assert!(write_txn.stats().unwrap().free_pages() > free_pages);
+        write_txn.abort().unwrap();
+        assert!(db2.check_integrity().unwrap().is_ok());
+    }
+
+    #[test]
+    fn corrupted_page() {
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let max_size = 1024 * 1024;
+        let db = Database::create(tmpfile.path(), max_size).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(X).unwrap();
+            table.insert(b"hello", b"world").unwrap();
+        }
+        write_txn.commit().unwrap();
+        drop(db);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(tmpfile.path())
+            .unwrap();
+        let mem = TransactionalMemory::new(file, max_size, None, true).unwrap();
+        let root = mem.get_data_root().unwrap();
+        // Flip a bit, without updating the page's checksum
+        unsafe {
+            mem.get_page_mut(root).memory_mut()[0] ^= 1;
+        }
+        assert!(matches!(mem.get_page(root), Err(Error::Corrupted(_))));
+        unsafe {
+            mem.get_page_mut(root).memory_mut()[0] ^= 1;
+        }
+        assert!(mem.get_page(root).is_ok());
+        // Pages are only verified the first time that they're read
+        unsafe {
+            mem.get_page_mut(root).memory_mut()[0] ^= 1;
+        }
+        assert!(mem.get_page(root).is_ok());
+        drop(mem);
+
+        let db = Database::create(tmpfile.path(), max_size).unwrap();
+        let read_txn = db.begin_read().unwrap();
+        assert!(matches!(read_txn.open_table(X), Err(Error::Corrupted(_))));
+    }
+
     #[test]
     fn too_small_db() {
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        let result = unsafe { Database::create(tmpfile.path(), 1) };
+        let result = Database::create(tmpfile.path(), 1);
         assert!(matches!(result, Err(Error::OutOfSpace)));
 
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        let result = unsafe { Database::create(tmpfile.path(), 1024) };
+        let result = Database::create(tmpfile.path(), 1024);
         assert!(matches!(result, Err(Error::OutOfSpace)));
 
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        let result =
-            unsafe { Database::create(tmpfile.path(), MIN_USABLE_PAGES * get_page_size() - 1) };
+        let result = Database::create(tmpfile.path(), MIN_USABLE_PAGES * get_page_size() - 1);
         assert!(matches!(result, Err(Error::OutOfSpace)));
     }
 
     #[test]
     fn smallest_db() {
         let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-        unsafe {
-            Database::create(
-                tmpfile.path(),
-                DB_HEADER_SIZE + (MIN_USABLE_PAGES + 2) * get_page_size(),
-            )
-            .unwrap();
-        }
+        Database::create(
+            tmpfile.path(),
+            DB_HEADER_SIZE + (MIN_USABLE_PAGES + 2) * get_page_size(),
+        )
+        .unwrap();
     }
 
     #[test]
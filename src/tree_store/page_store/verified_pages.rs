@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// The set of pages whose checksum doesn't need to be verified when they're read, either because
+// it has already been verified, or because the page was allocated by this instance. A page is
+// identified by the index of the first page-sized block that it occupies in the file
+//
+// The set is read and updated without locking, since it's used on the get_page() code path.
+// When the file grows, the bitmap is replaced by a larger copy. An insert() made by another thread
+// during the copy may be lost, which only means that the page is verified again. So reserve() must
+// not race with insert_allocated(), since a lost allocation would fail verification
+pub(crate) struct VerifiedPages {
+    bits: AtomicPtr<Vec<AtomicU64>>,
+    // Bitmaps which have been replaced by a larger one. These are kept until drop, because other
+    // threads may still be using them
+    retired: Mutex<Vec<*mut Vec<AtomicU64>>>,
+}
+
+// The bitmaps are only accessed through atomics
+unsafe impl Send for VerifiedPages {}
+unsafe impl Sync for VerifiedPages {}
+
+impl VerifiedPages {
+    pub(crate) fn new(blocks: usize) -> Self {
+        Self {
+            bits: AtomicPtr::new(Box::into_raw(Box::new(Self::bitmap(blocks)))),
+            retired: Mutex::new(vec![]),
+        }
+    }
+
+    fn bitmap(blocks: usize) -> Vec<AtomicU64> {
+        (0..((blocks + 63) / 64))
+            .map(|_| AtomicU64::new(0))
+            .collect()
+    }
+
+    fn words(&self) -> &[AtomicU64] {
+        // Safety: bitmaps are never freed until drop
+        unsafe { &*self.bits.load(Ordering::Acquire) }
+    }
+
+    pub(crate) fn contains(&self, block: usize) -> bool {
+        match self.words().get(block / 64) {
+            Some(word) => word.load(Ordering::Relaxed) & (1 << (block % 64)) != 0,
+            None => false,
+        }
+    }
+
+    // Records a page whose checksum has been verified. Pages past the end of the bitmap are not
+    // recorded, and will be verified again
+    pub(crate) fn insert(&self, block: usize) {
+        if let Some(word) = self.words().get(block / 64) {
+            word.fetch_or(1 << (block % 64), Ordering::Relaxed);
+        }
+    }
+
+    // Records a page which was just allocated, and so has no valid checksum until it's committed
+    pub(crate) fn insert_allocated(&self, block: usize) {
+        self.reserve(block + 1);
+        self.insert(block);
+    }
+
+    // Grows the bitmap to hold at least the given number of blocks
+    pub(crate) fn reserve(&self, blocks: usize) {
+        let old = self.words();
+        if old.len() * 64 >= blocks {
+            return;
+        }
+        let new = Self::bitmap(blocks.max(old.len() * 64 * 2));
+        for (new_word, old_word) in new.iter().zip(old.iter()) {
+            new_word.store(old_word.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        let old = self
+            .bits
+            .swap(Box::into_raw(Box::new(new)), Ordering::AcqRel);
+        self.retired.lock().unwrap().push(old);
+    }
+
+    // Forgets every page, so that they're all verified again
+    pub(crate) fn clear(&self) {
+        for word in self.words() {
+            word.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for VerifiedPages {
+    fn drop(&mut self) {
+        let current = *self.bits.get_mut();
+        for bits in self.retired.get_mut().unwrap().drain(..).chain([current]) {
+            // Safety: every bitmap was created by Box::into_raw(), and is no longer referenced
+            drop(unsafe { Box::from_raw(bits) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tree_store::page_store::verified_pages::VerifiedPages;
+
+    #[test]
+    fn grow() {
+        let pages = VerifiedPages::new(10);
+        pages.insert(3);
+        pages.insert(100);
+        assert!(pages.contains(3));
+        assert!(!pages.contains(100));
+        pages.insert_allocated(1000);
+        assert!(pages.contains(3));
+        assert!(pages.contains(1000));
+        assert!(!pages.contains(999));
+        pages.clear();
+        assert!(!pages.contains(3));
+        assert!(!pages.contains(1000));
+    }
+}
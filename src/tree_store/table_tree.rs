@@ -4,8 +4,10 @@ use crate::types::{
     AsBytesWithLifetime, OwnedAsBytesLifetime, OwnedLifetime, RedbKey, RedbValue, WithLifetime,
 };
 use crate::{DatabaseStats, Error, Result};
+use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::cmp::max;
+use std::collections::HashMap;
 use std::mem::size_of;
 use std::ops::RangeFull;
 use std::rc::Rc;
@@ -54,6 +56,52 @@ impl RedbKey for FreedTableKey {
     }
 }
 
+// A persistent snapshot: the root of the master table as of the given transaction. Pages freed
+// after that transaction are retained for as long as the snapshot exists
+#[derive(Debug)]
+pub(crate) struct SnapshotRecord {
+    pub(crate) root: Option<PageNumber>,
+    pub(crate) transaction_id: u64,
+}
+
+impl RedbValue for SnapshotRecord {
+    type View = OwnedLifetime<SnapshotRecord>;
+    type ToBytes = OwnedAsBytesLifetime<[u8; SnapshotRecord::SERIALIZED_SIZE]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        let transaction_id = u64::from_le_bytes(data[..size_of::<u64>()].try_into().unwrap());
+        let root = if data[size_of::<u64>()] != 0 {
+            Some(PageNumber::from_le_bytes(
+                data[(size_of::<u64>() + 1)..].try_into().unwrap(),
+            ))
+        } else {
+            None
+        };
+        Self {
+            root,
+            transaction_id,
+        }
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        let mut result = [0u8; SnapshotRecord::SERIALIZED_SIZE];
+        result[..size_of::<u64>()].copy_from_slice(&self.transaction_id.to_le_bytes());
+        if let Some(root) = self.root {
+            result[size_of::<u64>()] = 1;
+            result[(size_of::<u64>() + 1)..].copy_from_slice(&root.to_le_bytes());
+        }
+        result
+    }
+
+    fn redb_type_name() -> &'static str {
+        "SnapshotRecord"
+    }
+}
+
+impl SnapshotRecord {
+    const SERIALIZED_SIZE: usize = size_of::<u64>() + 1 + PageNumber::serialized_size();
+}
+
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub(crate) enum TableType {
     Normal,
@@ -84,11 +132,51 @@ impl From<u8> for TableType {
 pub(crate) struct InternalTableDefinition {
     table_root: Option<PageNumber>,
     table_type: TableType,
+    version: u64,
     key_type: String,
     value_type: String,
 }
 
 impl InternalTableDefinition {
+    fn new<K: RedbKey + ?Sized, V: RedbValue + ?Sized>(
+        table_root: Option<PageNumber>,
+        table_type: TableType,
+        version: u64,
+    ) -> Self {
+        Self {
+            table_root,
+            table_type,
+            version,
+            key_type: K::redb_type_name().to_string(),
+            value_type: V::redb_type_name().to_string(),
+        }
+    }
+
+    // Checks that the table is of the given type, ignoring its version
+    fn check_type<K: RedbKey + ?Sized, V: RedbValue + ?Sized>(
+        &self,
+        name: &str,
+        table_type: TableType,
+    ) -> Result {
+        if self.table_type != table_type {
+            return Err(Error::TableTypeMismatch(format!(
+                "{:?} is not of type {:?}",
+                name, table_type
+            )));
+        }
+        if self.key_type != K::redb_type_name() || self.value_type != V::redb_type_name() {
+            return Err(Error::TableTypeMismatch(format!(
+                "{} is of type Table<{}, {}> not Table<{}, {}>",
+                name,
+                &self.key_type,
+                &self.value_type,
+                K::redb_type_name(),
+                V::redb_type_name()
+            )));
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_root(&self) -> Option<PageNumber> {
         self.table_root
     }
@@ -96,6 +184,10 @@ impl InternalTableDefinition {
     pub(crate) fn get_type(&self) -> TableType {
         self.table_type
     }
+
+    pub(crate) fn get_key_type(&self) -> &str {
+        &self.key_type
+    }
 }
 
 impl RedbValue for InternalTableDefinition {
@@ -103,7 +195,7 @@ impl RedbValue for InternalTableDefinition {
     type ToBytes = OwnedAsBytesLifetime<Vec<u8>>;
 
     fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
-        debug_assert!(data.len() > 14);
+        debug_assert!(data.len() > 22);
         let mut offset = 0;
         let table_type = TableType::from(data[offset]);
         offset += 1;
@@ -120,6 +212,12 @@ impl RedbValue for InternalTableDefinition {
             None
         };
         offset += PageNumber::serialized_size();
+        let version = u64::from_le_bytes(
+            data[offset..(offset + size_of::<u64>())]
+                .try_into()
+                .unwrap(),
+        );
+        offset += size_of::<u64>();
         let key_type_len = u32::from_le_bytes(
             data[offset..(offset + size_of::<u32>())]
                 .try_into()
@@ -135,6 +233,7 @@ impl RedbValue for InternalTableDefinition {
         InternalTableDefinition {
             table_root,
             table_type,
+            version,
             key_type,
             value_type,
         }
@@ -149,6 +248,7 @@ impl RedbValue for InternalTableDefinition {
             result.push(0);
             result.extend_from_slice(&[0; PageNumber::serialized_size()])
         }
+        result.extend_from_slice(&self.version.to_le_bytes());
         result.extend_from_slice(&(self.key_type.as_bytes().len() as u32).to_le_bytes());
         result.extend_from_slice(self.key_type.as_bytes());
         result.extend_from_slice(self.value_type.as_bytes());
@@ -167,12 +267,18 @@ pub struct TableNameIter<'a> {
 }
 
 impl<'a> Iterator for TableNameIter<'a> {
-    type Item = String;
+    type Item = Result<String>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(entry) = self.inner.next() {
+        for entry in self.inner.by_ref() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    return Some(Err(err));
+                }
+            };
             if InternalTableDefinition::from_bytes(entry.value()).table_type == self.table_type {
-                return Some(str::from_bytes(entry.key()).to_string());
+                return Some(Ok(str::from_bytes(entry.key()).to_string()));
             }
         }
         None
@@ -183,6 +289,8 @@ pub(crate) struct TableTree<'txn> {
     tree: BtreeMut<'txn, str, InternalTableDefinition>,
     mem: &'txn TransactionalMemory,
     freed_pages: Rc<RefCell<Vec<PageNumber>>>,
+    // Roots of tables which were closed in this transaction, but not yet written to the master table
+    pending_table_updates: HashMap<String, Option<PageNumber>>,
 }
 
 impl<'txn> TableTree<'txn> {
@@ -195,6 +303,7 @@ impl<'txn> TableTree<'txn> {
             tree: BtreeMut::new(master_root, mem, freed_pages.clone()),
             mem,
             freed_pages,
+            pending_table_updates: Default::default(),
         }
     }
 
@@ -202,6 +311,31 @@ impl<'txn> TableTree<'txn> {
         self.tree.get_root()
     }
 
+    pub(crate) fn pending_table_updates(&self) -> &HashMap<String, Option<PageNumber>> {
+        &self.pending_table_updates
+    }
+
+    pub(crate) fn set_pending_table_updates(
+        &mut self,
+        updates: HashMap<String, Option<PageNumber>>,
+    ) {
+        self.pending_table_updates = updates;
+    }
+
+    // Records the new root of a table. It's visible to get_table(), but is only written to the
+    // master table by flush_table_root_updates()
+    pub(crate) fn stage_update_table_root(&mut self, name: &str, table_root: Option<PageNumber>) {
+        self.pending_table_updates
+            .insert(name.to_string(), table_root);
+    }
+
+    pub(crate) fn flush_table_root_updates(&mut self) -> Result {
+        for (name, root) in std::mem::take(&mut self.pending_table_updates) {
+            self.update_table_root(&name, root)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn update_table_root(
         &mut self,
         name: &str,
@@ -229,7 +363,19 @@ impl<'txn> TableTree<'txn> {
             inner: iter,
             table_type,
         };
-        Ok(iter.collect())
+        iter.collect()
+    }
+
+    // Returns the definition of the table, whatever its type
+    fn get_definition(&self, name: &str) -> Result<Option<InternalTableDefinition>> {
+        if let Some(mut definition) = self.tree.get(name)? {
+            if let Some(root) = self.pending_table_updates.get(name) {
+                definition.table_root = *root;
+            }
+            Ok(Some(definition))
+        } else {
+            Ok(None)
+        }
     }
 
     // root_page: the root of the master table
@@ -237,24 +383,14 @@ impl<'txn> TableTree<'txn> {
         &self,
         name: &str,
         table_type: TableType,
+        version: u64,
     ) -> Result<Option<InternalTableDefinition>> {
-        if let Some(definition) = self.tree.get(name)? {
-            if definition.get_type() != table_type {
+        if let Some(definition) = self.get_definition(name)? {
+            definition.check_type::<K, V>(name, table_type)?;
+            if definition.version != version {
                 return Err(Error::TableTypeMismatch(format!(
-                    "{:?} is not of type {:?}",
-                    name, table_type
-                )));
-            }
-            if definition.key_type != K::redb_type_name()
-                || definition.value_type != V::redb_type_name()
-            {
-                return Err(Error::TableTypeMismatch(format!(
-                    "{} is of type Table<{}, {}> not Table<{}, {}>",
-                    name,
-                    &definition.key_type,
-                    &definition.value_type,
-                    K::redb_type_name(),
-                    V::redb_type_name()
+                    "{} is version {} not {}",
+                    name, definition.version, version
                 )));
             }
 
@@ -264,20 +400,27 @@ impl<'txn> TableTree<'txn> {
         }
     }
 
+    fn free_tree(&self, table_root: Option<PageNumber>) -> Result {
+        if let Some(table_root) = table_root {
+            let iter = AllPageNumbersBtreeIter::new(table_root, self.mem)?;
+            let mut freed_pages = self.freed_pages.borrow_mut();
+            for page_number in iter {
+                freed_pages.push(page_number?);
+            }
+        }
+        Ok(())
+    }
+
     // root_page: the root of the master table
     pub(crate) fn delete_table<K: RedbKey + ?Sized, V: RedbValue + ?Sized>(
         &mut self,
         name: &str,
         table_type: TableType,
+        version: u64,
     ) -> Result<bool> {
-        if let Some(definition) = self.get_table::<K, V>(name, table_type)? {
-            if let Some(table_root) = definition.get_root() {
-                let iter = AllPageNumbersBtreeIter::new(table_root, self.mem);
-                let mut freed_pages = self.freed_pages.borrow_mut();
-                for page_number in iter {
-                    freed_pages.push(page_number);
-                }
-            }
+        if let Some(definition) = self.get_table::<K, V>(name, table_type, version)? {
+            self.pending_table_updates.remove(name);
+            self.free_tree(definition.get_root())?;
 
             // Safety: References into the master table are never returned to the user
             let found = unsafe { self.tree.remove(name)?.is_some() };
@@ -293,24 +436,139 @@ impl<'txn> TableTree<'txn> {
         &mut self,
         name: &str,
         table_type: TableType,
+        version: u64,
     ) -> Result<InternalTableDefinition> {
-        if let Some(found) = self.get_table::<K, V>(name, table_type)? {
+        if let Some(found) = self.get_table::<K, V>(name, table_type, version)? {
             return Ok(found);
         }
 
-        let table = InternalTableDefinition {
-            table_root: None,
-            table_type,
-            key_type: K::redb_type_name().to_string(),
-            value_type: V::redb_type_name().to_string(),
-        };
+        let table = InternalTableDefinition::new::<K, V>(None, table_type, version);
         // Safety: References into the master table are never returned to the user
         unsafe { self.tree.insert(name, &table)? };
         Ok(table)
     }
 
+    // Rewrites the table, of types K1 & V1 and any version, into a table of types K2 & V2 at the
+    // given version, by passing each entry through f. Returns false, without calling f, if the
+    // table does not exist or is already of the new types and version
+    // TODO: Simplify this when GATs are stable
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn migrate_table<K1, V1, K2, V2, F, KB, VB>(
+        &mut self,
+        name: &str,
+        version: u64,
+        mut f: F,
+    ) -> Result<bool>
+    where
+        K1: RedbKey + ?Sized,
+        V1: RedbValue + ?Sized,
+        K2: RedbKey + ?Sized,
+        V2: RedbValue + ?Sized,
+        F: for<'f> FnMut(
+            <<K1 as RedbValue>::View as WithLifetime<'f>>::Out,
+            <<V1 as RedbValue>::View as WithLifetime<'f>>::Out,
+        ) -> (KB, VB),
+        KB: Borrow<K2>,
+        VB: Borrow<V2>,
+    {
+        let definition = if let Some(definition) = self.get_definition(name)? {
+            definition
+        } else {
+            return Ok(false);
+        };
+        if definition.version == version
+            && definition
+                .check_type::<K2, V2>(name, TableType::Normal)
+                .is_ok()
+        {
+            return Ok(false);
+        }
+        definition.check_type::<K1, V1>(name, TableType::Normal)?;
+
+        let old_tree: Btree<K1, V1> = Btree::new(definition.get_root(), self.mem);
+        let mut new_tree: BtreeMut<K2, V2> =
+            BtreeMut::new(None, self.mem, self.freed_pages.clone());
+        let mut copy = || -> Result {
+            for entry in old_tree.range::<RangeFull, &K1>(..)? {
+                let entry = entry?;
+                let (key, value) = f(
+                    K1::try_from_bytes(entry.key())?,
+                    V1::try_from_bytes(entry.value())?,
+                );
+                // Safety: the new tree is not visible to the user, until the migration completes
+                if unsafe { new_tree.insert(key.borrow(), value.borrow())? }.is_some() {
+                    return Err(Error::DuplicateKey(name.to_string()));
+                }
+            }
+            Ok(())
+        };
+        if let Err(err) = copy() {
+            self.free_tree(new_tree.get_root())?;
+            return Err(err);
+        }
+
+        self.free_tree(definition.get_root())?;
+        self.pending_table_updates.remove(name);
+        let table =
+            InternalTableDefinition::new::<K2, V2>(new_tree.get_root(), TableType::Normal, version);
+        // Safety: References into the master table are never returned to the user
+        unsafe { self.tree.insert(name, &table)? };
+        Ok(true)
+    }
+
+    // Copies every table into `destination`, which must belong to a database with the same page
+    // size. The copies are allocated by the destination's write transaction
+    pub(crate) fn copy_to(&self, destination: &mut TableTree) -> Result {
+        let iter = self.tree.range::<RangeFull, &str>(..)?;
+        for entry in iter {
+            let entry = entry?;
+            let mut definition = InternalTableDefinition::from_bytes(entry.value());
+            let tree: Btree<[u8], [u8]> = Btree::new(definition.table_root, self.mem);
+            definition.table_root = tree.copy_to(destination.mem)?;
+            // Safety: References into the master table are never returned to the user
+            unsafe {
+                destination
+                    .tree
+                    .insert(str::from_bytes(entry.key()), &definition)?
+            };
+        }
+
+        Ok(())
+    }
+
+    // Moves the pages of every table, and of the master table, toward the start of the file.
+    // Returns true if any page was moved
+    pub(crate) fn relocate(&mut self) -> Result<bool> {
+        self.flush_table_root_updates()?;
+        let mut tables = vec![];
+        let mut iter = self.tree.range::<RangeFull, &str>(..)?;
+        for entry in iter.by_ref() {
+            let entry = entry?;
+            let definition = InternalTableDefinition::from_bytes(entry.value());
+            if let Some(root) = definition.get_root() {
+                tables.push((str::from_bytes(entry.key()).to_string(), root));
+            }
+        }
+        drop(iter);
+
+        let mut moved = false;
+        for (name, root) in tables {
+            let mut tree: BtreeMut<[u8], [u8]> =
+                BtreeMut::new(Some(root), self.mem, self.freed_pages.clone());
+            // Safety: no tables are open while pages are being relocated
+            if unsafe { tree.relocate()? } {
+                self.update_table_root(&name, tree.get_root())?;
+                moved = true;
+            }
+        }
+        // Safety: References into the master table are never returned to the user
+        moved |= unsafe { self.tree.relocate()? };
+
+        Ok(moved)
+    }
+
     pub fn stats(&self) -> Result<DatabaseStats> {
-        let master_tree_stats = self.tree.stats();
+        let master_tree_stats = self.tree.stats()?;
         let mut max_subtree_height = 0;
         let mut total_stored_bytes = 0;
         // Count the master tree leaf pages as branches, since they point to the data trees
@@ -321,11 +579,11 @@ impl<'txn> TableTree<'txn> {
             master_tree_stats.metadata_bytes + master_tree_stats.stored_leaf_bytes;
         let mut total_fragmented = master_tree_stats.fragmented_bytes;
 
-        let mut iter = self.tree.range::<RangeFull, &str>(..)?;
-        while let Some(entry) = iter.next() {
-            let definition = InternalTableDefinition::from_bytes(entry.value());
+        let iter = self.tree.range::<RangeFull, &str>(..)?;
+        for entry in iter {
+            let definition = InternalTableDefinition::from_bytes(entry?.value());
             let subtree: Btree<[u8], [u8]> = Btree::new(definition.get_root(), self.mem);
-            let subtree_stats = subtree.stats();
+            let subtree_stats = subtree.stats()?;
             max_subtree_height = max(max_subtree_height, subtree_stats.tree_height);
             total_stored_bytes += subtree_stats.stored_leaf_bytes;
             total_metadata_bytes += subtree_stats.metadata_bytes;
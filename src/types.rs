@@ -1,7 +1,13 @@
+use crate::{Error, Result};
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
 use std::convert::TryInto;
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering as AtomicOrdering};
+use std::sync::{Mutex, Once, PoisonError};
 
 pub trait AsBytesWithLifetime<'a> {
     type Out: AsRef<[u8]>;
@@ -47,18 +53,179 @@ pub trait RedbValue {
     /// ensure this method is safe, and not assume it contains the bytes returned by as_bytes()
     fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out;
 
+    /// Deserializes data, returning `Error::Corrupted` if it is not valid for this type
+    ///
+    /// This is used in place of from_bytes() when reading from a table, so that a corrupted or
+    /// mistyped table returns an error rather than panicking. The default implementation calls
+    /// from_bytes(), and should be overridden by implementations which validate `data`
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        Ok(Self::from_bytes(data))
+    }
+
     /// Serialize the key to a slice
     fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out;
 
+    /// Width of the serialized data, in bytes, if every value of this type serializes to the same
+    /// number of bytes
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    /// Alignment, in bytes, of the data passed to from_bytes(). At most 8
+    ///
+    /// Values are only guaranteed to be aligned if the length of every serialized value is a
+    /// multiple of the alignment, and this is not guaranteed for keys
+    fn alignment() -> usize {
+        1
+    }
+
     /// Globally unique identifier for this type
     fn redb_type_name() -> &'static str;
 }
 
+/// A plain old data type, which is stored as its in-memory representation and read back as a
+/// reference into the database, without being copied
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` or `#[repr(transparent)]`, contain no padding bytes, pointers
+/// or references, and be valid for any bit pattern. Their alignment must be at most 8 bytes.
+/// Note that the in-memory representation depends on the endianness of the platform.
+///
+/// Only the values of a [`Table`](crate::Table) are stored aligned, so `Pod` types cannot be used
+/// as keys or as the values of a multimap table. They can be used inside an `Option` or a tuple,
+/// which pad their contents to keep them aligned.
+///
+/// ```rust
+/// use redb::{Database, Error, Pod, ReadableTable, TableDefinition};
+/// # use tempfile::NamedTempFile;
+///
+/// #[repr(C)]
+/// #[derive(Debug, Clone, Copy)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// unsafe impl Pod for Point {
+///     const TYPE_NAME: &'static str = "Point";
+/// }
+///
+/// const TABLE: TableDefinition<str, Point> = TableDefinition::new("points");
+///
+/// # fn main() -> Result<(), Error> {
+/// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+/// # let filename = tmpfile.path();
+/// let db = Database::create(filename, 1024 * 1024)?;
+/// let write_txn = db.begin_write()?;
+/// {
+///     let mut table = write_txn.open_table(TABLE)?;
+///     table.insert("origin", &Point { x: 0.0, y: 0.0 })?;
+/// }
+/// write_txn.commit()?;
+///
+/// let read_txn = db.begin_read()?;
+/// let table = read_txn.open_table(TABLE)?;
+/// let origin: &Point = table.get("origin")?.unwrap();
+/// assert_eq!(0.0, origin.x);
+/// # Ok(())
+/// # }
+/// ```
+pub unsafe trait Pod: Copy + Debug + 'static {
+    /// Globally unique identifier for this type
+    const TYPE_NAME: &'static str;
+}
+
+impl<T: Pod> RedbValue for T {
+    type View = RefLifetime<T>;
+    type ToBytes = RefAsBytesLifetime<[u8]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        if data.len() != size_of::<T>() {
+            return Err(invalid_value::<T>(data));
+        }
+        if data.as_ptr() as usize % align_of::<T>() != 0 {
+            return Err(Error::Corrupted(format!(
+                "{} data is not aligned to {} bytes",
+                T::TYPE_NAME,
+                align_of::<T>()
+            )));
+        }
+        // Safety: data is large enough and aligned for T, and T is valid for any bit pattern
+        Ok(unsafe { &*(data.as_ptr() as *const T) })
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        // Safety: T contains no padding bytes, so all of its bytes are initialized
+        unsafe { std::slice::from_raw_parts(self as *const T as *const u8, size_of::<T>()) }
+    }
+
+    fn fixed_width() -> Option<usize> {
+        Some(size_of::<T>())
+    }
+
+    fn alignment() -> usize {
+        align_of::<T>()
+    }
+
+    fn redb_type_name() -> &'static str {
+        T::TYPE_NAME
+    }
+}
+
+impl<T: Pod> FixedWidth for T {
+    fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() != size_of::<T>() {
+            return Err(invalid_value::<T>(data));
+        }
+        // Safety: data is large enough for T, and T is valid for any bit pattern
+        Ok(unsafe { ptr::read_unaligned(data.as_ptr() as *const T) })
+    }
+}
+
 pub trait RedbKey: RedbValue {
     /// Compare data1 with data2
     fn compare(data1: &[u8], data2: &[u8]) -> Ordering;
+
+    /// Compare data1 with data2, returning `Error::Corrupted` if either is not valid for this type
+    ///
+    /// This is used in place of compare() when searching a table, so that a corrupted key returns
+    /// an error rather than panicking. The default implementation calls compare(), and should be
+    /// overridden by implementations which deserialize their input to compare it
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        Ok(Self::compare(data1, data2))
+    }
 }
 
+/// A type whose values all serialize to the same number of bytes, so that
+/// [`RedbValue::fixed_width`] returns `Some`
+///
+/// Every field of a struct which derives `RedbValue` must implement this, so that variable-width
+/// fields are rejected at compile time:
+///
+/// ```rust,compile_fail
+/// #[derive(Debug, redb::RedbValue)]
+/// struct User {
+///     id: u64,
+///     name: String,
+/// }
+/// ```
+pub trait FixedWidth: RedbValue + Sized {
+    /// Deserializes an owned value from data which may not be aligned, returning
+    /// `Error::Corrupted` if it is not valid for this type
+    fn from_unaligned_bytes(data: &[u8]) -> Result<Self>;
+}
+
+/// A key type which is ordered lexicographically by its serialized bytes
+///
+/// Required by [`ReadableTable::prefix`](crate::ReadableTable::prefix), since it finds the keys
+/// with a given prefix by comparing their serialized bytes
+pub trait ByteOrderedKey: RedbKey {}
+
 impl RedbValue for [u8] {
     type View = RefLifetime<[u8]>;
     type ToBytes = RefAsBytesLifetime<[u8]>;
@@ -82,12 +249,18 @@ impl RedbKey for [u8] {
     }
 }
 
+impl ByteOrderedKey for [u8] {}
+
 impl RedbValue for str {
     type View = RefLifetime<str>;
     type ToBytes = RefAsBytesLifetime<str>;
 
     fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
-        std::str::from_utf8(data).unwrap()
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        std::str::from_utf8(data).map_err(|_| invalid_value::<str>(data))
     }
 
     fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
@@ -101,12 +274,18 @@ impl RedbValue for str {
 
 impl RedbKey for str {
     fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
-        let str1 = str::from_bytes(data1);
-        let str2 = str::from_bytes(data2);
-        str1.cmp(str2)
+        Self::try_compare(data1, data2).unwrap()
+    }
+
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        let str1 = str::try_from_bytes(data1)?;
+        let str2 = str::try_from_bytes(data2)?;
+        Ok(str1.cmp(str2))
     }
 }
 
+impl ByteOrderedKey for str {}
+
 macro_rules! be_value {
     ($t:ty) => {
         impl RedbValue for $t {
@@ -114,17 +293,33 @@ macro_rules! be_value {
             type ToBytes = OwnedAsBytesLifetime<[u8; std::mem::size_of::<$t>()]>;
 
             fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
-                <$t>::from_le_bytes(data.try_into().unwrap())
+                Self::try_from_bytes(data).unwrap()
+            }
+
+            fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+                data.try_into()
+                    .map(<$t>::from_le_bytes)
+                    .map_err(|_| invalid_value::<$t>(data))
             }
 
             fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
                 self.to_le_bytes()
             }
 
+            fn fixed_width() -> Option<usize> {
+                Some(size_of::<$t>())
+            }
+
             fn redb_type_name() -> &'static str {
                 stringify!($t)
             }
         }
+
+        impl FixedWidth for $t {
+            fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+                Self::try_from_bytes(data)
+            }
+        }
     };
 }
 
@@ -134,7 +329,11 @@ macro_rules! be_impl {
 
         impl RedbKey for $t {
             fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
-                Self::from_bytes(data1).cmp(&Self::from_bytes(data2))
+                Self::try_compare(data1, data2).unwrap()
+            }
+
+            fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+                Ok(Self::try_from_bytes(data1)?.cmp(&Self::try_from_bytes(data2)?))
             }
         }
     };
@@ -152,3 +351,590 @@ be_impl!(i64);
 be_impl!(i128);
 be_value!(f32);
 be_value!(f64);
+
+macro_rules! float_key {
+    ($t:ty, $bits:ty) => {
+        impl RedbKey for $t {
+            // Orders by the IEEE 754 totalOrder predicate, so that NaNs and signed zeros have a
+            // well defined position. Flipping the bits of negative values, and setting the sign bit
+            // of positive ones, makes their unsigned integer order match it
+            fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+                Self::try_compare(data1, data2).unwrap()
+            }
+
+            fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+                let total_order = |value: $t| {
+                    let bits = value.to_bits();
+                    let sign = 1 << (size_of::<$bits>() * 8 - 1);
+                    if bits & sign == 0 {
+                        bits | sign
+                    } else {
+                        !bits
+                    }
+                };
+                let order1 = total_order(Self::try_from_bytes(data1)?);
+                let order2 = total_order(Self::try_from_bytes(data2)?);
+                Ok(order1.cmp(&order2))
+            }
+        }
+    };
+}
+
+float_key!(f32, u32);
+float_key!(f64, u64);
+
+impl RedbValue for bool {
+    type View = OwnedLifetime<bool>;
+    type ToBytes = OwnedAsBytesLifetime<[u8; 1]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        match data {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(invalid_value::<bool>(data)),
+        }
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        [u8::from(*self)]
+    }
+
+    fn fixed_width() -> Option<usize> {
+        Some(1)
+    }
+
+    fn redb_type_name() -> &'static str {
+        "bool"
+    }
+}
+
+impl RedbKey for bool {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::try_compare(data1, data2).unwrap()
+    }
+
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        Ok(Self::try_from_bytes(data1)?.cmp(&Self::try_from_bytes(data2)?))
+    }
+}
+
+impl FixedWidth for bool {
+    fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+        Self::try_from_bytes(data)
+    }
+}
+
+impl RedbValue for char {
+    type View = OwnedLifetime<char>;
+    type ToBytes = OwnedAsBytesLifetime<[u8; 4]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        data.try_into()
+            .ok()
+            .and_then(|bytes| char::from_u32(u32::from_le_bytes(bytes)))
+            .ok_or_else(|| invalid_value::<char>(data))
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        u32::from(*self).to_le_bytes()
+    }
+
+    fn fixed_width() -> Option<usize> {
+        Some(4)
+    }
+
+    fn redb_type_name() -> &'static str {
+        "char"
+    }
+}
+
+impl RedbKey for char {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::try_compare(data1, data2).unwrap()
+    }
+
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        Ok(Self::try_from_bytes(data1)?.cmp(&Self::try_from_bytes(data2)?))
+    }
+}
+
+impl FixedWidth for char {
+    fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+        Self::try_from_bytes(data)
+    }
+}
+
+impl RedbValue for () {
+    type View = OwnedLifetime<()>;
+    type ToBytes = OwnedAsBytesLifetime<[u8; 0]>;
+
+    fn from_bytes(_data: &[u8]) -> <Self::View as WithLifetime>::Out {}
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        if data.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_value::<()>(data))
+        }
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        []
+    }
+
+    fn fixed_width() -> Option<usize> {
+        Some(0)
+    }
+
+    fn redb_type_name() -> &'static str {
+        "()"
+    }
+}
+
+impl RedbKey for () {
+    fn compare(_data1: &[u8], _data2: &[u8]) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl FixedWidth for () {
+    fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+        Self::try_from_bytes(data)
+    }
+}
+
+impl<const N: usize> RedbValue for [u8; N] {
+    type View = RefLifetime<[u8; N]>;
+    type ToBytes = RefAsBytesLifetime<[u8; N]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        data.try_into().map_err(|_| invalid_value::<[u8; N]>(data))
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        self
+    }
+
+    fn fixed_width() -> Option<usize> {
+        Some(N)
+    }
+
+    fn redb_type_name() -> &'static str {
+        intern_type_name(format!("[u8;{}]", N))
+    }
+}
+
+impl<const N: usize> RedbKey for [u8; N] {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
+impl<const N: usize> FixedWidth for [u8; N] {
+    fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+        Ok(*Self::try_from_bytes(data)?)
+    }
+}
+
+impl<const N: usize> ByteOrderedKey for [u8; N] {}
+
+// Owned types are stored in the same format as the types they own, but read back as views of
+// those types, so that reads don't have to copy
+impl RedbValue for Vec<u8> {
+    type View = RefLifetime<[u8]>;
+    type ToBytes = RefAsBytesLifetime<[u8]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        data
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        self
+    }
+
+    fn redb_type_name() -> &'static str {
+        "Vec<u8>"
+    }
+}
+
+impl RedbKey for Vec<u8> {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        <[u8]>::compare(data1, data2)
+    }
+}
+
+impl ByteOrderedKey for Vec<u8> {}
+
+impl RedbValue for String {
+    type View = RefLifetime<str>;
+    type ToBytes = RefAsBytesLifetime<str>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        str::from_bytes(data)
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        str::try_from_bytes(data)
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        self
+    }
+
+    fn redb_type_name() -> &'static str {
+        "String"
+    }
+}
+
+impl RedbKey for String {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        str::compare(data1, data2)
+    }
+
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        str::try_compare(data1, data2)
+    }
+}
+
+impl ByteOrderedKey for String {}
+
+impl<'a, T: WithLifetime<'a>> WithLifetime<'a> for Option<T> {
+    type Out = Option<T::Out>;
+}
+
+// Stored as a tag byte, which is zero for None, followed by the value. The tag is padded to the
+// alignment of the value, so that the value stays aligned
+impl<T: RedbValue> RedbValue for Option<T> {
+    type View = Option<T::View>;
+    type ToBytes = OwnedAsBytesLifetime<Vec<u8>>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+        let header = T::alignment();
+        match data.first() {
+            Some(0) if data.len() == header => Ok(None),
+            Some(1) if data.len() >= header => Ok(Some(T::try_from_bytes(&data[header..])?)),
+            _ => Err(invalid_value::<Self>(data)),
+        }
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        let mut output = vec![0; T::alignment()];
+        if let Some(value) = self {
+            output[0] = 1;
+            output.extend_from_slice(value.as_bytes().as_ref());
+        }
+        output
+    }
+
+    fn alignment() -> usize {
+        T::alignment()
+    }
+
+    fn redb_type_name() -> &'static str {
+        intern_type_name(format!("Option<{}>", T::redb_type_name()))
+    }
+}
+
+impl<T: RedbKey> RedbKey for Option<T> {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::try_compare(data1, data2).unwrap()
+    }
+
+    // None is ordered before all other values
+    fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+        let header = T::alignment();
+        let is_some = |data: &[u8]| match data.first() {
+            Some(0) if data.len() == header => Ok(false),
+            Some(1) if data.len() >= header => Ok(true),
+            _ => Err(invalid_value::<Self>(data)),
+        };
+        match (is_some(data1)?, is_some(data2)?) {
+            (false, false) => Ok(Ordering::Equal),
+            (false, true) => Ok(Ordering::Less),
+            (true, false) => Ok(Ordering::Greater),
+            (true, true) => T::try_compare(&data1[header..], &data2[header..]),
+        }
+    }
+}
+
+fn invalid_value<T: RedbValue + ?Sized>(data: &[u8]) -> Error {
+    Error::Corrupted(format!(
+        "{} bytes are not a valid {}",
+        data.len(),
+        T::redb_type_name()
+    ))
+}
+
+// The type names of generic types, such as tuples, are built at runtime, so they have to be leaked to
+// give them a static lifetime. Names are interned by a single set shared by all threads, so that
+// each one is only leaked once
+pub(crate) fn intern_type_name(name: String) -> &'static str {
+    static INIT: Once = Once::new();
+    static NAMES: AtomicPtr<Mutex<BTreeSet<&'static str>>> = AtomicPtr::new(ptr::null_mut());
+    INIT.call_once(|| {
+        let names = Box::new(Mutex::new(BTreeSet::new()));
+        NAMES.store(Box::into_raw(names), AtomicOrdering::Release);
+    });
+    // Safety: the set is initialized above, and never freed
+    let names = unsafe { &*NAMES.load(AtomicOrdering::Acquire) };
+    // The set is never left inconsistent, so it's still usable if another thread panicked
+    let mut names = names.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(interned) = names.get(name.as_str()) {
+        interned
+    } else {
+        let interned: &'static str = Box::leak(name.into_boxed_str());
+        names.insert(interned);
+        interned
+    }
+}
+
+fn align_up(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+// Every element of a tuple, except the last, is stored prefixed with its length as a u32.
+// If any element has an alignment greater than 1, then the last element is also prefixed with its
+// length, each element is padded to its own alignment, and the tuple is padded to the largest
+// alignment of its elements, so that the elements of an aligned tuple are themselves aligned
+struct TupleLayout {
+    alignment: usize,
+    offset: usize,
+}
+
+impl TupleLayout {
+    fn new(alignment: usize) -> Self {
+        Self {
+            alignment,
+            offset: 0,
+        }
+    }
+
+    // Returns the offset of the next element, which is prefixed with its length
+    fn element(&mut self, len: usize, alignment: usize) -> usize {
+        let start = align_up(self.offset + size_of::<u32>(), alignment);
+        self.offset = start + len;
+        start
+    }
+
+    // Returns the offset of the last element
+    fn last(&mut self, len: usize, alignment: usize) -> usize {
+        if self.alignment == 1 {
+            let start = self.offset;
+            self.offset += len;
+            start
+        } else {
+            let start = self.element(len, alignment);
+            self.offset = align_up(self.offset, self.alignment);
+            start
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.offset
+    }
+}
+
+struct TupleEncoder {
+    layout: TupleLayout,
+    output: Vec<u8>,
+}
+
+impl TupleEncoder {
+    fn new(alignment: usize) -> Self {
+        Self {
+            layout: TupleLayout::new(alignment),
+            output: vec![],
+        }
+    }
+
+    fn element(&mut self, element: &[u8], alignment: usize) {
+        let start = self.layout.element(element.len(), alignment);
+        self.output
+            .extend_from_slice(&(element.len() as u32).to_le_bytes());
+        self.output.resize(start, 0);
+        self.output.extend_from_slice(element);
+    }
+
+    fn last(mut self, element: &[u8], alignment: usize) -> Vec<u8> {
+        let start = self.layout.last(element.len(), alignment);
+        if self.layout.alignment != 1 {
+            self.output
+                .extend_from_slice(&(element.len() as u32).to_le_bytes());
+            self.output.resize(start, 0);
+        }
+        self.output.extend_from_slice(element);
+        self.output.resize(self.layout.len(), 0);
+        self.output
+    }
+}
+
+struct TupleElements<'a> {
+    data: &'a [u8],
+    layout: TupleLayout,
+}
+
+impl<'a> TupleElements<'a> {
+    fn new(data: &'a [u8], alignment: usize) -> Self {
+        Self {
+            data,
+            layout: TupleLayout::new(alignment),
+        }
+    }
+
+    // Returns None if the data is too short to contain the next element
+    fn next(&mut self, alignment: usize) -> Option<&'a [u8]> {
+        let offset = self.layout.len();
+        let len = self.data.get(offset..(offset + size_of::<u32>()))?;
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let start = self.layout.element(len, alignment);
+        self.data.get(start..(start + len))
+    }
+
+    // Returns None if the remaining data is not a valid last element
+    fn last(mut self, alignment: usize) -> Option<&'a [u8]> {
+        if self.layout.alignment == 1 {
+            return self.data.get(self.layout.len()..);
+        }
+        let element = self.next(alignment)?;
+        if align_up(self.layout.len(), self.layout.alignment) == self.data.len() {
+            Some(element)
+        } else {
+            None
+        }
+    }
+}
+
+macro_rules! tuple_impl {
+    ($($name:ident $index:tt),+; $last:ident $last_index:tt) => {
+        impl<'a, $($name: WithLifetime<'a>,)+ $last: WithLifetime<'a>> WithLifetime<'a>
+            for ($($name,)+ $last)
+        {
+            type Out = ($($name::Out,)+ $last::Out);
+        }
+
+        impl<$($name: RedbValue,)+ $last: RedbValue> RedbValue for ($($name,)+ $last) {
+            type View = ($($name::View,)+ $last::View);
+            type ToBytes = OwnedAsBytesLifetime<Vec<u8>>;
+
+            fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+                Self::try_from_bytes(data).unwrap()
+            }
+
+            fn try_from_bytes(data: &[u8]) -> Result<<Self::View as WithLifetime>::Out> {
+                let mut elements = TupleElements::new(data, Self::alignment());
+                Ok((
+                    $($name::try_from_bytes(
+                        elements
+                            .next($name::alignment())
+                            .ok_or_else(|| invalid_value::<Self>(data))?,
+                    )?,)+
+                    $last::try_from_bytes(
+                        elements
+                            .last($last::alignment())
+                            .ok_or_else(|| invalid_value::<Self>(data))?,
+                    )?,
+                ))
+            }
+
+            fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+                let mut encoder = TupleEncoder::new(Self::alignment());
+                $(encoder.element(self.$index.as_bytes().as_ref(), $name::alignment());)+
+                encoder.last(self.$last_index.as_bytes().as_ref(), $last::alignment())
+            }
+
+            fn fixed_width() -> Option<usize> {
+                let mut layout = TupleLayout::new(Self::alignment());
+                $(layout.element($name::fixed_width()?, $name::alignment());)+
+                layout.last($last::fixed_width()?, $last::alignment());
+                Some(layout.len())
+            }
+
+            fn alignment() -> usize {
+                let alignments = [$($name::alignment(),)+ $last::alignment()];
+                alignments.iter().copied().max().unwrap()
+            }
+
+            fn redb_type_name() -> &'static str {
+                let names = [$($name::redb_type_name(),)+ $last::redb_type_name()];
+                intern_type_name(format!("({})", names.join(",")))
+            }
+        }
+
+        impl<$($name: RedbKey,)+ $last: RedbKey> RedbKey for ($($name,)+ $last) {
+            fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+                Self::try_compare(data1, data2).unwrap()
+            }
+
+            fn try_compare(data1: &[u8], data2: &[u8]) -> Result<Ordering> {
+                let mut elements1 = TupleElements::new(data1, Self::alignment());
+                let mut elements2 = TupleElements::new(data2, Self::alignment());
+                $(
+                    let element1 = elements1
+                        .next($name::alignment())
+                        .ok_or_else(|| invalid_value::<Self>(data1))?;
+                    let element2 = elements2
+                        .next($name::alignment())
+                        .ok_or_else(|| invalid_value::<Self>(data2))?;
+                    match $name::try_compare(element1, element2)? {
+                        Ordering::Equal => {}
+                        ordering => return Ok(ordering),
+                    }
+                )+
+                $last::try_compare(
+                    elements1
+                        .last($last::alignment())
+                        .ok_or_else(|| invalid_value::<Self>(data1))?,
+                    elements2
+                        .last($last::alignment())
+                        .ok_or_else(|| invalid_value::<Self>(data2))?,
+                )
+            }
+        }
+
+        impl<$($name: FixedWidth,)+ $last: FixedWidth> FixedWidth for ($($name,)+ $last) {
+            fn from_unaligned_bytes(data: &[u8]) -> Result<Self> {
+                let mut elements = TupleElements::new(data, Self::alignment());
+                Ok((
+                    $($name::from_unaligned_bytes(
+                        elements
+                            .next($name::alignment())
+                            .ok_or_else(|| invalid_value::<Self>(data))?,
+                    )?,)+
+                    $last::from_unaligned_bytes(
+                        elements
+                            .last($last::alignment())
+                            .ok_or_else(|| invalid_value::<Self>(data))?,
+                    )?,
+                ))
+            }
+        }
+    };
+}
+
+tuple_impl!(T0 0; T1 1);
+tuple_impl!(T0 0, T1 1; T2 2);
+tuple_impl!(T0 0, T1 1, T2 2; T3 3);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3; T4 4);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4; T5 5);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5; T6 6);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6; T7 7);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7; T8 8);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8; T9 9);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9; T10 10);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10; T11 11);
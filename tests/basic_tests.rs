@@ -1,5 +1,11 @@
-use redb::{Database, MultimapTableDefinition, RangeIter, ReadableTable, TableDefinition};
-use std::ops::{Range, RangeFull};
+use redb::{
+    AsBytesWithLifetime, Database, Entry, Error, MultimapTableDefinition, Pod, RangeIter,
+    ReadableTable, RedbKey, RedbValue, RefAsBytesLifetime, RefLifetime, TableDefinition,
+    WithLifetime,
+};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::{Bound, Range, RangeFull};
 use tempfile::NamedTempFile;
 
 const SLICE_TABLE: TableDefinition<[u8], [u8]> = TableDefinition::new("x");
@@ -8,7 +14,7 @@ const U64_TABLE: TableDefinition<u64, u64> = TableDefinition::new("u64");
 #[test]
 fn len() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -26,7 +32,7 @@ fn len() {
 #[test]
 fn stored_size() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -44,7 +50,7 @@ fn stored_size() {
 #[test]
 fn create_open() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
@@ -53,7 +59,7 @@ fn create_open() {
     write_txn.commit().unwrap();
     drop(db);
 
-    let db2 = unsafe { Database::open(tmpfile.path()).unwrap() };
+    let db2 = Database::open(tmpfile.path()).unwrap();
 
     let read_txn = db2.begin_read().unwrap();
     let table = read_txn.open_table(U64_TABLE).unwrap();
@@ -66,7 +72,7 @@ fn multiple_tables() {
     let definition2: TableDefinition<[u8], [u8]> = TableDefinition::new("2");
 
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(definition1).unwrap();
@@ -89,7 +95,7 @@ fn multiple_tables() {
 #[test]
 fn list_tables() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let definition_x: TableDefinition<[u8], [u8]> = TableDefinition::new("x");
     let definition_y: TableDefinition<[u8], [u8]> = TableDefinition::new("y");
@@ -120,7 +126,7 @@ fn list_tables() {
 #[test]
 fn is_empty() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
@@ -137,7 +143,7 @@ fn is_empty() {
 #[test]
 fn abort() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
@@ -165,9 +171,9 @@ fn abort() {
 }
 
 #[test]
-fn insert_overwrite() {
+fn abort_preserves_committed() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -175,26 +181,57 @@ fn insert_overwrite() {
     }
     write_txn.commit().unwrap();
 
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"hello2", b"aborted").unwrap();
+    }
+    write_txn.abort().unwrap();
+
     let read_txn = db.begin_read().unwrap();
     let table = read_txn.open_table(SLICE_TABLE).unwrap();
     assert_eq!(b"world", table.get(b"hello").unwrap().unwrap());
+    assert!(table.get(b"hello2").unwrap().is_none());
+}
 
+#[test]
+fn insert_overwrite() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
-        table.insert(b"hello", b"replaced").unwrap();
+        assert!(table.insert(b"hello", b"world").unwrap().is_none());
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
     let table = read_txn.open_table(SLICE_TABLE).unwrap();
-    assert_eq!(b"replaced", table.get(b"hello").unwrap().unwrap());
+    assert_eq!(b"world", table.get(b"hello").unwrap().unwrap());
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        // Replaces a committed value
+        let old_value = table.insert(b"hello", b"replaced").unwrap();
+        assert_eq!(b"world", old_value.unwrap().to_value().unwrap());
+        // Replaces a value written by this transaction
+        let old_value = table.insert(b"hello", b"replaced2").unwrap();
+        assert_eq!(b"replaced", old_value.unwrap().to_value().unwrap());
+        let old_value = table.insert(b"hello", b"replaced3").unwrap();
+        assert_eq!(b"replaced2", old_value.unwrap().to_value().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(b"replaced3", table.get(b"hello").unwrap().unwrap());
 }
 
 #[test]
 fn insert_reserve() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let value = b"world";
     let write_txn = db.begin_write().unwrap();
     {
@@ -212,7 +249,7 @@ fn insert_reserve() {
 #[test]
 fn delete() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -231,7 +268,7 @@ fn delete() {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
         assert_eq!(
             b"world",
-            table.remove(b"hello").unwrap().unwrap().to_value()
+            table.remove(b"hello").unwrap().unwrap().to_value().unwrap()
         );
         assert!(table.remove(b"hello").unwrap().is_none());
     }
@@ -243,10 +280,531 @@ fn delete() {
     assert_eq!(table.len().unwrap(), 1);
 }
 
+#[test]
+fn entry() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            table
+                .entry(&(i % 100))
+                .unwrap()
+                .and_modify(|x| x + 1)
+                .unwrap()
+                .or_insert(&1)
+                .unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(10, table.get(&5).unwrap().unwrap());
+        match table.entry(&5).unwrap() {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(10, entry.value().unwrap().to_value().unwrap());
+                assert_eq!(10, entry.insert(&11).unwrap().to_value().unwrap());
+                assert_eq!(11, entry.remove().unwrap().to_value().unwrap());
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match table.entry(&1000).unwrap() {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => {
+                let entry = entry.insert(&1).unwrap();
+                assert_eq!(1, entry.value().unwrap().to_value().unwrap());
+            }
+        }
+        let entry = table.entry(&6).unwrap().or_insert(&0).unwrap();
+        assert_eq!(10, entry.value().unwrap().to_value().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(100, table.len().unwrap());
+    assert!(table.get(&5).unwrap().is_none());
+    assert_eq!(10, table.get(&99).unwrap().unwrap());
+    assert_eq!(1, table.get(&1000).unwrap().unwrap());
+}
+
+#[test]
+fn entry_large_values() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let mut expected = vec![vec![]; 50];
+    for round in 0..3 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+            for (i, value) in expected.iter_mut().enumerate() {
+                // Grow the values, so that leaves must be split
+                value.push(round as u8);
+                value.extend_from_slice(&[i as u8; 300]);
+                let key = [i as u8];
+                table
+                    .entry(&key)
+                    .unwrap()
+                    .and_modify(|old| {
+                        assert_eq!(&value[..(value.len() - 301)], old);
+                        value.clone()
+                    })
+                    .unwrap()
+                    .or_insert(value)
+                    .unwrap();
+            }
+            for i in (0..50u8).step_by(7) {
+                if let Entry::Occupied(entry) = table.entry(&[i]).unwrap() {
+                    entry.remove().unwrap();
+                }
+            }
+        }
+        write_txn.commit().unwrap();
+        for i in (0..50).step_by(7) {
+            expected[i].clear();
+        }
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(SLICE_TABLE).unwrap();
+        for (i, value) in expected.iter().enumerate() {
+            if value.is_empty() {
+                assert!(table.get(&[i as u8]).unwrap().is_none());
+            } else {
+                assert_eq!(value.as_slice(), table.get(&[i as u8]).unwrap().unwrap());
+            }
+        }
+    }
+}
+
+#[test]
+fn remove_range() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000u64 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.remove_range(100..9_000).unwrap();
+        table.remove_range(..=10).unwrap();
+        table.remove_range(9_990..).unwrap();
+        assert_eq!(89 + 990, table.len().unwrap());
+        // The tree must still be usable after the bulk removal
+        for i in 0..100u64 {
+            table.insert(&(i * 100), &0).unwrap();
+        }
+        assert_eq!(
+            0,
+            table.remove(&9_500).unwrap().unwrap().to_value().unwrap()
+        );
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut iter = table.range::<RangeFull, u64>(..).unwrap();
+    let mut expected = (11..100).chain(9_000..9_990).collect::<BTreeSet<u64>>();
+    expected.extend((0..100).map(|i| i * 100));
+    expected.remove(&9_500);
+    for key in expected {
+        assert_eq!(key, iter.next().unwrap().unwrap().0);
+    }
+    assert!(iter.next().is_none());
+    assert!(db.check_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn first_last() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.first().unwrap().is_none());
+        assert!(table.last().unwrap().is_none());
+        for i in 1..1_000u64 {
+            table.insert(&i, &(i * 2)).unwrap();
+        }
+        assert_eq!((1, 2), table.first().unwrap().unwrap());
+        assert_eq!((999, 1998), table.last().unwrap().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!((1, 2), table.first().unwrap().unwrap());
+    assert_eq!((999, 1998), table.last().unwrap().unwrap());
+    let keys: Vec<u64> = table.iter().unwrap().map(|x| x.unwrap().0).collect();
+    assert_eq!((1..1_000).collect::<Vec<u64>>(), keys);
+}
+
+#[test]
+fn pop_first_last() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1_000u64 {
+            table.insert(&i, &(i * 2)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..500u64 {
+            {
+                let (key, value) = table.pop_first().unwrap().unwrap();
+                assert_eq!(i, key.to_value().unwrap());
+                assert_eq!(i * 2, value.to_value().unwrap());
+            }
+            let (key, value) = table.pop_last().unwrap().unwrap();
+            assert_eq!(999 - i, key.to_value().unwrap());
+            assert_eq!((999 - i) * 2, value.to_value().unwrap());
+        }
+        assert!(table.pop_first().unwrap().is_none());
+        assert!(table.pop_last().unwrap().is_none());
+        assert!(table.is_empty().unwrap());
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn rank_nth() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(0, table.rank(&5).unwrap());
+        assert!(table.nth(0).unwrap().is_none());
+        for i in 0..1_000u64 {
+            table.insert(&(i * 2), &i).unwrap();
+        }
+        assert_eq!(1_000, table.len().unwrap());
+        assert_eq!((0, 0), table.nth(0).unwrap().unwrap());
+        assert_eq!((1_234, 617), table.nth(617).unwrap().unwrap());
+        assert_eq!((1_998, 999), table.nth(999).unwrap().unwrap());
+        assert!(table.nth(1_000).unwrap().is_none());
+        assert_eq!(617, table.rank(&1_234).unwrap());
+        assert_eq!(618, table.rank(&1_235).unwrap());
+        assert_eq!(1_000, table.rank(&5_000).unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.remove_range(100..500).unwrap();
+        table.remove(&0).unwrap();
+        table.pop_last().unwrap();
+        if let Entry::Vacant(entry) = table.entry(&101).unwrap() {
+            entry.insert(&0).unwrap();
+        }
+        if let Entry::Occupied(entry) = table.entry(&98).unwrap() {
+            entry.remove().unwrap();
+        }
+        table
+            .bulk_load((2_000..2_500u64).map(|i| (i, i)), 1.0)
+            .unwrap();
+        assert_eq!(1_298, table.len().unwrap());
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let entries: Vec<(u64, u64)> = table
+        .iter()
+        .unwrap()
+        .map(|x| {
+            let (key, value) = x.unwrap();
+            (key, value)
+        })
+        .collect();
+    assert_eq!(1_298, entries.len());
+    assert_eq!(entries.len(), table.len().unwrap());
+    for (i, (key, value)) in entries.iter().enumerate() {
+        assert_eq!((*key, *value), table.nth(i).unwrap().unwrap());
+        assert_eq!(i, table.rank(key).unwrap());
+    }
+}
+
+#[test]
+fn count_range_approximate_size() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(0, table.count_range::<RangeFull, u64>(..).unwrap());
+        assert_eq!(0, table.approximate_size::<RangeFull, u64>(..).unwrap());
+        for i in 0..1_000u64 {
+            table.insert(&(i * 2), &i).unwrap();
+        }
+        let mut other = write_txn.open_table(SLICE_TABLE).unwrap();
+        for i in 0..1_000u64 {
+            let value = vec![0; (i % 50) as usize];
+            other
+                .insert(format!("{:04}", i).as_bytes(), &value)
+                .unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(1_000, table.count_range::<RangeFull, u64>(..).unwrap());
+    assert_eq!(50, table.count_range(100..200).unwrap());
+    assert_eq!(51, table.count_range(100..=200).unwrap());
+    assert_eq!(50, table.count_range(101..=200).unwrap());
+    assert_eq!(
+        49,
+        table
+            .count_range::<_, u64>((Bound::Excluded(100), Bound::Excluded(200)))
+            .unwrap()
+    );
+    assert_eq!(995, table.count_range(10..).unwrap());
+    assert_eq!(0, table.count_range(5_000..).unwrap());
+    let inverted = (Bound::Included(200u64), Bound::Excluded(100u64));
+    assert_eq!(0, table.count_range::<_, u64>(inverted).unwrap());
+    // Every entry is a u64 key and value, so the estimate is exact
+    assert_eq!(50 * 16, table.approximate_size(100..200).unwrap());
+    assert_eq!(
+        1_000 * 16,
+        table.approximate_size::<RangeFull, u64>(..).unwrap()
+    );
+    assert_eq!(0, table.approximate_size::<_, u64>(inverted).unwrap());
+
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    let range = &b"0100"[..]..&b"0900"[..];
+    assert_eq!(800, table.count_range(range.clone()).unwrap());
+    let actual: u64 = (100..900u64).map(|i| 4 + i % 50).sum();
+    let estimate = table.approximate_size(range).unwrap();
+    assert!(estimate > actual / 2 && estimate < actual * 2);
+}
+
+#[test]
+fn prefix() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let mut keys = vec![];
+    for a in [0u8, 1, 0x7F, 0xFF] {
+        for b in [0u8, 1, 0xFF] {
+            for i in 0..50u8 {
+                keys.push(vec![a, b, i]);
+            }
+            keys.push(vec![a, b]);
+        }
+        keys.push(vec![a]);
+    }
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for key in keys.iter() {
+            table.insert(key, &[0; 100]).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+    keys.sort();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    let prefixes: &[&[u8]] = &[
+        &[],
+        &[0],
+        &[1],
+        &[2],
+        &[0x7F, 1],
+        &[0xFF],
+        &[0xFF, 0xFF],
+        &[0xFF, 0xFF, 49],
+        &[0xFF, 0xFF, 0xFF],
+        &[0, 2],
+    ];
+    for prefix in prefixes {
+        let expected: Vec<&[u8]> = keys
+            .iter()
+            .filter(|k| k.starts_with(prefix))
+            .map(|k| k.as_slice())
+            .collect();
+        let forward: Vec<&[u8]> = table
+            .prefix(prefix)
+            .unwrap()
+            .map(|x| x.unwrap().0)
+            .collect();
+        assert_eq!(expected, forward);
+        let mut reverse: Vec<&[u8]> = table
+            .prefix(prefix)
+            .unwrap()
+            .rev()
+            .map(|x| x.unwrap().0)
+            .collect();
+        reverse.reverse();
+        assert_eq!(expected, reverse);
+    }
+
+    let definition: TableDefinition<str, u64> = TableDefinition::new("str");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for (i, key) in ["a", "ab", "abc", "ab\u{7f}", "ab\u{ff}", "ac", "b"]
+            .iter()
+            .enumerate()
+        {
+            table.insert(key, &(i as u64)).unwrap();
+        }
+        let keys: Vec<&str> = table.prefix("ab").unwrap().map(|x| x.unwrap().0).collect();
+        assert_eq!(keys, ["ab", "abc", "ab\u{7f}", "ab\u{ff}"]);
+        assert!(table.prefix("abd").unwrap().next().is_none());
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn drain() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1_000u64 {
+            table.insert(&i, &(i * 2)).unwrap();
+        }
+        let mut drain = table.drain(10..500).unwrap();
+        {
+            let (key, value) = drain.next_back().unwrap();
+            assert_eq!(499, key.to_value().unwrap());
+            assert_eq!(998, value.to_value().unwrap());
+        }
+        for (i, (key, value)) in (10..499u64).zip(&mut drain) {
+            assert_eq!(i, key.to_value().unwrap());
+            assert_eq!(i * 2, value.to_value().unwrap());
+        }
+        assert!(drain.next().is_none());
+        drop(drain);
+        assert!(table.get(&10).unwrap().is_none());
+        assert_eq!(18, table.get(&9).unwrap().unwrap());
+        assert_eq!(510, table.len().unwrap());
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn retain() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..2_000u64 {
+            table.insert(&i, &(i % 200)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table
+            .retain(|key, value| key % 2 == 0 || value < 20)
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(1_000 + 100, table.len().unwrap());
+    for i in 0..2_000u64 {
+        assert_eq!(i % 2 == 0 || i % 200 < 20, table.get(&i).unwrap().is_some());
+    }
+    assert!(db.check_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn bulk_load() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.bulk_load((0..5_000u64).map(|i| (i, i)), 0.8).unwrap();
+        // Appended after the existing keys
+        table
+            .bulk_load((5_000..10_000u64).map(|i| (i, i)), 1.0)
+            .unwrap();
+        // Overlaps the existing keys
+        table
+            .bulk_load((0..50u64).map(|i| (i * 200, 0)), 1.0)
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(10_000, table.len().unwrap());
+    let mut iter = table.range::<RangeFull, u64>(..).unwrap();
+    for i in 0..10_000u64 {
+        let expected = if i % 200 == 0 { 0 } else { i };
+        assert_eq!((i, expected), iter.next().unwrap().unwrap());
+    }
+    assert!(iter.next().is_none());
+    assert!(db.check_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn bulk_load_unsorted() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        // Enough entries to fill several leaves before the unsorted key
+        let unsorted = (0..10_000u64).chain([5]).map(|i| (i, i));
+        assert!(matches!(
+            table.bulk_load(unsorted, 1.0),
+            Err(Error::UnsortedInput)
+        ));
+        assert!(table.is_empty().unwrap());
+        assert!(matches!(
+            table.bulk_load(vec![(0u64, 0u64)], 0.0),
+            Err(Error::InvalidFillFactor(_))
+        ));
+        assert!(matches!(
+            table.bulk_load(vec![(0u64, 0u64)], f32::NAN),
+            Err(Error::InvalidFillFactor(_))
+        ));
+
+        table.insert(&100, &100).unwrap();
+        // Overlaps the existing key, with a duplicate after some entries which would be inserted
+        let duplicate = vec![(1u64, 1u64), (50, 50), (200, 200), (200, 0)];
+        assert!(matches!(
+            table.bulk_load(duplicate, 1.0),
+            Err(Error::UnsortedInput)
+        ));
+        assert_eq!(1, table.len().unwrap());
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+}
+
 #[test]
 fn no_dirty_reads() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -266,7 +824,7 @@ fn no_dirty_reads() {
 #[test]
 fn read_isolation() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -303,7 +861,7 @@ fn read_isolation() {
 #[test]
 fn reopen_table() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
@@ -311,15 +869,20 @@ fn reopen_table() {
     }
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(0, table.get(&0).unwrap().unwrap());
         table.insert(&1, &1).unwrap();
     }
     write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 2);
 }
 
 #[test]
 fn u64_type() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
@@ -335,7 +898,7 @@ fn u64_type() {
 #[test]
 fn i128_type() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
 
     let definition: TableDefinition<i128, i128> = TableDefinition::new("x");
@@ -353,7 +916,7 @@ fn i128_type() {
     assert_eq!(-2, table.get(&-1).unwrap().unwrap());
     let mut iter: RangeIter<i128, i128> = table.range::<RangeFull, i128>(..).unwrap();
     for i in -11..10 {
-        assert_eq!(iter.next().unwrap().1, i);
+        assert_eq!(iter.next().unwrap().unwrap().1, i);
     }
     assert!(iter.next().is_none());
 }
@@ -361,7 +924,7 @@ fn i128_type() {
 #[test]
 fn f32_type() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let definition: TableDefinition<u8, f32> = TableDefinition::new("x");
 
@@ -377,10 +940,138 @@ fn f32_type() {
     assert_eq!(0.3, table.get(&0).unwrap().unwrap());
 }
 
+#[test]
+fn f64_key_order() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<f64, u8> = TableDefinition::new("x");
+
+    let values = [
+        f64::NEG_INFINITY,
+        -1.5,
+        -f64::MIN_POSITIVE,
+        -0.0,
+        0.0,
+        f64::MIN_POSITIVE,
+        1.0,
+        f64::INFINITY,
+        f64::NAN,
+    ];
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for (i, value) in values.iter().enumerate().rev() {
+            table.insert(value, &(i as u8)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let order: Vec<u8> = table.iter().unwrap().map(|x| x.unwrap().1).collect();
+    assert_eq!(order, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(3, table.get(&-0.0).unwrap().unwrap());
+    assert_eq!(8, table.get(&f64::NAN).unwrap().unwrap());
+    assert_eq!(4, table.range(-1.0..1.0).unwrap().count());
+}
+
+#[test]
+fn bool_char_unit_types() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let bool_definition: TableDefinition<bool, char> = TableDefinition::new("bool");
+    let char_definition: TableDefinition<char, ()> = TableDefinition::new("char");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(bool_definition).unwrap();
+        table.insert(&true, &'\u{1F980}').unwrap();
+        table.insert(&false, &'a').unwrap();
+        let mut table = write_txn.open_table(char_definition).unwrap();
+        for c in ['z', 'a', '\u{1F980}', 'é'] {
+            table.insert(&c, &()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(bool_definition).unwrap();
+    assert_eq!('\u{1F980}', table.get(&true).unwrap().unwrap());
+    assert_eq!((false, 'a'), table.iter().unwrap().next().unwrap().unwrap());
+    let table = read_txn.open_table(char_definition).unwrap();
+    let keys: Vec<char> = table.iter().unwrap().map(|x| x.unwrap().0).collect();
+    assert_eq!(keys, ['a', 'z', 'é', '\u{1F980}']);
+    assert_eq!(Some(()), table.get(&'z').unwrap());
+}
+
+#[test]
+fn owned_types() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<String, Vec<u8>> = TableDefinition::new("x");
+    let array_definition: TableDefinition<[u8; 3], (String, u64)> = TableDefinition::new("y");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(&"hello".to_string(), &vec![1, 2, 3]).unwrap();
+        let mut table = write_txn.open_table(array_definition).unwrap();
+        table.insert(&[3, 2, 1], &("world".to_string(), 1)).unwrap();
+        table.insert(&[1, 2, 3], &("hello".to_string(), 2)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(
+        &[1, 2, 3],
+        table.get(&"hello".to_string()).unwrap().unwrap()
+    );
+    let table = read_txn.open_table(array_definition).unwrap();
+    assert_eq!(("world", 1), table.get(&[3, 2, 1]).unwrap().unwrap());
+    let (key, value) = table.iter().unwrap().next().unwrap().unwrap();
+    assert_eq!(&[1, 2, 3], key);
+    assert_eq!(("hello", 2), value);
+
+    let wrong_definition: TableDefinition<[u8; 4], (String, u64)> = TableDefinition::new("y");
+    assert!(matches!(
+        read_txn.open_table(wrong_definition),
+        Err(Error::TableTypeMismatch(_))
+    ));
+}
+
+#[test]
+fn option_type() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<Option<u64>, Option<String>> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(&Some(256), &None).unwrap();
+        table.insert(&Some(1), &Some("one".to_string())).unwrap();
+        table.insert(&None, &Some("none".to_string())).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(Some("one"), table.get(&Some(1)).unwrap().unwrap());
+    assert_eq!(None, table.get(&Some(256)).unwrap().unwrap());
+    assert!(table.get(&Some(2)).unwrap().is_none());
+    let keys: Vec<Option<u64>> = table.iter().unwrap().map(|x| x.unwrap().0).collect();
+    assert_eq!(keys, [None, Some(1), Some(256)]);
+}
+
 #[test]
 fn str_type() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let definition: TableDefinition<str, str> = TableDefinition::new("x");
 
@@ -397,22 +1088,350 @@ fn str_type() {
     assert_eq!("world", table.get(&hello).unwrap().unwrap());
 
     let mut iter: RangeIter<str, str> = table.range::<RangeFull, &str>(..).unwrap();
-    assert_eq!(iter.next().unwrap().1, "world");
+    assert_eq!(iter.next().unwrap().unwrap().1, "world");
     assert!(iter.next().is_none());
 
     let mut iter: RangeIter<str, str> = table.range("a".to_string().."z".to_string()).unwrap();
-    assert_eq!(iter.next().unwrap().1, "world");
+    assert_eq!(iter.next().unwrap().unwrap().1, "world");
     assert!(iter.next().is_none());
 
     let mut iter: RangeIter<str, str> = table.range("a".."z").unwrap();
-    assert_eq!(iter.next().unwrap().1, "world");
+    assert_eq!(iter.next().unwrap().unwrap().1, "world");
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn tuple_type() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<(u64, i32), (u8, u16, u64)> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for a in [300u64, 1, 256] {
+            for b in [-1i32, 1, -300] {
+                table.insert(&(a, b), &(1, 2, a)).unwrap();
+            }
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!((1, 2, 256), table.get(&(256, 1)).unwrap().unwrap());
+    assert!(table.get(&(256, 2)).unwrap().is_none());
+    let keys: Vec<(u64, i32)> = table.iter().unwrap().map(|x| x.unwrap().0).collect();
+    assert_eq!(
+        keys,
+        [
+            (1, -300),
+            (1, -1),
+            (1, 1),
+            (256, -300),
+            (256, -1),
+            (256, 1),
+            (300, -300),
+            (300, -1),
+            (300, 1)
+        ]
+    );
+    let keys: Vec<(u64, i32)> = table
+        .range((256, -1)..(300, -1))
+        .unwrap()
+        .map(|x| x.unwrap().0)
+        .collect();
+    assert_eq!(keys, [(256, -1), (256, 1), (300, -300)]);
+
+    let wrong_definition: TableDefinition<(u64, u64), (u8, u16, u64)> = TableDefinition::new("x");
+    assert!(matches!(
+        read_txn.open_table(wrong_definition),
+        Err(Error::TableTypeMismatch(_))
+    ));
+
+    // Type names of tuples are built at runtime, and only stored once for the whole process
+    let name = <(u64, i32)>::redb_type_name();
+    let other_thread = std::thread::spawn(<(u64, i32)>::redb_type_name)
+        .join()
+        .unwrap();
+    assert_eq!(name, other_thread);
+    assert!(std::ptr::eq(name, other_thread));
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Record {
+    id: u64,
+    x: u32,
+    y: u32,
+}
+
+unsafe impl Pod for Record {
+    const TYPE_NAME: &'static str = "Record";
+}
+
+fn record(i: u64) -> Record {
+    Record {
+        id: i,
+        x: i as u32 * 2,
+        y: i as u32 * 3,
+    }
+}
+
+#[test]
+fn pod_type() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<str, Record> = TableDefinition::new("x");
+    // Keys of varying length, so that the end of the keys is rarely aligned
+    let key = |i: u64| format!("{}{}", "k".repeat(i as usize % 7), i);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..500 {
+            let i = i * 37 % 500;
+            table.insert(&key(i), &record(i)).unwrap();
+        }
+        for i in (0..500).step_by(3) {
+            table.remove(&key(i)).unwrap();
+        }
+        let old = table.insert(&key(1), &record(1_000)).unwrap();
+        assert_eq!(&record(1), old.unwrap().to_value().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        let value: &Record = table.get(&key(2)).unwrap().unwrap();
+        assert_eq!(&record(2), value);
+        assert_eq!(&record(1_000), table.get(&key(1)).unwrap().unwrap());
+        assert!(table.get(&key(3)).unwrap().is_none());
+
+        for i in (1..500).step_by(5) {
+            table.remove(&key(i)).unwrap();
+        }
+        for i in (0..500).step_by(3) {
+            table.insert(&key(i), &record(i)).unwrap();
+        }
+        if let Entry::Occupied(entry) = table.entry(&key(4)).unwrap() {
+            assert_eq!(&record(4), entry.remove().unwrap().to_value().unwrap());
+        }
+        {
+            let (_, value) = table.pop_first().unwrap().unwrap();
+            assert_eq!(&record(0), value.to_value().unwrap());
+        }
+
+        let mut drained = 0;
+        for (key, value) in table.drain::<_, &str>("k10".."k2").unwrap() {
+            let i: u64 = key.to_value().unwrap()[1..].parse().unwrap();
+            assert_eq!(&record(i), value.to_value().unwrap());
+            drained += 1;
+        }
+        assert!(drained > 0);
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let mut count = 0;
+    for entry in table.iter().unwrap() {
+        let (key, value) = entry.unwrap();
+        let i: u64 = key.trim_start_matches('k').parse().unwrap();
+        assert_eq!(value.id, i);
+        assert_eq!(&record(i), value);
+        count += 1;
+    }
+    assert_eq!(count, table.len().unwrap());
+    assert!(count > 300);
+}
+
+#[test]
+fn pod_in_option_and_tuple() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let option_definition: TableDefinition<u64, Option<Record>> = TableDefinition::new("option");
+    let tuple_definition: TableDefinition<u64, (u8, Record)> = TableDefinition::new("tuple");
+    let last_definition: TableDefinition<u64, (Record, u8)> = TableDefinition::new("last");
+    let str_definition: TableDefinition<u64, (Record, String)> = TableDefinition::new("str");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut option_table = write_txn.open_table(option_definition).unwrap();
+        let mut tuple_table = write_txn.open_table(tuple_definition).unwrap();
+        let mut last_table = write_txn.open_table(last_definition).unwrap();
+        let mut str_table = write_txn.open_table(str_definition).unwrap();
+        for i in 0..100 {
+            let value = if i % 3 == 0 { None } else { Some(record(i)) };
+            option_table.insert(&i, &value).unwrap();
+            tuple_table.insert(&i, &(i as u8, record(i))).unwrap();
+            last_table.insert(&i, &(record(i), i as u8)).unwrap();
+            str_table
+                .insert(&i, &(record(i), "x".repeat(i as usize % 5)))
+                .unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let value = (record(1), 2u8);
+    assert_eq!(Some(value.as_bytes().len()), <(Record, u8)>::fixed_width());
+
+    let read_txn = db.begin_read().unwrap();
+    let option_table = read_txn.open_table(option_definition).unwrap();
+    let tuple_table = read_txn.open_table(tuple_definition).unwrap();
+    let last_table = read_txn.open_table(last_definition).unwrap();
+    let str_table = read_txn.open_table(str_definition).unwrap();
+    for i in 0..100 {
+        let value = option_table.get(&i).unwrap().unwrap();
+        if i % 3 == 0 {
+            assert!(value.is_none());
+        } else {
+            assert_eq!(&record(i), value.unwrap());
+        }
+        let (x, value) = tuple_table.get(&i).unwrap().unwrap();
+        assert_eq!(i as u8, x);
+        assert_eq!(&record(i), value);
+        let (value, x) = last_table.get(&i).unwrap().unwrap();
+        assert_eq!(&record(i), value);
+        assert_eq!(i as u8, x);
+        let (value, name) = str_table.get(&i).unwrap().unwrap();
+        assert_eq!(&record(i), value);
+        assert_eq!("x".repeat(i as usize % 5), name);
+    }
+}
+
+// Writes bytes which are not a valid instance of the type it claims to be
+struct Impostor(&'static [u8]);
+
+impl RedbValue for Impostor {
+    type View = RefLifetime<[u8]>;
+    type ToBytes = RefAsBytesLifetime<[u8]>;
+
+    fn from_bytes(data: &[u8]) -> <Self::View as WithLifetime>::Out {
+        data
+    }
+
+    fn as_bytes(&self) -> <Self::ToBytes as AsBytesWithLifetime>::Out {
+        self.0
+    }
+
+    fn redb_type_name() -> &'static str {
+        IMPOSTOR_TYPE.with(|name| name.get())
+    }
+}
+
+impl RedbKey for Impostor {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
+thread_local! {
+    static IMPOSTOR_TYPE: std::cell::Cell<&'static str> = std::cell::Cell::new("");
+}
+
+#[test]
+fn invalid_values() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let str_definition: TableDefinition<u64, str> = TableDefinition::new("x");
+    let u64_definition: TableDefinition<u64, u64> = TableDefinition::new("y");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        IMPOSTOR_TYPE.with(|name| name.set("str"));
+        let mut table = write_txn
+            .open_table(TableDefinition::<u64, Impostor>::new("x"))
+            .unwrap();
+        table.insert(&0, &Impostor(b"valid")).unwrap();
+        table.insert(&1, &Impostor(&[0xC3, 0x28])).unwrap();
+    }
+    {
+        IMPOSTOR_TYPE.with(|name| name.set("u64"));
+        let mut table = write_txn
+            .open_table(TableDefinition::<u64, Impostor>::new("y"))
+            .unwrap();
+        table.insert(&0, &Impostor(&[1, 2, 3])).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(str_definition).unwrap();
+    assert_eq!("valid", table.get(&0).unwrap().unwrap());
+    assert!(matches!(table.get(&1), Err(Error::Corrupted(_))));
+    let mut iter = table.iter().unwrap();
+    assert_eq!("valid", iter.next().unwrap().unwrap().1);
+    assert!(matches!(iter.next().unwrap(), Err(Error::Corrupted(_))));
+    let table = read_txn.open_table(u64_definition).unwrap();
+    assert!(matches!(table.get(&0), Err(Error::Corrupted(_))));
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(str_definition).unwrap();
+        {
+            let old = table.insert(&1, "fixed").unwrap().unwrap();
+            assert!(matches!(old.to_value(), Err(Error::Corrupted(_))));
+        }
+        assert_eq!("fixed", table.get(&1).unwrap().unwrap());
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn invalid_keys() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let str_definition: TableDefinition<str, u64> = TableDefinition::new("x");
+    let u64_definition: TableDefinition<u64, u64> = TableDefinition::new("y");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        IMPOSTOR_TYPE.with(|name| name.set("str"));
+        let mut table = write_txn
+            .open_table(TableDefinition::<Impostor, u64>::new("x"))
+            .unwrap();
+        table.insert(&Impostor(&[0xC3, 0x28]), &0).unwrap();
+    }
+    {
+        IMPOSTOR_TYPE.with(|name| name.set("u64"));
+        let mut table = write_txn
+            .open_table(TableDefinition::<Impostor, u64>::new("y"))
+            .unwrap();
+        table.insert(&Impostor(&[1, 2, 3]), &0).unwrap();
+        table.insert(&Impostor(&[4, 5]), &1).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(str_definition).unwrap();
+    assert!(matches!(table.get("a"), Err(Error::Corrupted(_))));
+    let table = read_txn.open_table(u64_definition).unwrap();
+    assert!(matches!(table.get(&0), Err(Error::Corrupted(_))));
+    assert!(matches!(table.range(0..10), Err(Error::Corrupted(_))));
+    drop(table);
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(u64_definition).unwrap();
+        assert!(matches!(table.remove(&0), Err(Error::Corrupted(_))));
+        assert!(matches!(table.insert(&0, &0), Err(Error::Corrupted(_))));
+    }
+    write_txn.abort().unwrap();
+}
+
 #[test]
 fn owned_get_signatures() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let definition: TableDefinition<u32, u32> = TableDefinition::new("x");
 
@@ -432,17 +1451,17 @@ fn owned_get_signatures() {
 
     let mut iter: RangeIter<u32, u32> = table.range::<RangeFull, u32>(..).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, i + 1);
+        assert_eq!(iter.next().unwrap().unwrap().1, i + 1);
     }
     assert!(iter.next().is_none());
     let mut iter: RangeIter<u32, u32> = table.range(0..10).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, i + 1);
+        assert_eq!(iter.next().unwrap().unwrap().1, i + 1);
     }
     assert!(iter.next().is_none());
     let mut iter = table.range::<Range<&u32>, &u32>(&0..&10).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, i + 1);
+        assert_eq!(iter.next().unwrap().unwrap().1, i + 1);
     }
     assert!(iter.next().is_none());
 }
@@ -450,7 +1469,7 @@ fn owned_get_signatures() {
 #[test]
 fn ref_get_signatures() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -472,25 +1491,135 @@ fn ref_get_signatures() {
     let end = vec![10u8];
     let mut iter = table.range::<RangeFull, &[u8]>(..).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, &[i + 1]);
+        assert_eq!(iter.next().unwrap().unwrap().1, &[i + 1]);
     }
     assert!(iter.next().is_none());
 
     let mut iter = table.range(start.as_slice()..&end).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, &[i + 1]);
+        assert_eq!(iter.next().unwrap().unwrap().1, &[i + 1]);
     }
     assert!(iter.next().is_none());
 
     let mut iter = table.range(start..end).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, &[i + 1]);
+        assert_eq!(iter.next().unwrap().unwrap().1, &[i + 1]);
     }
     assert!(iter.next().is_none());
 
     let mut iter = table.range([0u8].as_slice()..[10u8].as_slice()).unwrap();
     for i in 0..10 {
-        assert_eq!(iter.next().unwrap().1, &[i + 1]);
+        assert_eq!(iter.next().unwrap().unwrap().1, &[i + 1]);
     }
     assert!(iter.next().is_none());
 }
+
+#[test]
+fn migrate_table() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let v0: TableDefinition<u64, u32> = TableDefinition::new("x");
+    let v1: TableDefinition<str, u64> = TableDefinition::new("x").with_version(1);
+    let v2: TableDefinition<str, u64> = TableDefinition::new("x").with_version(2);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(v0).unwrap();
+        for i in 0..1_000 {
+            table.insert(&i, &(i as u32 * 2)).unwrap();
+        }
+        assert!(matches!(
+            write_txn
+                .migrate_table::<u64, u32, _, _, _, _, _>(v1, |k, v| (k.to_string(), u64::from(v))),
+            Err(Error::TableAlreadyOpen(_, _))
+        ));
+    }
+    write_txn.commit().unwrap();
+
+    let migrate_v1 = |k: u64, v: u32| (format!("{:04}", k), u64::from(v));
+
+    // Mapping two entries to the same key fails, and leaves the table unchanged
+    let write_txn = db.begin_write().unwrap();
+    assert!(matches!(
+        write_txn.migrate_table::<u64, u32, _, _, _, _, _>(v1, |k, v| {
+            (format!("{}", k / 2), u64::from(v))
+        }),
+        Err(Error::DuplicateKey(_))
+    ));
+    assert_eq!(1_000, write_txn.open_table(v0).unwrap().len().unwrap());
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    // An aborted migration leaves the table unchanged
+    let write_txn = db.begin_write().unwrap();
+    assert!(write_txn
+        .migrate_table::<u64, u32, _, _, _, _, _>(v1, migrate_v1)
+        .unwrap());
+    write_txn.abort().unwrap();
+    let read_txn = db.begin_read().unwrap();
+    assert!(matches!(
+        read_txn.open_table(v1),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    assert_eq!(1_000, read_txn.open_table(v0).unwrap().len().unwrap());
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    assert!(write_txn
+        .migrate_table::<u64, u32, _, _, _, _, _>(v1, migrate_v1)
+        .unwrap());
+    // Already migrated
+    assert!(!write_txn
+        .migrate_table::<u64, u32, _, _, _, _, _>(v1, migrate_v1)
+        .unwrap());
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    let read_txn = db.begin_read().unwrap();
+    assert!(matches!(
+        read_txn.open_table(v0),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    let table = read_txn.open_table(v1).unwrap();
+    assert_eq!(1_000, table.len().unwrap());
+    assert_eq!(200, table.get("0100").unwrap().unwrap());
+    drop(read_txn);
+
+    // Only the version changes, so opening with the old one is rejected
+    let write_txn = db.begin_write().unwrap();
+    assert!(write_txn
+        .migrate_table::<str, u64, _, _, _, _, _>(v2, |k, v| (k.to_string(), v + 1))
+        .unwrap());
+    assert!(matches!(
+        write_txn.open_table(v1),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    assert_eq!(
+        201,
+        write_txn
+            .open_table(v2)
+            .unwrap()
+            .get("0100")
+            .unwrap()
+            .unwrap()
+    );
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    let write_txn = db.begin_write().unwrap();
+    let multimap: MultimapTableDefinition<u64, u64> = MultimapTableDefinition::new("multimap");
+    write_txn.open_multimap_table(multimap).unwrap();
+    assert!(matches!(
+        write_txn.migrate_table::<u64, u64, _, _, _, _, _>(
+            TableDefinition::<u64, u64>::new("multimap").with_version(1),
+            |k, v| (k, v)
+        ),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    let missing: TableDefinition<u64, u64> = TableDefinition::new("missing").with_version(1);
+    assert!(!write_txn
+        .migrate_table::<u64, u64, _, _, _, _, _>(missing, |k, v| (k, v))
+        .unwrap());
+    write_txn.abort().unwrap();
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "derive")]
+
+use redb::{Database, Error, Pod, ReadableTable, RedbKey, RedbValue, TableDefinition};
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Clone, Copy, PartialEq, RedbValue, RedbKey)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, RedbValue)]
+struct Sample {
+    position: Position,
+    id: [u8; 4],
+    active: bool,
+    label: char,
+    value: f64,
+}
+
+#[derive(Debug, PartialEq, RedbValue, RedbKey)]
+struct Version(u16, u8);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+unsafe impl Pod for Point {
+    const TYPE_NAME: &'static str = "Point";
+}
+
+// The fields after `tag` are not aligned
+#[derive(Debug, PartialEq, RedbValue)]
+struct Marker {
+    tag: u8,
+    point: Point,
+    range: (u16, [u8; 3]),
+}
+
+mod v1 {
+    #[derive(Debug, PartialEq, redb::RedbValue)]
+    pub struct User {
+        pub id: u64,
+    }
+}
+
+mod v2 {
+    #[derive(Debug, PartialEq, redb::RedbValue)]
+    pub struct User {
+        pub id: i64,
+    }
+
+    #[derive(Debug, PartialEq, redb::RedbValue)]
+    #[redb(type_name = "User")]
+    pub struct Renamed {
+        pub id: u64,
+    }
+}
+
+#[test]
+fn derived_types() {
+    assert_eq!(Some(8), Position::fixed_width());
+    assert_eq!(Some(25), Sample::fixed_width());
+    assert_eq!("derive_tests::Position", Position::redb_type_name());
+    assert!(matches!(
+        Position::try_from_bytes(&[0; 7]),
+        Err(Error::Corrupted(_))
+    ));
+    assert!(matches!(
+        Sample::try_from_bytes(&[2; 25]),
+        Err(Error::Corrupted(_))
+    ));
+
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<Position, Sample> = TableDefinition::new("x");
+
+    let positions = [(1, -1), (-1, 1), (1, 1), (-300, 2), (-1, -1)];
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for (i, (x, y)) in positions.iter().enumerate() {
+            let position = Position { x: *x, y: *y };
+            let sample = Sample {
+                position,
+                id: [i as u8; 4],
+                active: i % 2 == 0,
+                label: 'a',
+                value: i as f64 / 2.0,
+            };
+            table.insert(&position, &sample).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let sample = table.get(&Position { x: -300, y: 2 }).unwrap().unwrap();
+    assert_eq!(
+        Sample {
+            position: Position { x: -300, y: 2 },
+            id: [3; 4],
+            active: false,
+            label: 'a',
+            value: 1.5,
+        },
+        sample
+    );
+    let keys: Vec<(i32, i32)> = table
+        .iter()
+        .unwrap()
+        .map(|x| {
+            let position = x.unwrap().0;
+            (position.x, position.y)
+        })
+        .collect();
+    assert_eq!(keys, [(-300, 2), (-1, -1), (-1, 1), (1, -1), (1, 1)]);
+}
+
+#[test]
+fn derived_tuple_struct() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<Version, str> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(&Version(256, 0), "b").unwrap();
+        table.insert(&Version(1, 2), "a").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let (key, value) = table.iter().unwrap().next().unwrap().unwrap();
+    assert_eq!(Version(1, 2), key);
+    assert_eq!("a", value);
+    assert_eq!("b", table.get(&Version(256, 0)).unwrap().unwrap());
+}
+
+#[test]
+fn derived_unaligned_fields() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<u64, Marker> = TableDefinition::new("x");
+
+    let marker = Marker {
+        tag: 7,
+        point: Point { x: 1.5, y: -2.0 },
+        range: (300, [1, 2, 3]),
+    };
+    assert_eq!(Some(1 + 8 + 4 + 2 + 3), Marker::fixed_width());
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(&0, &marker).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(marker, table.get(&0).unwrap().unwrap());
+}
+
+#[test]
+fn derived_type_names() {
+    assert_eq!("derive_tests::v1::User", v1::User::redb_type_name());
+    assert_eq!("User", v2::Renamed::redb_type_name());
+
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<u64, v1::User> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(&0, &v1::User { id: u64::MAX }).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    assert!(matches!(
+        read_txn.open_table(TableDefinition::<u64, v2::User>::new("x")),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(v1::User { id: u64::MAX }, table.get(&0).unwrap().unwrap());
+}
@@ -5,8 +5,8 @@ use tempfile::NamedTempFile;
 use rand::prelude::SliceRandom;
 use rand::Rng;
 use redb::{
-    Database, DatabaseBuilder, Durability, Error, MultimapTableDefinition, ReadableTable,
-    TableDefinition,
+    Database, DatabaseBuilder, Durability, Error, MultimapTableDefinition, ReadableMultimapTable,
+    ReadableTable, TableDefinition,
 };
 
 const ELEMENTS: usize = 100;
@@ -33,7 +33,7 @@ fn mixed_durable_commit() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 129 * 4096;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let mut txn = db.begin_write().unwrap();
     txn.set_durability(Durability::None);
     {
@@ -51,7 +51,7 @@ fn non_durable_commit_persistence() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 16 * 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let mut txn = db.begin_write().unwrap();
     txn.set_durability(Durability::None);
     let pairs = gen_data(100, 16, 20);
@@ -66,7 +66,7 @@ fn non_durable_commit_persistence() {
 
     // Check that cleanly closing the database persists the non-durable commit
     drop(db);
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_read().unwrap();
     let table = txn.open_table(SLICE_TABLE).unwrap();
 
@@ -86,7 +86,7 @@ fn test_persistence(durability: Durability) {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 16 * 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let mut txn = db.begin_write().unwrap();
     txn.set_durability(durability);
     let pairs = gen_data(100, 16, 20);
@@ -100,7 +100,7 @@ fn test_persistence(durability: Durability) {
     txn.commit().unwrap();
 
     drop(db);
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_read().unwrap();
     let table = txn.open_table(SLICE_TABLE).unwrap();
 
@@ -131,11 +131,230 @@ fn change_db_size() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 16 * 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     drop(db);
 
-    let db = unsafe { Database::create(tmpfile.path(), db_size * 2) };
+    // The maximum size can't be lowered
+    let db = Database::create(tmpfile.path(), db_size / 2);
     assert!(matches!(db.err().unwrap(), Error::DbSizeMismatch { .. }));
+
+    let db = Database::create(tmpfile.path(), db_size * 2).unwrap();
+    drop(db);
+    let db = Database::create(tmpfile.path(), db_size);
+    assert!(matches!(db.err().unwrap(), Error::DbSizeMismatch { .. }));
+    let db = Database::open(tmpfile.path()).unwrap();
+    drop(db);
+    Database::create(tmpfile.path(), db_size * 2).unwrap();
+
+    // The header only has room to track a limited number of regions
+    let db = Database::create(tmpfile.path(), usize::MAX);
+    assert!(matches!(db.err().unwrap(), Error::DbSizeNotRaisable { .. }));
+    Database::create(tmpfile.path(), db_size * 2).unwrap();
+}
+
+#[test]
+fn already_open() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+    assert!(matches!(
+        Database::open(tmpfile.path()).err().unwrap(),
+        Error::DatabaseAlreadyOpen
+    ));
+    assert!(matches!(
+        Database::create(tmpfile.path(), 1024 * 1024).err().unwrap(),
+        Error::DatabaseAlreadyOpen
+    ));
+    drop(db);
+
+    Database::open(tmpfile.path()).unwrap();
+}
+
+#[test]
+fn read_only() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+
+    let db = Database::create(tmpfile.path(), 1024 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.insert(&0, &0).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let reader = Database::open_read_only(tmpfile.path()).unwrap();
+    let reader2 = Database::open_read_only(tmpfile.path()).unwrap();
+    assert!(matches!(
+        Database::open(tmpfile.path()).err().unwrap(),
+        Error::DatabaseAlreadyOpen
+    ));
+    assert!(matches!(
+        reader.begin_write().err().unwrap(),
+        Error::ReadOnly
+    ));
+
+    let read_txn = reader.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.get(&0).unwrap().unwrap(), 0);
+    assert!(table.get(&1).unwrap().is_none());
+    drop(table);
+    drop(read_txn);
+
+    // Write enough to grow the file
+    let pairs = gen_data(4, 16, 512 * 1024);
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.insert(&1, &1).unwrap();
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, value) in pairs.iter() {
+            table.insert(key, value).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = reader.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.get(&1).unwrap().unwrap(), 1);
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    for (key, value) in pairs.iter() {
+        assert_eq!(table.get(key).unwrap().unwrap(), value.as_slice());
+    }
+    drop(table);
+    drop(read_txn);
+    drop(reader);
+    drop(reader2);
+    drop(db);
+
+    // No writer
+    let reader = Database::open_read_only(tmpfile.path()).unwrap();
+    let read_txn = reader.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), pairs.len());
+}
+
+#[test]
+fn read_only_pins_commit() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+
+    let db = Database::create(tmpfile.path(), 1024 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+    drop(db);
+
+    // A writer may open the database while it's open read-only
+    let reader = Database::open_read_only(tmpfile.path()).unwrap();
+    let db = Database::open(tmpfile.path()).unwrap();
+
+    let read_txn = reader.begin_read().unwrap();
+    // Rewrite every page several times, so that the pages of the pinned commit would be reused
+    // if the writer didn't know about it
+    for round in 1..10 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            for i in 0..1000 {
+                table.insert(&i, &(i + round)).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    for i in 0..1000 {
+        assert_eq!(table.get(&i).unwrap().unwrap(), i);
+    }
+    drop(table);
+    drop(read_txn);
+
+    let read_txn = reader.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.get(&0).unwrap().unwrap(), 9);
+    drop(table);
+    drop(read_txn);
+    assert!(db.check_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn raise_db_size() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+
+    let db_size = 2 * 1024 * 1024;
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
+    let pairs = gen_data(15, 16, 200_000);
+    let mut written = 0;
+    for (key, value) in pairs.iter() {
+        let txn = db.begin_write().unwrap();
+        let result = {
+            let mut table = txn.open_table(SLICE_TABLE).unwrap();
+            table.insert(key, value).map(|_| ())
+        };
+        if matches!(result, Err(Error::OutOfSpace)) {
+            txn.abort().unwrap();
+            break;
+        }
+        result.unwrap();
+        match txn.commit() {
+            Ok(_) => {
+                written += 1;
+            }
+            Err(Error::OutOfSpace) => {
+                break;
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+    assert!(written < pairs.len());
+    drop(db);
+
+    let db = Database::create(tmpfile.path(), 4 * db_size).unwrap();
+    for (key, value) in pairs.iter().skip(written) {
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(SLICE_TABLE).unwrap();
+            table.insert(key, value).unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    assert!(fs::metadata(tmpfile.path()).unwrap().len() > db_size as u64);
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    for (key, value) in pairs.iter() {
+        assert_eq!(table.get(key).unwrap().unwrap(), value.as_slice());
+    }
+    drop(table);
+    drop(read_txn);
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+}
+
+#[test]
+fn unbounded_db_size() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+
+    let db = Database::create(tmpfile.path(), usize::MAX).unwrap();
+    let pairs = gen_data(20, 16, 100_000);
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        for (key, value) in pairs.iter() {
+            table.insert(key, value).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    let db = Database::create(tmpfile.path(), usize::MAX).unwrap();
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), pairs.len());
 }
 
 #[test]
@@ -143,12 +362,10 @@ fn free() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 8 * 1024 * 1024;
-    let db = unsafe {
-        Database::builder()
-            .set_dynamic_growth(false)
-            .create(tmpfile.path(), db_size)
-            .unwrap()
-    };
+    let db = Database::builder()
+        .set_dynamic_growth(false)
+        .create(tmpfile.path(), db_size)
+        .unwrap();
     let txn = db.begin_write().unwrap();
     {
         let _table = txn.open_table(SLICE_TABLE).unwrap();
@@ -204,7 +421,7 @@ fn large_values() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 50 * 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_write().unwrap();
 
     let mut key = vec![0; 1024];
@@ -234,7 +451,7 @@ fn large_keys() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 1024_1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_write().unwrap();
 
     let mut key = vec![0; 1024];
@@ -266,7 +483,7 @@ fn dynamic_growth() {
     let big_value = vec![0; 1024];
 
     let db_size = 10 * 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_write().unwrap();
     {
         let mut table = txn.open_table(table_definition).unwrap();
@@ -298,12 +515,10 @@ fn multi_page_kv() {
     let page_size = 4096;
 
     let db_size = 1024_1024;
-    let db = unsafe {
-        DatabaseBuilder::new()
-            .set_page_size(page_size)
-            .create(tmpfile.path(), db_size)
-            .unwrap()
-    };
+    let db = DatabaseBuilder::new()
+        .set_page_size(page_size)
+        .create(tmpfile.path(), db_size)
+        .unwrap();
     let txn = db.begin_write().unwrap();
 
     let mut key = vec![0; page_size + 1];
@@ -343,7 +558,7 @@ fn regression() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_write().unwrap();
     {
         let mut table = txn.open_table(U64_TABLE).unwrap();
@@ -405,7 +620,7 @@ fn regression2() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let tx = db.begin_write().unwrap();
 
     let a_def: TableDefinition<[u8], [u8]> = TableDefinition::new("a");
@@ -426,7 +641,7 @@ fn regression3() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 1024 * 1024;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let tx = db.begin_write().unwrap();
     {
         let mut t = tx.open_table(SLICE_TABLE).unwrap();
@@ -450,7 +665,7 @@ fn regression4() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 1507328;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let tx = db.begin_write().unwrap();
     {
         let mut t = tx.open_table(SLICE_TABLE).unwrap();
@@ -465,7 +680,7 @@ fn regression5() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 387;
-    let db = unsafe { Database::create(tmpfile.path(), db_size) };
+    let db = Database::create(tmpfile.path(), db_size);
     assert!(matches!(db, Err(Error::OutOfSpace)));
 }
 
@@ -474,7 +689,7 @@ fn regression6() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 0;
-    let db = unsafe { Database::create(tmpfile.path(), db_size) };
+    let db = Database::create(tmpfile.path(), db_size);
     assert!(matches!(db, Err(Error::OutOfSpace)));
 }
 
@@ -483,7 +698,7 @@ fn regression7() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 321033;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
 
     let table_def: TableDefinition<u64, [u8]> = TableDefinition::new("x");
 
@@ -515,8 +730,8 @@ fn regression7() {
         t.remove(&145227).unwrap();
 
         let mut iter = t.range(138763..(138763 + 232359)).unwrap().rev();
-        assert_eq!(iter.next().unwrap().0, 153701);
-        assert_eq!(iter.next().unwrap().0, 146255);
+        assert_eq!(iter.next().unwrap().unwrap().0, 153701);
+        assert_eq!(iter.next().unwrap().unwrap().0, 146255);
         assert!(iter.next().is_none());
     }
     tx.commit().unwrap();
@@ -527,7 +742,7 @@ fn regression8() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 321033;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
 
     let table_def: TableDefinition<u64, [u8]> = TableDefinition::new("x");
 
@@ -565,8 +780,8 @@ fn regression8() {
     {
         let t = tx.open_table(table_def).unwrap();
         let mut iter = t.range(118749..142650).unwrap();
-        assert_eq!(iter.next().unwrap().0, 118749);
-        assert_eq!(iter.next().unwrap().0, 130571);
+        assert_eq!(iter.next().unwrap().unwrap().0, 118749);
+        assert_eq!(iter.next().unwrap().unwrap().0, 130571);
         assert!(iter.next().is_none());
     }
     tx.commit().unwrap();
@@ -575,7 +790,7 @@ fn regression8() {
 #[test]
 fn non_durable_read_isolation() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let mut write_txn = db.begin_write().unwrap();
     write_txn.set_durability(Durability::None);
     {
@@ -614,7 +829,7 @@ fn non_durable_read_isolation() {
 #[test]
 fn range_query() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
@@ -628,7 +843,7 @@ fn range_query() {
     let table = read_txn.open_table(U64_TABLE).unwrap();
     let mut iter = table.range(3..7).unwrap();
     for i in 3..7u64 {
-        let (key, value) = iter.next().unwrap();
+        let (key, value) = iter.next().unwrap().unwrap();
         assert_eq!(i, key);
         assert_eq!(i, value);
     }
@@ -636,7 +851,7 @@ fn range_query() {
 
     let mut iter = table.range(3..=7).unwrap();
     for i in 3..=7u64 {
-        let (key, value) = iter.next().unwrap();
+        let (key, value) = iter.next().unwrap().unwrap();
         assert_eq!(i, key);
         assert_eq!(i, value);
     }
@@ -646,7 +861,7 @@ fn range_query() {
 #[test]
 fn range_query_reversed() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
@@ -660,34 +875,96 @@ fn range_query_reversed() {
     let table = read_txn.open_table(U64_TABLE).unwrap();
     let mut iter = table.range(3..7).unwrap().rev();
     for i in (3..7u64).rev() {
-        let (key, value) = iter.next().unwrap();
+        let (key, value) = iter.next().unwrap().unwrap();
         assert_eq!(i, key);
         assert_eq!(i, value);
     }
     assert!(iter.next().is_none());
 
-    // Test reversing multiple times
+    // Test iterating from both ends
     let mut iter = table.range(3..7).unwrap();
-    let (key, _) = iter.next().unwrap();
+    let (key, _) = iter.next().unwrap().unwrap();
     assert_eq!(3, key);
 
-    iter = iter.rev();
-    let (key, _) = iter.next().unwrap();
+    let (key, _) = iter.next_back().unwrap().unwrap();
     assert_eq!(6, key);
-    let (key, _) = iter.next().unwrap();
+    let (key, _) = iter.next_back().unwrap().unwrap();
     assert_eq!(5, key);
 
-    iter = iter.rev();
-    let (key, _) = iter.next().unwrap();
+    let (key, _) = iter.next().unwrap().unwrap();
     assert_eq!(4, key);
 
     assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+}
+
+#[test]
+fn range_iterator_adapters() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&i, &(i * 2)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut expected = 0;
+    for entry in table.range(0..).unwrap() {
+        let (key, value) = entry.unwrap();
+        assert_eq!(expected, key);
+        assert_eq!(expected * 2, value);
+        expected += 1;
+    }
+    assert_eq!(1000, expected);
+
+    let keys: Vec<u64> = table
+        .range(10..)
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .take(3)
+        .collect();
+    assert_eq!(vec![10, 11, 12], keys);
+
+    let keys: Vec<u64> = table
+        .range(..=990)
+        .unwrap()
+        .rev()
+        .step_by(10)
+        .map(|entry| entry.unwrap().0)
+        .take(3)
+        .collect();
+    assert_eq!(vec![990, 980, 970], keys);
+
+    // Both ends must meet exactly once, even when they cross many pages
+    let mut iter = table.range(100..900).unwrap();
+    let mut front = 100;
+    let mut back = 900;
+    while let Some(entry) = iter.next() {
+        assert_eq!(front, entry.unwrap().0);
+        front += 1;
+        for _ in 0..3 {
+            if let Some(entry) = iter.next_back() {
+                back -= 1;
+                assert_eq!(back, entry.unwrap().0);
+            }
+        }
+    }
+    assert_eq!(front, back);
+    assert!(iter.next_back().is_none());
+
+    let (start, end) = (500, 100);
+    assert!(table.range(start..end).unwrap().next().is_none());
 }
 
 #[test]
 fn alias_table() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let write_txn = db.begin_write().unwrap();
     let table = write_txn.open_table(SLICE_TABLE).unwrap();
@@ -702,7 +979,7 @@ fn alias_table() {
 #[test]
 fn delete_table() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let y_def: MultimapTableDefinition<[u8], [u8]> = MultimapTableDefinition::new("y");
 
@@ -732,7 +1009,7 @@ fn delete_table() {
 #[test]
 fn leaked_write() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let write_txn = db.begin_write().unwrap();
     drop(write_txn);
@@ -749,7 +1026,7 @@ fn non_page_size_multiple() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
     let db_size = 1024 * 1024 + 1;
-    let db = unsafe { Database::create(tmpfile.path(), db_size).unwrap() };
+    let db = Database::create(tmpfile.path(), db_size).unwrap();
     let txn = db.begin_write().unwrap();
     let key = vec![0; 1024];
     let value = vec![0; 1];
@@ -768,7 +1045,7 @@ fn non_page_size_multiple() {
 fn does_not_exist() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
     fs::remove_file(&tmpfile.path()).unwrap();
-    let result = unsafe { Database::open(tmpfile.path()) };
+    let result = Database::open(tmpfile.path());
     if let Err(Error::Io(e)) = result {
         assert!(matches!(e.kind(), ErrorKind::NotFound));
     } else {
@@ -777,7 +1054,7 @@ fn does_not_exist() {
 
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
 
-    let result = unsafe { Database::open(tmpfile.path()) };
+    let result = Database::open(tmpfile.path());
     if let Err(Error::Io(e)) = result {
         assert!(matches!(e.kind(), ErrorKind::InvalidData));
     } else {
@@ -788,7 +1065,7 @@ fn does_not_exist() {
 #[test]
 fn wrong_types() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let definition: TableDefinition<u32, u32> = TableDefinition::new("x");
     let wrong_definition: TableDefinition<u64, u64> = TableDefinition::new("x");
@@ -837,12 +1114,10 @@ fn tree_balance() {
     let key_size = 100;
     // Set the page size so that exactly 9 keys will fit
     let page_size = 1024;
-    let db = unsafe {
-        Database::builder()
-            .set_page_size(page_size)
-            .create(tmpfile.path(), 16 * 1024 * 1024)
-            .unwrap()
-    };
+    let db = Database::builder()
+        .set_page_size(page_size)
+        .create(tmpfile.path(), 16 * 1024 * 1024)
+        .unwrap();
     let txn = db.begin_write().unwrap();
 
     let elements = (EXPECTED_ORDER / 2).pow(2) as usize - num_internal_entries;
@@ -889,3 +1164,385 @@ fn tree_balance() {
         expected
     );
 }
+
+#[test]
+fn check_integrity() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.tables(), 0);
+
+    let write_txn = db.begin_write().unwrap();
+    assert!(matches!(
+        db.check_integrity(),
+        Err(Error::WriteTransactionInProgress)
+    ));
+    write_txn.abort().unwrap();
+
+    let multimap_def: MultimapTableDefinition<[u8], [u8]> = MultimapTableDefinition::new("mm");
+    let pairs = gen_data(1000, 16, 100);
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        let mut u64_table = write_txn.open_table(U64_TABLE).unwrap();
+        let mut multimap = write_txn.open_multimap_table(multimap_def).unwrap();
+        for (i, (key, value)) in pairs.iter().enumerate() {
+            table.insert(key, value).unwrap();
+            u64_table.insert(&(i as u64), &(i as u64)).unwrap();
+            multimap.insert(&key[..4], value).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // Free some pages, while a read transaction keeps them from being reclaimed
+    let read_txn = db.begin_read().unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, _) in pairs.iter().take(500) {
+            table.remove(key).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+    assert_eq!(report.tables(), 3);
+    // The multimap table's ordering can't be checked
+    assert_eq!(report.unordered_tables(), ["mm"]);
+    assert!(report.referenced_pages() > 0);
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    assert!(write_txn.delete_table(U64_TABLE).unwrap());
+    write_txn.commit().unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+    assert_eq!(report.tables(), 2);
+
+    // Every non-generic key type, and byte arrays, have their ordering checked
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn
+            .open_table(TableDefinition::<String, u64>::new("string"))
+            .unwrap();
+        table.insert(&"a".to_string(), &0).unwrap();
+        let mut table = write_txn
+            .open_table(TableDefinition::<[u8; 4], u64>::new("array"))
+            .unwrap();
+        table.insert(&[1; 4], &0).unwrap();
+        let mut table = write_txn
+            .open_table(TableDefinition::<f64, u64>::new("float"))
+            .unwrap();
+        table.insert(&-1.5, &0).unwrap();
+        let mut table = write_txn
+            .open_table(TableDefinition::<(u64, u64), u64>::new("tuple"))
+            .unwrap();
+        table.insert(&(1, 2), &0).unwrap();
+    }
+    write_txn.commit().unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+    assert_eq!(report.tables(), 6);
+    let mut unordered = report.unordered_tables().to_vec();
+    unordered.sort();
+    assert_eq!(unordered, ["mm", "tuple"]);
+}
+
+#[test]
+fn compact() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let mut db = Database::create(tmpfile.path(), 1024 * 1024 * 1024).unwrap();
+
+    let big_pairs = gen_data(1000, 16, 2_000);
+    let small_pairs = gen_data(100, 16, 100);
+    for chunk in big_pairs.chunks(100) {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+            for (key, value) in chunk {
+                table.insert(key, value).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+    // Written last, so that its pages are at the end of the file
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE2).unwrap();
+        for (key, value) in small_pairs.iter() {
+            table.insert(key, value).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    write_txn.delete_table(SLICE_TABLE).unwrap();
+    write_txn.commit().unwrap();
+
+    let original_len = fs::metadata(tmpfile.path()).unwrap().len();
+    // The file can't be truncated while it's open read-only, but the pages are still moved
+    let reader = Database::open_read_only(tmpfile.path()).unwrap();
+    assert!(!db.compact().unwrap());
+    assert_eq!(fs::metadata(tmpfile.path()).unwrap().len(), original_len);
+    let read_txn = reader.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE2).unwrap();
+    for (key, value) in small_pairs.iter() {
+        assert_eq!(table.get(key).unwrap().unwrap(), value.as_slice());
+    }
+    drop(table);
+    drop(read_txn);
+    drop(reader);
+
+    assert!(db.compact().unwrap());
+    let compacted_len = fs::metadata(tmpfile.path()).unwrap().len();
+    assert!(compacted_len < original_len / 2);
+    // Nothing left to do
+    assert!(!db.compact().unwrap());
+
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE2).unwrap();
+    for (key, value) in small_pairs.iter() {
+        assert_eq!(table.get(key).unwrap().unwrap(), value.as_slice());
+    }
+    drop(table);
+    drop(read_txn);
+    drop(db);
+
+    let db = Database::open(tmpfile.path()).unwrap();
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE2).unwrap();
+    assert_eq!(table.len().unwrap(), small_pairs.len());
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, value) in big_pairs.iter().take(100) {
+            table.insert(key, value).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn savepoint() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let pairs = gen_data(500, 16, 100);
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, value) in pairs.iter() {
+            table.insert(key, value).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let mut write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"before", b"savepoint").unwrap();
+    }
+    let savepoint = write_txn.savepoint();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, _) in pairs.iter() {
+            table.remove(key).unwrap();
+        }
+        table.insert(b"after", b"savepoint").unwrap();
+    }
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE2).unwrap();
+        table.insert(b"hello", b"world").unwrap();
+    }
+    let nested = write_txn.savepoint();
+    write_txn.delete_table(SLICE_TABLE2).unwrap();
+    write_txn.restore_savepoint(&nested).unwrap();
+    assert_eq!(write_txn.list_tables().unwrap().count(), 2);
+
+    write_txn.restore_savepoint(&savepoint).unwrap();
+    // Restoring the outer savepoint invalidates the nested one
+    assert!(matches!(
+        write_txn.restore_savepoint(&nested),
+        Err(Error::InvalidSavepoint)
+    ));
+    {
+        let table = write_txn.open_table(SLICE_TABLE).unwrap();
+        assert_eq!(b"savepoint", table.get(b"before").unwrap().unwrap());
+        assert!(table.get(b"after").unwrap().is_none());
+        for (key, value) in pairs.iter() {
+            assert_eq!(table.get(key).unwrap().unwrap(), value.as_slice());
+        }
+    }
+    assert_eq!(write_txn.list_tables().unwrap().count(), 1);
+    write_txn.commit().unwrap();
+
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+
+    let mut write_txn = db.begin_write().unwrap();
+    assert!(matches!(
+        write_txn.restore_savepoint(&savepoint),
+        Err(Error::InvalidSavepoint)
+    ));
+    write_txn.abort().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), pairs.len() + 1);
+}
+
+#[test]
+fn snapshots() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..100 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    write_txn.create_snapshot("v1").unwrap();
+    assert!(matches!(
+        write_txn.create_snapshot("v1"),
+        Err(Error::SnapshotAlreadyExists(_))
+    ));
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..100 {
+            table.insert(&i, &(i + 1)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // Overwrite the data many times, so that the pages of the snapshot would be reused if
+    // they weren't retained
+    for _ in 0..10 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            for i in 0..100 {
+                table.remove(&i).unwrap();
+            }
+            for i in 0..100 {
+                table.insert(&i, &(i + 2)).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+    drop(db);
+
+    let db = Database::open(tmpfile.path()).unwrap();
+    let read_txn = db.begin_read().unwrap();
+    assert_eq!(
+        read_txn.list_snapshots().unwrap().collect::<Vec<_>>(),
+        vec!["v1".to_string()]
+    );
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.get(&0).unwrap().unwrap(), 2);
+    drop(table);
+    drop(read_txn);
+
+    let snapshot_txn = db.begin_read_snapshot("v1").unwrap();
+    let write_txn = db.begin_write().unwrap();
+    assert!(write_txn.delete_snapshot("v1").unwrap());
+    assert!(!write_txn.delete_snapshot("v1").unwrap());
+    assert_eq!(write_txn.list_snapshots().unwrap().count(), 0);
+    write_txn.commit().unwrap();
+    db.begin_write().unwrap().commit().unwrap();
+
+    // The open transaction still sees the deleted snapshot
+    let table = snapshot_txn.open_table(U64_TABLE).unwrap();
+    for i in 0..100 {
+        assert_eq!(table.get(&i).unwrap().unwrap(), i);
+    }
+    drop(table);
+    drop(snapshot_txn);
+
+    assert!(matches!(
+        db.begin_read_snapshot("v1"),
+        Err(Error::SnapshotDoesNotExist(_))
+    ));
+    db.begin_write().unwrap().commit().unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+}
+
+#[test]
+fn backup() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 16 * 1024 * 1024).unwrap();
+    let multimap_def: MultimapTableDefinition<[u8], [u8]> = MultimapTableDefinition::new("mm");
+    let pairs = gen_data(1000, 16, 2000);
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, value) in pairs.iter() {
+            table.insert(key, value).unwrap();
+        }
+        let mut multimap = write_txn.open_multimap_table(multimap_def).unwrap();
+        multimap.insert(b"hello", b"world").unwrap();
+        multimap.insert(b"hello", b"world2").unwrap();
+    }
+    write_txn.commit().unwrap();
+    // Free most of the file, so that the backup is smaller than the original
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for (key, _) in pairs.iter().skip(10) {
+            table.remove(key).unwrap();
+        }
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.insert(&0, &1).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    // Writes in progress are not included
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.insert(&0, &2).unwrap();
+    }
+    let backup_dir = tempfile::tempdir().unwrap();
+    let backup_path = backup_dir.path().join("backup.redb");
+    db.backup_to(&backup_path).unwrap();
+    write_txn.commit().unwrap();
+    assert!(db.backup_to(&backup_path).is_err());
+    // The temporary file is moved into place, or removed
+    assert_eq!(fs::read_dir(backup_dir.path()).unwrap().count(), 1);
+    // Fails without leaving a partial backup, since the directory doesn't exist
+    let missing_path = backup_dir.path().join("missing").join("backup.redb");
+    assert!(db.backup_to(&missing_path).is_err());
+    assert!(!missing_path.exists());
+
+    let backup = Database::open(&backup_path).unwrap();
+    let report = backup.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.violations());
+    assert_eq!(report.tables(), 3);
+    assert!(
+        fs::metadata(&backup_path).unwrap().len() < fs::metadata(tmpfile.path()).unwrap().len()
+    );
+
+    let read_txn = backup.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10);
+    for (key, value) in pairs.iter().take(10) {
+        assert_eq!(table.get(key).unwrap().unwrap(), value.as_slice());
+    }
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.get(&0).unwrap().unwrap(), 1);
+    let multimap = read_txn.open_multimap_table(multimap_def).unwrap();
+    let mut iter = multimap.get(b"hello").unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), b"world");
+    assert_eq!(iter.next().unwrap().unwrap(), b"world2");
+    assert!(iter.next().is_none());
+}
@@ -5,22 +5,17 @@ const SLICE_TABLE: MultimapTableDefinition<[u8], [u8]> =
     MultimapTableDefinition::new("slice_to_slice");
 
 fn get_vec(table: &impl ReadableMultimapTable<[u8], [u8]>, key: &[u8]) -> Vec<Vec<u8>> {
-    let mut result = vec![];
-    let mut iter = table.get(key).unwrap();
-    loop {
-        let item = iter.next();
-        if let Some(item_value) = item {
-            result.push(item_value.to_vec());
-        } else {
-            return result;
-        }
-    }
+    table
+        .get(key)
+        .unwrap()
+        .map(|item| item.unwrap().to_vec())
+        .collect()
 }
 
 #[test]
 fn len() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_multimap_table(SLICE_TABLE).unwrap();
@@ -38,7 +33,7 @@ fn len() {
 #[test]
 fn is_empty() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
@@ -55,7 +50,7 @@ fn is_empty() {
 #[test]
 fn insert() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_multimap_table(SLICE_TABLE).unwrap();
@@ -76,7 +71,7 @@ fn insert() {
 #[test]
 fn range_query() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_multimap_table(SLICE_TABLE).unwrap();
@@ -101,7 +96,7 @@ fn range_query() {
     let end = b"1".as_ref();
     let mut iter = table.range(start..=end).unwrap();
     for i in 0..10u8 {
-        let (key, value) = iter.next().unwrap();
+        let (key, value) = iter.next().unwrap().unwrap();
         if i < 5 {
             assert_eq!(b"0", key);
         } else {
@@ -110,12 +105,25 @@ fn range_query() {
         assert_eq!(&[i], value);
     }
     assert!(iter.next().is_none());
+
+    let mut iter = table.range(start..=end).unwrap().rev();
+    for i in (0..10u8).rev() {
+        let (_, value) = iter.next().unwrap().unwrap();
+        assert_eq!(&[i], value);
+    }
+    assert!(iter.next().is_none());
+
+    let mut values = table.get(b"2").unwrap();
+    assert_eq!(&[10], values.next().unwrap().unwrap());
+    assert_eq!(&[14], values.next_back().unwrap().unwrap());
+    let rest: Vec<u8> = values.map(|value| value.unwrap()[0]).collect();
+    assert_eq!(vec![11, 12, 13], rest);
 }
 
 #[test]
 fn delete() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_multimap_table(SLICE_TABLE).unwrap();
@@ -152,8 +160,8 @@ fn delete() {
     {
         let mut table = write_txn.open_multimap_table(SLICE_TABLE).unwrap();
         let mut iter = table.remove_all(b"hello").unwrap();
-        assert_eq!(b"world", iter.next().unwrap());
-        assert_eq!(b"world3", iter.next().unwrap());
+        assert_eq!(b"world", iter.next().unwrap().unwrap());
+        assert_eq!(b"world3", iter.next().unwrap().unwrap());
         assert!(iter.next().is_none());
     }
     write_txn.commit().unwrap();
@@ -168,7 +176,7 @@ fn delete() {
 #[test]
 fn wrong_types() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
 
     let definition: MultimapTableDefinition<u32, u32> = MultimapTableDefinition::new("x");
     let wrong_definition: MultimapTableDefinition<u64, u64> = MultimapTableDefinition::new("x");
@@ -195,7 +203,7 @@ fn wrong_types() {
 #[test]
 fn reopen_table() {
     let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
-    let db = unsafe { Database::create(tmpfile.path(), 1024 * 1024).unwrap() };
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_multimap_table(SLICE_TABLE).unwrap();
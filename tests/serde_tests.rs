@@ -0,0 +1,110 @@
+#![cfg(feature = "serde")]
+
+use redb::{Bincode, Database, Error, Json, ReadableTable, RedbValue, TableDefinition};
+use serde_crate::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tempfile::NamedTempFile;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Record {
+    name: String,
+    scores: Vec<u32>,
+    tags: BTreeMap<String, Option<i64>>,
+}
+
+fn record(name: &str) -> Record {
+    let mut tags = BTreeMap::new();
+    tags.insert("a".to_string(), Some(-1));
+    tags.insert("b".to_string(), None);
+    Record {
+        name: name.to_string(),
+        scores: vec![1, 2, 3],
+        tags,
+    }
+}
+
+#[test]
+fn bincode_type() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<str, Bincode<Record>> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert("hello", &Bincode(record("hello"))).unwrap();
+        table.insert("world", &Bincode(record("world"))).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(record("hello"), table.get("hello").unwrap().unwrap());
+    let (key, value) = table.iter().unwrap().next_back().unwrap().unwrap();
+    assert_eq!("world", key);
+    assert_eq!(record("world"), value);
+}
+
+#[test]
+fn json_type() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let definition: TableDefinition<u64, Json<Record>> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(&1, &Json(record("hello"))).unwrap();
+        let old = table.insert(&1, &Json(record("world"))).unwrap();
+        assert_eq!(record("hello"), old.unwrap().to_value().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(record("world"), table.get(&1).unwrap().unwrap());
+    assert!(matches!(
+        Json::<Record>::try_from_bytes(b"{}"),
+        Err(Error::Corrupted(_))
+    ));
+}
+
+#[test]
+fn inner_type_mismatch() {
+    let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+    let db = Database::create(tmpfile.path(), 1024 * 1024).unwrap();
+
+    let bincode_definition: TableDefinition<u64, Bincode<u64>> = TableDefinition::new("x");
+    let json_definition: TableDefinition<u64, Json<u64>> = TableDefinition::new("y");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(bincode_definition).unwrap();
+        table.insert(&1, &Bincode(u64::MAX)).unwrap();
+        let mut table = write_txn.open_table(json_definition).unwrap();
+        table.insert(&1, &Json(1)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    assert!(matches!(
+        read_txn.open_table(TableDefinition::<u64, Bincode<i64>>::new("x")),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    assert!(matches!(
+        read_txn.open_table(TableDefinition::<u64, Json<String>>::new("y")),
+        Err(Error::TableTypeMismatch(_))
+    ));
+    assert_eq!(
+        u64::MAX,
+        read_txn
+            .open_table(bincode_definition)
+            .unwrap()
+            .get(&1)
+            .unwrap()
+            .unwrap()
+    );
+}